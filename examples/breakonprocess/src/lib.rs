@@ -1,15 +1,14 @@
 // Justin Moore - March 30 2024
-use std::cell::{OnceCell, RefCell};
-use std::collections::HashMap;
+use std::cell::OnceCell;
+use std::rc::Rc;
 use std::sync::Once;
 
 use anyhow::{Context, Result};
-use dbgeng::breakpoint::{BreakpointFlags, BreakpointType, DebugBreakpoint};
+use dbgeng::breakpoint::{BreakpointManager, DebugBreakpoint};
 use dbgeng::client::DebugClient;
-use dbgeng::events::{DebugInstruction, EventCallbacks};
-use dbgeng::exception::ExceptionInfo;
+use dbgeng::events::DebugInstruction;
 use dbgeng::{dlogln, export_cmd};
-use windows::core::{GUID, HRESULT};
+use windows::core::HRESULT;
 use windows::Win32::Foundation::S_OK;
 use windows::Win32::System::Diagnostics::Debug::Extensions::{
     DEBUG_NOTIFY_SESSION_ACCESSIBLE, DEBUG_STACK_FRAME,
@@ -41,58 +40,7 @@ fn read_unicode_string(client: &DebugClient, addr: u64) -> Result<String> {
 
 thread_local! {
     static CLIENT: OnceCell<DebugClient> = OnceCell::new();
-    static BREAKPOINTS: bp::CallbackBreakpoints = bp::CallbackBreakpoints::new();
-}
-
-mod bp {
-    use super::*;
-
-    struct CallbackBreakpointData {
-        bp: DebugBreakpoint,
-        callback: Box<dyn FnMut(&DebugClient, &DebugBreakpoint) -> Result<DebugInstruction>>,
-    }
-
-    pub struct CallbackBreakpoints {
-        inner: RefCell<HashMap<GUID, CallbackBreakpointData>>,
-    }
-
-    impl CallbackBreakpoints {
-        pub fn new() -> Self {
-            Self {
-                inner: RefCell::new(HashMap::new()),
-            }
-        }
-
-        pub fn insert<
-            T: FnMut(&DebugClient, &DebugBreakpoint) -> Result<DebugInstruction> + 'static,
-        >(
-            &self,
-            bp: DebugBreakpoint,
-            cb: T,
-        ) {
-            self.inner
-                .borrow_mut()
-                .insert(bp.guid().unwrap(), CallbackBreakpointData {
-                    bp,
-                    callback: Box::new(cb),
-                });
-        }
-
-        pub fn call(&self, client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
-            let mut inner = self.inner.borrow_mut();
-            if let Some(data) = inner.get_mut(&bp.guid().unwrap()) {
-                match (data.callback)(client, bp) {
-                    Ok(i) => i,
-                    Err(e) => {
-                        let _ = dbgeng::dlogln!(client, "Error in breakpoint callback: {e:?}");
-                        DebugInstruction::NoChange
-                    }
-                }
-            } else {
-                DebugInstruction::NoChange
-            }
-        }
-    }
+    static BREAKPOINTS: OnceCell<Rc<BreakpointManager>> = OnceCell::new();
 }
 
 mod cmd {
@@ -102,19 +50,11 @@ mod cmd {
         let mut args = args.split_whitespace();
         let process_name = args.next().context("missing process name")?.to_string();
 
-        CLIENT.with(|c| -> anyhow::Result<()> {
-            let client: &DebugClient = c.get().context("client not set")?;
-
-            let bp = client.add_breakpoint(BreakpointType::Code, None)?;
-
-            bp.set_offset_expression("nt!NtCreateUserProcess")?;
-            bp.set_flags(BreakpointFlags::ENABLED)?;
-
-            BREAKPOINTS.with(|breakpoints| {
-                breakpoints.insert(bp, move |client, bp| -> Result<DebugInstruction> {
-                    bpproc_create(client, bp, process_name.clone())
-                });
-            });
+        BREAKPOINTS.with(|b| -> anyhow::Result<()> {
+            let breakpoints = b.get().context("breakpoints not set")?;
+            breakpoints.add("nt!NtCreateUserProcess", move |client, bp| {
+                bpproc_create(client, bp, process_name.clone())
+            })?;
 
             Ok(())
         })?;
@@ -126,17 +66,6 @@ mod cmd {
     export_cmd!(breakonprocess, break_on_process);
 }
 
-struct PluginEventCallbacks;
-impl EventCallbacks for PluginEventCallbacks {
-    fn breakpoint(&self, client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
-        BREAKPOINTS.with(|breakpoints| breakpoints.call(client, bp))
-    }
-
-    fn exception(&self, _client: &DebugClient, _ei: &ExceptionInfo) -> DebugInstruction {
-        DebugInstruction::NoChange
-    }
-}
-
 fn bpproc_create(
     client: &DebugClient,
     bp: &DebugBreakpoint,
@@ -184,17 +113,16 @@ fn bpproc_create(
         .context("failed to read stack")?;
     let ra = stack[2].ReturnOffset;
 
-    let bp = client.add_breakpoint(BreakpointType::Code, None)?;
-    bp.set_offset(ra)
-        .context("failed to set postcreate breakpoint addr")?;
-    bp.set_flags(BreakpointFlags::ENABLED)
-        .context("failed to set postcreate breakpoint flags")?;
+    BREAKPOINTS.with(|b| -> Result<()> {
+        let breakpoints = b.get().context("breakpoints not set")?;
+        breakpoints
+            .add(ra, move |client, _bp| {
+                bpproc_postcreate(client, p_proc_handle, p_thrd_handle)
+            })
+            .context("failed to set postcreate breakpoint")?;
 
-    BREAKPOINTS.with(|breakpoints| {
-        breakpoints.insert(bp, move |client, _bp| {
-            bpproc_postcreate(client, p_proc_handle, p_thrd_handle)
-        });
-    });
+        Ok(())
+    })?;
 
     Ok(DebugInstruction::NoChange)
 }
@@ -214,7 +142,12 @@ fn bpproc_postcreate(
 
 fn init_accessible(client: DebugClient) -> anyhow::Result<()> {
     dbgeng::dlogln!(client, "Extension loaded")?;
-    client.set_event_callbacks(PluginEventCallbacks)?;
+    let breakpoints = Rc::new(BreakpointManager::new(client.clone()));
+    client.set_event_callbacks(Rc::clone(&breakpoints))?;
+    BREAKPOINTS.with(|b| {
+        b.set(breakpoints)
+            .map_err(|_e| anyhow::anyhow!("Failed to set the breakpoints"))
+    })?;
 
     CLIENT.with(|c| {
         c.set(client)
@@ -7,10 +7,9 @@ use dbgeng::breakpoint::BreakpointFlags;
 use dbgeng::{
     breakpoint::{BreakpointType, DebugBreakpoint}, 
     client::DebugClient, 
-    events::{DebugInstruction, EventCallbacks}, 
+    events::{DebugInstruction, EventCallbacks, ExecutionStatus}, 
     exception::ExceptionInfo
 };
-use windows::Win32::System::Diagnostics::Debug::Extensions::{DEBUG_CES_EXECUTION_STATUS, DEBUG_STATUS_BREAK};
 use windows::Win32::System::Memory::{VirtualProtectEx, PAGE_EXECUTE_READWRITE, PAGE_PROTECTION_FLAGS, PAGE_READWRITE};
 use windows::Win32::System::Threading::{OpenProcess, PROCESS_ALL_ACCESS};
 use windows::Win32::Foundation::{CloseHandle, EXCEPTION_ACCESS_VIOLATION};
@@ -25,9 +24,7 @@ fn dump_dynamic_code(client: &DebugClient, mem_alloc: &AllocatedMemory) -> anyho
     MEMORY_REGIONS.with(|regions| { 
         let file_name = regions.get_dump_file(mem_alloc)?;
         if !file_name.is_file() {
-            let mut buffer = vec![0; mem_alloc.size as usize];
-            client.read_virtual(mem_alloc.address, &mut buffer[..])?;        
-            fs::write(&file_name, buffer)?;
+            client.dump_range_to_file(mem_alloc.address, mem_alloc.size, &file_name)?;
             dbgeng::dlogln!(client, "Dumped allocated memory to file: {}", file_name.display())?;            
         }
         Ok(())
@@ -196,7 +193,8 @@ impl EventCallbacks for PluginEventCallbacks {
 
     fn change_engine_state(&self, client: &DebugClient, flags: u32, argument: u64) {  
         let exception_pending = self.exception_handled.borrow().is_positive();
-        if flags == DEBUG_CES_EXECUTION_STATUS && argument as u32 == DEBUG_STATUS_BREAK && exception_pending {
+        let status = ExecutionStatus::from_engine_state(flags, argument);
+        if status == Some(ExecutionStatus::Break) && exception_pending {
             let old_value = self.exception_handled.replace_with(|&mut old| old - 1);        
             if old_value > 0 {
                 let _ = dbgeng::dlogln!(client, "Continue execution with 'g'");
@@ -0,0 +1,410 @@
+//! This contains an allocation tracker: it hooks allocator APIs (see
+//! [`crate::hooks`]) to keep track of the memory allocated by the target, its
+//! protection, and to find the allocation owning an address.
+//!
+//! ```ignore
+//! let tracker = AllocationTracker::new(CallConv::Win64);
+//! let hooks = Rc::new(HookManager::new(client.clone()));
+//! tracker.install(&hooks, Allocator::VirtualAlloc)?;
+//! client.set_event_callbacks(Rc::clone(&hooks))?;
+//! // ...
+//! if let Some(allocation) = tracker.allocation(client.ip()?) {
+//!     // Executing dynamically allocated code.
+//! }
+//! ```
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use anyhow::Result;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::core::GUID;
+use windows::Win32::System::Memory::MEM_RELEASE;
+
+use crate::callconv::CallConv;
+use crate::hooks::{Hook, HookContext, HookManager};
+
+/// A family of allocator APIs tracked by an [`AllocationTracker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Allocator {
+    /// `VirtualAlloc`, `VirtualFree` and `VirtualProtect`.
+    VirtualAlloc,
+    /// `NtAllocateVirtualMemory`, `NtFreeVirtualMemory` and
+    /// `NtProtectVirtualMemory`, for the current process.
+    NtAllocateVirtualMemory,
+    /// `RtlAllocateHeap` and `RtlFreeHeap`, which back `HeapAlloc` and
+    /// `HeapFree`.
+    HeapAlloc,
+}
+
+impl Allocator {
+    /// Get the functions allocating, freeing and protecting memory.
+    fn functions(&self) -> (&'static str, &'static str, Option<&'static str>) {
+        match self {
+            Self::VirtualAlloc => (
+                "KERNELBASE!VirtualAlloc",
+                "KERNELBASE!VirtualFree",
+                Some("KERNELBASE!VirtualProtect"),
+            ),
+            Self::NtAllocateVirtualMemory => (
+                "ntdll!NtAllocateVirtualMemory",
+                "ntdll!NtFreeVirtualMemory",
+                Some("ntdll!NtProtectVirtualMemory"),
+            ),
+            Self::HeapAlloc => ("ntdll!RtlAllocateHeap", "ntdll!RtlFreeHeap", None),
+        }
+    }
+}
+
+/// An allocation made by the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Allocation {
+    /// The address of the allocation.
+    pub address: u64,
+    /// The size of the allocation in bytes.
+    pub size: u64,
+    /// The current protection of the allocation (`PAGE_*`); 0 for heap
+    /// allocations.
+    pub protection: u32,
+    /// The API that made the allocation.
+    pub allocator: Allocator,
+    /// The address the allocator returned to.
+    pub return_address: u64,
+}
+
+impl Allocation {
+    /// Address of the first byte past the end of the allocation.
+    pub fn end(&self) -> u64 {
+        self.address.saturating_add(self.size)
+    }
+
+    /// Is `addr` inside the allocation?
+    pub fn contains(&self, addr: u64) -> bool {
+        (self.address..self.end()).contains(&addr)
+    }
+}
+
+/// The live allocations, by address.
+#[derive(Default)]
+struct AllocationMap {
+    allocations: BTreeMap<u64, Allocation>,
+}
+
+impl AllocationMap {
+    fn insert(&mut self, allocation: Allocation) {
+        self.allocations.insert(allocation.address, allocation);
+    }
+
+    /// Get the allocation containing `addr`.
+    fn find(&self, addr: u64) -> Option<&Allocation> {
+        self.allocations
+            .range(..=addr)
+            .next_back()
+            .map(|(_, allocation)| allocation)
+            .filter(|allocation| allocation.contains(addr))
+    }
+
+    /// Forget the allocation starting at `addr`.
+    fn remove(&mut self, addr: u64) -> Option<Allocation> {
+        self.allocations.remove(&addr)
+    }
+
+    /// Update the protection of the allocation containing `addr`.
+    fn protect(&mut self, addr: u64, protection: u32) {
+        let Some(address) = self.find(addr).map(|allocation| allocation.address) else {
+            return;
+        };
+
+        if let Some(allocation) = self.allocations.get_mut(&address) {
+            allocation.protection = protection;
+        }
+    }
+}
+
+/// The arguments of an allocator call that hasn't returned yet.
+enum PendingCall {
+    /// An allocation of `size` bytes; for the native API, `address` and `size`
+    /// point to where the results are written.
+    Allocate {
+        address: u64,
+        size: u64,
+        protection: u32,
+    },
+    /// A protection change of the memory at `address`, which points to the
+    /// address for the native API.
+    Protect { address: u64, protection: u32 },
+}
+
+/// The state shared by an [`AllocationTracker`] and its hooks.
+#[derive(Default)]
+struct TrackerState {
+    allocations: AllocationMap,
+    /// The pending calls, by engine thread ID and stack pointer at function
+    /// entry.
+    pending: HashMap<(u32, u64), PendingCall>,
+}
+
+/// The `NtCurrentProcess()` pseudo-handle, truncated to 32 bits so that it
+/// compares equal on 32-bit targets.
+const CURRENT_PROCESS: u32 = u32::MAX;
+
+/// Tracks the allocations of the target.
+pub struct AllocationTracker {
+    state: Rc<RefCell<TrackerState>>,
+    conv: CallConv,
+}
+
+impl AllocationTracker {
+    /// Create a tracker reading the arguments according to the calling
+    /// convention `conv`.
+    pub fn new(conv: CallConv) -> Self {
+        Self {
+            state: Rc::default(),
+            conv,
+        }
+    }
+
+    /// Install the hooks tracking `allocator` in `hooks`; the returned GUIDs
+    /// identify them.
+    pub fn install(&self, hooks: &HookManager, allocator: Allocator) -> Result<Vec<GUID>> {
+        let (allocate, free, protect) = allocator.functions();
+        let mut guids = vec![
+            hooks.install(self.allocate_hook(allocate, allocator))?,
+            hooks.install(self.free_hook(free, allocator))?,
+        ];
+
+        if let Some(protect) = protect {
+            guids.push(hooks.install(self.protect_hook(protect, allocator))?);
+        }
+
+        Ok(guids)
+    }
+
+    /// Get the allocation containing `addr`, if any.
+    pub fn allocation(&self, addr: u64) -> Option<Allocation> {
+        self.state.borrow().allocations.find(addr).copied()
+    }
+
+    /// Get the live allocations, sorted by address.
+    pub fn allocations(&self) -> Vec<Allocation> {
+        self.state
+            .borrow()
+            .allocations
+            .allocations
+            .values()
+            .copied()
+            .collect()
+    }
+
+    /// Forget every allocation.
+    pub fn clear(&self) {
+        let mut state = self.state.borrow_mut();
+        state.allocations = AllocationMap::default();
+        state.pending.clear();
+    }
+
+    fn allocate_hook(&self, target: &str, allocator: Allocator) -> Hook {
+        let conv = self.conv;
+        let enter_state = Rc::clone(&self.state);
+        let exit_state = Rc::clone(&self.state);
+
+        Hook::on(target)
+            .enter(move |ctx| {
+                let call = match allocator {
+                    Allocator::VirtualAlloc => {
+                        let args = ctx.args(4, conv)?;
+                        PendingCall::Allocate {
+                            address: 0,
+                            size: args[1],
+                            protection: args[3] as u32,
+                        }
+                    }
+                    Allocator::NtAllocateVirtualMemory => {
+                        let args = ctx.args(6, conv)?;
+                        if args[0] as u32 != CURRENT_PROCESS {
+                            return Ok(());
+                        }
+
+                        PendingCall::Allocate {
+                            address: args[1],
+                            size: args[3],
+                            protection: args[5] as u32,
+                        }
+                    }
+                    Allocator::HeapAlloc => {
+                        let args = ctx.args(3, conv)?;
+                        PendingCall::Allocate {
+                            address: 0,
+                            size: args[2],
+                            protection: 0,
+                        }
+                    }
+                };
+
+                enter_state.borrow_mut().pending.insert(key(ctx), call);
+
+                Ok(())
+            })
+            .exit(move |ctx| {
+                let Some(PendingCall::Allocate {
+                    address,
+                    size,
+                    protection,
+                }) = exit_state.borrow_mut().pending.remove(&key(ctx))
+                else {
+                    return Ok(());
+                };
+
+                let client = ctx.client();
+                let ret = ctx.return_value()?;
+                let (address, size) = match allocator {
+                    Allocator::VirtualAlloc | Allocator::HeapAlloc if ret != 0 => (ret, size),
+                    // N.B: The native API returns an `NTSTATUS`, and writes the
+                    // address and the size, rounded to pages, to its arguments.
+                    Allocator::NtAllocateVirtualMemory if ret as i32 >= 0 => {
+                        (client.read_pointer(address)?, client.read_pointer(size)?)
+                    }
+                    _ => return Ok(()),
+                };
+
+                exit_state.borrow_mut().allocations.insert(Allocation {
+                    address,
+                    size,
+                    protection,
+                    allocator,
+                    return_address: ctx.return_address(),
+                });
+
+                Ok(())
+            })
+    }
+
+    fn free_hook(&self, target: &str, allocator: Allocator) -> Hook {
+        let conv = self.conv;
+        let state = Rc::clone(&self.state);
+
+        Hook::on(target).enter(move |ctx| {
+            let address = match allocator {
+                Allocator::VirtualAlloc => {
+                    let args = ctx.args(3, conv)?;
+                    if args[2] as u32 != MEM_RELEASE.0 {
+                        return Ok(());
+                    }
+
+                    args[0]
+                }
+                Allocator::NtAllocateVirtualMemory => {
+                    let args = ctx.args(4, conv)?;
+                    if args[0] as u32 != CURRENT_PROCESS || args[3] as u32 != MEM_RELEASE.0 {
+                        return Ok(());
+                    }
+
+                    ctx.client().read_pointer(args[1])?
+                }
+                Allocator::HeapAlloc => ctx.args(3, conv)?[2],
+            };
+
+            state.borrow_mut().allocations.remove(address);
+
+            Ok(())
+        })
+    }
+
+    fn protect_hook(&self, target: &str, allocator: Allocator) -> Hook {
+        let conv = self.conv;
+        let enter_state = Rc::clone(&self.state);
+        let exit_state = Rc::clone(&self.state);
+
+        Hook::on(target)
+            .enter(move |ctx| {
+                let call = match allocator {
+                    Allocator::NtAllocateVirtualMemory => {
+                        let args = ctx.args(4, conv)?;
+                        if args[0] as u32 != CURRENT_PROCESS {
+                            return Ok(());
+                        }
+
+                        PendingCall::Protect {
+                            address: ctx.client().read_pointer(args[1])?,
+                            protection: args[3] as u32,
+                        }
+                    }
+                    _ => {
+                        let args = ctx.args(3, conv)?;
+                        PendingCall::Protect {
+                            address: args[0],
+                            protection: args[2] as u32,
+                        }
+                    }
+                };
+
+                enter_state.borrow_mut().pending.insert(key(ctx), call);
+
+                Ok(())
+            })
+            .exit(move |ctx| {
+                let Some(PendingCall::Protect {
+                    address,
+                    protection,
+                }) = exit_state.borrow_mut().pending.remove(&key(ctx))
+                else {
+                    return Ok(());
+                };
+
+                // N.B: `VirtualProtect` returns a `BOOL`, and the native API an
+                // `NTSTATUS`.
+                let ret = ctx.return_value()?;
+                let succeeded = match allocator {
+                    Allocator::NtAllocateVirtualMemory => ret as i32 >= 0,
+                    _ => ret as u32 != 0,
+                };
+
+                if succeeded {
+                    exit_state
+                        .borrow_mut()
+                        .allocations
+                        .protect(address, protection);
+                }
+
+                Ok(())
+            })
+    }
+}
+
+/// Get the key identifying the call of `ctx` while it is pending.
+fn key(ctx: &HookContext) -> (u32, u64) {
+    (ctx.thread_id(), ctx.stack_pointer())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map() {
+        let mut map = AllocationMap::default();
+        for (address, size) in [(0x1000, 0x2000), (0x4000, 0x10)] {
+            map.insert(Allocation {
+                address,
+                size,
+                protection: 0x40,
+                allocator: Allocator::VirtualAlloc,
+                return_address: 0,
+            });
+        }
+
+        assert_eq!(map.find(0x1fff).map(|a| a.address), Some(0x1000));
+        assert_eq!(map.find(0x3000), None);
+        assert_eq!(map.find(0x400f).map(|a| a.address), Some(0x4000));
+        assert_eq!(map.find(0x4010), None);
+        assert_eq!(map.find(0xfff), None);
+
+        map.protect(0x2000, 0x4);
+        assert_eq!(map.find(0x1000).map(|a| a.protection), Some(0x4));
+        assert!(map.remove(0x1000).is_some());
+        assert_eq!(map.find(0x1fff), None);
+    }
+}
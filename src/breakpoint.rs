@@ -1,17 +1,30 @@
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, CString};
+use std::rc::Rc;
+use std::time::SystemTime;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use bitflags::bitflags;
 use windows::core::{IUnknown, Interface, GUID};
+use windows::Win32::Foundation::E_NOINTERFACE;
 use windows::Win32::System::Diagnostics::Debug::Extensions::{
-    IDebugBreakpoint3, DEBUG_BREAKPOINT_ADDER_ONLY, DEBUG_BREAKPOINT_DEFERRED,
-    DEBUG_BREAKPOINT_ENABLED, DEBUG_BREAKPOINT_GO_ONLY, DEBUG_BREAKPOINT_ONE_SHOT,
-    DEBUG_BREAKPOINT_PARAMETERS,
+    IDebugBreakpoint3, DEBUG_ANY_ID, DEBUG_BREAKPOINT_ADDER_ONLY, DEBUG_BREAKPOINT_CODE,
+    DEBUG_BREAKPOINT_DATA, DEBUG_BREAKPOINT_DEFERRED, DEBUG_BREAKPOINT_ENABLED,
+    DEBUG_BREAKPOINT_GO_ONLY, DEBUG_BREAKPOINT_ONE_SHOT, DEBUG_BREAKPOINT_PARAMETERS,
+    DEBUG_BREAK_EXECUTE, DEBUG_BREAK_IO, DEBUG_BREAK_READ, DEBUG_BREAK_WRITE,
 };
 
 use crate::as_pcstr::AsPCSTR;
+use crate::client::DebugClient;
+use crate::dlogln;
+use crate::events::{DebugInstruction, EventCallbacks, EventInterest};
+use crate::exception::ExceptionInfo;
+use crate::symbol::ModuleInfo;
 
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct BreakpointFlags: u32 {
         const NONE = 0;
         const ENABLED = DEBUG_BREAKPOINT_ENABLED;
@@ -23,11 +36,73 @@ bitflags! {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BreakpointType {
     Code,
     Data,
 }
 
+/// A snapshot of the configuration of a breakpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakpointParameters {
+    /// The engine ID of the breakpoint.
+    pub id: u32,
+    /// The type of the breakpoint.
+    pub ty: BreakpointType,
+    /// The processor type (`IMAGE_FILE_MACHINE_*`) the breakpoint is set for.
+    pub proc_type: u32,
+    /// The address of the breakpoint; `None` if it is deferred.
+    pub offset: Option<u64>,
+    /// The flags of the breakpoint.
+    pub flags: BreakpointFlags,
+    /// The size and the access type of a data breakpoint.
+    pub data: Option<(u32, DataAccess)>,
+    /// The number of times the breakpoint has to be hit before it triggers.
+    pub pass_count: u32,
+    /// The number of hits remaining before the breakpoint triggers.
+    pub current_pass_count: u32,
+    /// The engine thread ID the breakpoint is restricted to, if any.
+    pub match_thread: Option<u32>,
+    /// The command executed when the breakpoint triggers.
+    pub command: String,
+    /// The expression the address of the breakpoint is evaluated from.
+    pub offset_expression: String,
+}
+
+/// The kind of access that triggers a data breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataAccess {
+    /// The memory is read or written.
+    Read,
+    /// The memory is written.
+    Write,
+    /// The memory is executed.
+    Execute,
+    /// The I/O port is accessed (kernel targets only).
+    Io,
+}
+
+impl DataAccess {
+    fn as_flags(&self) -> u32 {
+        match self {
+            Self::Read => DEBUG_BREAK_READ,
+            Self::Write => DEBUG_BREAK_WRITE,
+            Self::Execute => DEBUG_BREAK_EXECUTE,
+            Self::Io => DEBUG_BREAK_IO,
+        }
+    }
+
+    fn from_flags(flags: u32) -> Result<Self> {
+        Ok(match flags {
+            DEBUG_BREAK_READ => Self::Read,
+            DEBUG_BREAK_WRITE => Self::Write,
+            DEBUG_BREAK_EXECUTE => Self::Execute,
+            DEBUG_BREAK_IO => Self::Io,
+            _ => bail!("unknown data breakpoint access type {flags:#x}"),
+        })
+    }
+}
+
 /// A DbgEng breakpoint.
 ///
 /// Typically to setup a breakpoint, you will want to set the offset
@@ -54,6 +129,8 @@ impl DebugBreakpoint {
         Ok(unsafe { self.0.GetGuid()? })
     }
 
+    /// Get the command string executed by the engine when the breakpoint
+    /// triggers.
     pub fn command(&self) -> Result<String> {
         let mut params = DEBUG_BREAKPOINT_PARAMETERS::default();
         unsafe { self.0.GetParameters(&mut params) }
@@ -61,7 +138,7 @@ impl DebugBreakpoint {
 
         let mut buf = vec![0u8; params.CommandSize as usize];
         let mut len = buf.len() as u32;
-        unsafe { self.0.GetOffsetExpression(Some(&mut buf), Some(&mut len)) }
+        unsafe { self.0.GetCommand(Some(&mut buf), Some(&mut len)) }
             .context("failed to get breakpoint command")?;
 
         // Should always be equal...
@@ -75,6 +152,8 @@ impl DebugBreakpoint {
         Ok(s.to_owned())
     }
 
+    /// Set the command string executed by the engine when the breakpoint
+    /// triggers (like `r; k`); it runs in addition to the event callbacks.
     pub fn set_command<S: Into<String>>(&self, command: S) -> Result<()> {
         let cstr = CString::new(command.into())?;
         unsafe { self.0.SetCommand(cstr.as_pcstr()) }
@@ -129,4 +208,655 @@ impl DebugBreakpoint {
         unsafe { self.0.SetOffset(offset) }
             .with_context(|| format!("failed to set breakpoint offset to {offset:#018X}"))
     }
+
+    /// Get the size and the access type of a data breakpoint.
+    pub fn data_parameters(&self) -> Result<(u32, DataAccess)> {
+        let mut size = 0;
+        let mut access = 0;
+        unsafe { self.0.GetDataParameters(&mut size, &mut access) }
+            .context("failed to get breakpoint data parameters")?;
+
+        Ok((size, DataAccess::from_flags(access)?))
+    }
+
+    /// Configure a data breakpoint (a hardware watchpoint) to trigger when
+    /// `size` bytes at its offset are accessed with `access`. The size has to
+    /// be supported by the processor (1, 2, 4 or 8 bytes on x64) and the
+    /// offset aligned on it.
+    pub fn set_data_parameters(&self, size: u32, access: DataAccess) -> Result<()> {
+        unsafe { self.0.SetDataParameters(size, access.as_flags()) }.with_context(|| {
+            format!("failed to set breakpoint data parameters to {size:#x} / {access:?}")
+        })
+    }
+
+    /// Get the number of times the breakpoint has to be hit before it
+    /// triggers.
+    pub fn pass_count(&self) -> Result<u32> {
+        unsafe { self.0.GetPassCount() }.context("failed to get breakpoint pass count")
+    }
+
+    /// Make the breakpoint trigger only once it has been hit `count` times.
+    pub fn set_pass_count(&self, count: u32) -> Result<()> {
+        unsafe { self.0.SetPassCount(count) }
+            .with_context(|| format!("failed to set breakpoint pass count to {count}"))
+    }
+
+    /// Get the number of hits remaining before the breakpoint triggers.
+    pub fn current_pass_count(&self) -> Result<u32> {
+        unsafe { self.0.GetCurrentPassCount() }
+            .context("failed to get breakpoint current pass count")
+    }
+
+    /// Get the engine thread ID the breakpoint is restricted to, if any.
+    pub fn match_thread(&self) -> Result<Option<u32>> {
+        match unsafe { self.0.GetMatchThreadId() } {
+            Ok(thread_id) => Ok(Some(thread_id)),
+            // `E_NOINTERFACE` signals that no thread has been set.
+            Err(e) if e.code() == E_NOINTERFACE => Ok(None),
+            Err(e) => Err(e).context("failed to get breakpoint match thread"),
+        }
+    }
+
+    /// Restrict the breakpoint to the thread with the engine thread ID
+    /// `thread_id`; it doesn't trigger when other threads hit it.
+    pub fn set_match_thread(&self, thread_id: u32) -> Result<()> {
+        unsafe { self.0.SetMatchThreadId(thread_id) }
+            .with_context(|| format!("failed to set breakpoint match thread to {thread_id}"))
+    }
+
+    /// Let the breakpoint trigger on every thread again.
+    pub fn clear_match_thread(&self) -> Result<()> {
+        unsafe { self.0.SetMatchThreadId(DEBUG_ANY_ID) }
+            .context("failed to clear breakpoint match thread")
+    }
+
+    /// Get the whole configuration of the breakpoint.
+    pub fn parameters(&self) -> Result<BreakpointParameters> {
+        let mut params = DEBUG_BREAKPOINT_PARAMETERS::default();
+        unsafe { self.0.GetParameters(&mut params) }
+            .context("failed to get breakpoint parameters")?;
+
+        let ty = match params.BreakType {
+            DEBUG_BREAKPOINT_CODE => BreakpointType::Code,
+            DEBUG_BREAKPOINT_DATA => BreakpointType::Data,
+            ty => bail!("unknown breakpoint type {ty:#x}"),
+        };
+
+        let data = match ty {
+            BreakpointType::Code => None,
+            BreakpointType::Data => Some((
+                params.DataSize,
+                DataAccess::from_flags(params.DataAccessType)?,
+            )),
+        };
+
+        let flags = BreakpointFlags::from_bits(params.Flags)
+            .with_context(|| format!("could not convert flags from {:#010X}", params.Flags))?;
+
+        Ok(BreakpointParameters {
+            id: params.Id,
+            ty,
+            proc_type: params.ProcType,
+            // `DEBUG_INVALID_OFFSET` is used when the address isn't known yet.
+            offset: (params.Offset != u64::MAX).then_some(params.Offset),
+            flags,
+            data,
+            pass_count: params.PassCount,
+            current_pass_count: params.CurrentPassCount,
+            match_thread: (params.MatchThread != DEBUG_ANY_ID).then_some(params.MatchThread),
+            command: self.command()?,
+            offset_expression: self.offset_expression()?,
+        })
+    }
+}
+
+/// Get the range a data breakpoint watches to cover the `size` bytes at
+/// `addr`, as the hardware only watches 1, 2, 4 or 8 bytes aligned on their
+/// size; the range can be wider than the bytes.
+fn watch_range(addr: u64, size: u32) -> Result<(u64, u32)> {
+    if size == 0 {
+        bail!("cannot watch zero bytes at {addr:#x}");
+    }
+
+    if size <= 8 {
+        let last = addr
+            .checked_add(u64::from(size - 1))
+            .with_context(|| format!("{size:#x} bytes at {addr:#x} overflow"))?;
+
+        let mut len = size.next_power_of_two();
+        while len <= 8 {
+            // N.B: `start` is aligned on `len`, so the address of the last byte
+            // of the range can't overflow.
+            let start = addr & !u64::from(len - 1);
+            if start + u64::from(len - 1) >= last {
+                return Ok((start, len));
+            }
+
+            len *= 2;
+        }
+    }
+
+    bail!("{size:#x} bytes at {addr:#x} don't fit in a data breakpoint")
+}
+
+/// Where to set a breakpoint managed by a [`BreakpointManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointTarget {
+    /// An address.
+    Address(u64),
+    /// An expression evaluated by the engine, like `nt!NtCreateUserProcess`.
+    Expression(String),
+}
+
+impl From<u64> for BreakpointTarget {
+    fn from(addr: u64) -> Self {
+        Self::Address(addr)
+    }
+}
+
+impl From<&str> for BreakpointTarget {
+    fn from(expression: &str) -> Self {
+        Self::Expression(expression.to_string())
+    }
+}
+
+impl From<String> for BreakpointTarget {
+    fn from(expression: String) -> Self {
+        Self::Expression(expression)
+    }
+}
+
+/// The callback invoked when a managed breakpoint triggers.
+type BreakpointCallback = dyn FnMut(&DebugClient, &DebugBreakpoint) -> Result<DebugInstruction>;
+
+/// A breakpoint owned by a [`BreakpointManager`].
+struct ManagedBreakpoint {
+    bp: DebugBreakpoint,
+    // N.B: The callback is reference counted so that it can run without
+    // borrowing the registry, which allows it to add or remove breakpoints.
+    callback: Rc<RefCell<BreakpointCallback>>,
+    stats: Cell<BreakpointStats>,
+}
+
+/// The hit statistics of a breakpoint of a [`BreakpointManager`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BreakpointStats {
+    /// The number of times the breakpoint triggered.
+    pub hits: u64,
+    /// When the breakpoint last triggered.
+    pub last_hit: Option<SystemTime>,
+    /// The system ID of the thread that last triggered the breakpoint.
+    pub last_thread: Option<u32>,
+}
+
+/// A breakpoint waiting for its module to be loaded.
+struct PendingBreakpoint {
+    module: String,
+    expression: String,
+    callback: Rc<RefCell<BreakpointCallback>>,
+}
+
+/// Remove the breakpoints `bps`. Every breakpoint gets a chance to be removed
+/// even if removing one fails; the first error is returned.
+pub(crate) fn remove_all(
+    client: &DebugClient,
+    bps: impl IntoIterator<Item = DebugBreakpoint>,
+) -> Result<()> {
+    let mut res = Ok(());
+    for bp in bps {
+        res = res.and(client.remove_breakpoint(bp));
+    }
+
+    res
+}
+
+/// A registry of breakpoints paired with Rust callbacks.
+///
+/// The manager implements [`EventCallbacks`], and dispatches the breakpoint
+/// events to the callback of the breakpoint that triggered. To keep adding
+/// breakpoints after handing it to [`DebugClient::set_event_callbacks`], wrap
+/// it in a [`Rc`]. The breakpoints are removed when the manager is dropped.
+pub struct BreakpointManager {
+    client: DebugClient,
+    breakpoints: RefCell<HashMap<GUID, ManagedBreakpoint>>,
+    pending: RefCell<Vec<PendingBreakpoint>>,
+    groups: RefCell<HashMap<String, HashSet<GUID>>>,
+}
+
+impl BreakpointManager {
+    pub fn new(client: DebugClient) -> Self {
+        Self {
+            client,
+            breakpoints: RefCell::new(HashMap::new()),
+            pending: RefCell::new(Vec::new()),
+            groups: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Set an enabled code breakpoint on `target` that invokes `callback`
+    /// when it triggers. The returned GUID identifies the breakpoint.
+    pub fn add<T, F>(&self, target: T, callback: F) -> Result<GUID>
+    where
+        T: Into<BreakpointTarget>,
+        F: FnMut(&DebugClient, &DebugBreakpoint) -> Result<DebugInstruction> + 'static,
+    {
+        self.insert(target.into(), Rc::new(RefCell::new(callback)))
+    }
+
+    /// Set an enabled code breakpoint on `target` (like `ntdll!LdrLoadDll`)
+    /// that invokes `callback` when it triggers. If the module isn't loaded
+    /// yet, the breakpoint is set once it is; this requires the manager to
+    /// receive the events.
+    pub fn add_deferred<F>(&self, target: &str, callback: F) -> Result<()>
+    where
+        F: FnMut(&DebugClient, &DebugBreakpoint) -> Result<DebugInstruction> + 'static,
+    {
+        let (module, _) = target
+            .split_once('!')
+            .with_context(|| format!("expected a module!symbol target, got {target}"))?;
+
+        let callback = Rc::new(RefCell::new(callback));
+        if self.client.get_sym_module(module).is_ok() {
+            self.insert(target.into(), callback)?;
+        } else {
+            self.pending.borrow_mut().push(PendingBreakpoint {
+                module: module.to_string(),
+                expression: target.to_string(),
+                callback,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Set an enabled write data breakpoint on the field `field` (like
+    /// `Flags`) of the structure of type `type_name` (like `nt!_EPROCESS`) at
+    /// `base_addr`, that invokes `callback` when the field is written. The
+    /// offset and the size of the field come from the symbols, so no offset
+    /// has to be hardcoded per build. As data breakpoints watch aligned
+    /// ranges of 1, 2, 4 or 8 bytes, the breakpoint can also trigger on
+    /// writes to the bytes next to a small unaligned field; larger fields
+    /// can't be watched. The returned GUID identifies the breakpoint.
+    pub fn watch_field<F>(
+        &self,
+        type_name: &str,
+        field: &str,
+        base_addr: u64,
+        callback: F,
+    ) -> Result<GUID>
+    where
+        F: FnMut(&DebugClient, &DebugBreakpoint) -> Result<DebugInstruction> + 'static,
+    {
+        let value = self
+            .client
+            .typed_value(type_name, base_addr)?
+            .field(field)?;
+        let (addr, size) = watch_range(value.addr(), value.size()?)
+            .with_context(|| format!("cannot watch {type_name}.{field}"))?;
+
+        self.insert_with(
+            BreakpointType::Data,
+            |bp| {
+                bp.set_offset(addr)?;
+                bp.set_data_parameters(size, DataAccess::Write)
+            },
+            Rc::new(RefCell::new(callback)),
+        )
+    }
+
+    /// Set the breakpoints waiting for `module` to be loaded.
+    fn materialize(&self, module: &ModuleInfo) {
+        let pending = self.pending.take();
+        let (ready, pending) = pending
+            .into_iter()
+            .partition::<Vec<_>, _>(|p| p.module.eq_ignore_ascii_case(&module.name));
+
+        self.pending.borrow_mut().extend(pending);
+        for p in ready {
+            if let Err(e) = self.insert(p.expression.clone().into(), p.callback) {
+                let _ = dlogln!(
+                    self.client,
+                    "Failed to set breakpoint {}: {e:?}",
+                    p.expression
+                );
+            }
+        }
+    }
+
+    /// Set an enabled code breakpoint on `target` that invokes `callback`.
+    fn insert(
+        &self,
+        target: BreakpointTarget,
+        callback: Rc<RefCell<BreakpointCallback>>,
+    ) -> Result<GUID> {
+        self.insert_with(
+            BreakpointType::Code,
+            |bp| match target {
+                BreakpointTarget::Address(addr) => bp.set_offset(addr),
+                BreakpointTarget::Expression(expression) => bp.set_offset_expression(expression),
+            },
+            callback,
+        )
+    }
+
+    /// Create a breakpoint of type `ty`, set it up with `configure`, enable it
+    /// and make it invoke `callback`.
+    fn insert_with(
+        &self,
+        ty: BreakpointType,
+        configure: impl FnOnce(&DebugBreakpoint) -> Result<()>,
+        callback: Rc<RefCell<BreakpointCallback>>,
+    ) -> Result<GUID> {
+        let bp = self.client.add_breakpoint(ty, None)?;
+        let res = configure(&bp)
+            .and_then(|_| bp.set_flags(BreakpointFlags::ENABLED))
+            .and_then(|_| bp.guid());
+
+        let guid = match res {
+            Ok(guid) => guid,
+            Err(e) => {
+                let _ = self.client.remove_breakpoint(bp);
+                return Err(e);
+            }
+        };
+
+        self.breakpoints
+            .borrow_mut()
+            .insert(guid, ManagedBreakpoint {
+                bp,
+                callback,
+                stats: Cell::default(),
+            });
+
+        Ok(guid)
+    }
+
+    /// Remove the breakpoint identified by `guid`; `false` is returned if the
+    /// manager doesn't know about it.
+    pub fn remove(&self, guid: &GUID) -> Result<bool> {
+        let Some(managed) = self.breakpoints.borrow_mut().remove(guid) else {
+            return Ok(false);
+        };
+
+        for guids in self.groups.borrow_mut().values_mut() {
+            guids.remove(guid);
+        }
+
+        self.client.remove_breakpoint(managed.bp)?;
+
+        Ok(true)
+    }
+
+    /// Remove every breakpoint, including the ones waiting for their module.
+    pub fn clear(&self) -> Result<()> {
+        self.pending.borrow_mut().clear();
+        self.groups.borrow_mut().clear();
+
+        let bps = self
+            .breakpoints
+            .take()
+            .into_values()
+            .map(|managed| managed.bp);
+
+        remove_all(&self.client, bps)
+    }
+
+    /// Get the group `name`; it is created if it doesn't exist yet.
+    pub fn group(&self, name: &str) -> BreakpointGroup<'_> {
+        self.groups
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_default();
+
+        BreakpointGroup {
+            manager: self,
+            name: name.to_string(),
+        }
+    }
+
+    /// Get the names of the groups.
+    pub fn groups(&self) -> Vec<String> {
+        self.groups.borrow().keys().cloned().collect()
+    }
+
+    /// Add the breakpoint identified by `guid` to the group `name`; `false`
+    /// is returned if the manager doesn't know about it.
+    pub fn tag(&self, guid: &GUID, name: &str) -> bool {
+        if !self.breakpoints.borrow().contains_key(guid) {
+            return false;
+        }
+
+        self.groups
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_default()
+            .insert(*guid);
+
+        true
+    }
+
+    /// Enable or disable the breakpoint identified by `guid`.
+    fn set_enabled(&self, guid: &GUID, enabled: bool) -> Result<()> {
+        let breakpoints = self.breakpoints.borrow();
+        let managed = breakpoints
+            .get(guid)
+            .with_context(|| format!("unknown breakpoint {guid:?}"))?;
+
+        let mut flags = managed.bp.flags()?;
+        flags.set(BreakpointFlags::ENABLED, enabled);
+
+        managed.bp.set_flags(flags)
+    }
+
+    /// Get the number of breakpoints waiting for their module to be loaded.
+    pub fn pending_len(&self) -> usize {
+        self.pending.borrow().len()
+    }
+
+    /// Get the number of breakpoints.
+    pub fn len(&self) -> usize {
+        self.breakpoints.borrow().len()
+    }
+
+    /// Is the manager empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the hit statistics of the breakpoint identified by `guid`.
+    pub fn stats(&self, guid: &GUID) -> Option<BreakpointStats> {
+        self.breakpoints
+            .borrow()
+            .get(guid)
+            .map(|managed| managed.stats.get())
+    }
+
+    /// Get the hit statistics of every breakpoint, the most hit first.
+    pub fn all_stats(&self) -> Vec<(GUID, BreakpointStats)> {
+        let mut stats = self
+            .breakpoints
+            .borrow()
+            .iter()
+            .map(|(guid, managed)| (*guid, managed.stats.get()))
+            .collect::<Vec<_>>();
+
+        stats.sort_by_key(|(_, stats)| Reverse(stats.hits));
+
+        stats
+    }
+
+    /// Reset the hit statistics of every breakpoint.
+    pub fn reset_stats(&self) {
+        for managed in self.breakpoints.borrow().values() {
+            managed.stats.take();
+        }
+    }
+
+    /// Invoke the callback of `bp`; [`DebugInstruction::NoChange`] is returned
+    /// if the manager doesn't know about it.
+    pub fn dispatch(&self, client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        let Ok(guid) = bp.guid() else {
+            return DebugInstruction::NoChange;
+        };
+
+        let Some(callback) = self.breakpoints.borrow().get(&guid).map(|managed| {
+            let mut stats = managed.stats.get();
+            stats.hits += 1;
+            stats.last_hit = Some(SystemTime::now());
+            stats.last_thread = client.get_current_thread_id().ok();
+            managed.stats.set(stats);
+
+            Rc::clone(&managed.callback)
+        }) else {
+            return DebugInstruction::NoChange;
+        };
+
+        let mut callback = callback.borrow_mut();
+        match (*callback)(client, bp) {
+            Ok(i) => i,
+            Err(e) => {
+                let _ = dlogln!(client, "Error in breakpoint callback: {e:?}");
+                DebugInstruction::NoChange
+            }
+        }
+    }
+}
+
+impl EventCallbacks for BreakpointManager {
+    fn breakpoint(&self, client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        self.dispatch(client, bp)
+    }
+
+    fn exception(&self, _client: &DebugClient, _ei: &ExceptionInfo) -> DebugInstruction {
+        DebugInstruction::NoChange
+    }
+
+    fn change_engine_state(&self, _client: &DebugClient, _flags: u32, _argument: u64) {}
+
+    fn load_module(&self, _client: &DebugClient, module: &ModuleInfo) {
+        self.materialize(module);
+    }
+
+    fn interest_mask(&self) -> EventInterest {
+        EventInterest::BREAKPOINT | EventInterest::LOAD_MODULE
+    }
+}
+
+/// A named set of breakpoints of a [`BreakpointManager`], like allocation
+/// hooks, that are enabled, disabled or removed as a unit.
+///
+/// ```ignore
+/// let hooks = breakpoints.group("alloc");
+/// hooks.add("ntdll!RtlAllocateHeap", on_alloc)?;
+/// hooks.add("ntdll!RtlFreeHeap", on_free)?;
+/// hooks.disable()?;
+/// ```
+pub struct BreakpointGroup<'a> {
+    manager: &'a BreakpointManager,
+    name: String,
+}
+
+impl BreakpointGroup<'_> {
+    /// Get the name of the group.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set an enabled code breakpoint on `target` that invokes `callback`
+    /// when it triggers, and add it to the group.
+    pub fn add<T, F>(&self, target: T, callback: F) -> Result<GUID>
+    where
+        T: Into<BreakpointTarget>,
+        F: FnMut(&DebugClient, &DebugBreakpoint) -> Result<DebugInstruction> + 'static,
+    {
+        let guid = self.manager.add(target, callback)?;
+        self.manager.tag(&guid, &self.name);
+
+        Ok(guid)
+    }
+
+    /// Get the IDs of the breakpoints of the group.
+    pub fn guids(&self) -> Vec<GUID> {
+        self.manager
+            .groups
+            .borrow()
+            .get(&self.name)
+            .map(|guids| guids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get the number of breakpoints in the group.
+    pub fn len(&self) -> usize {
+        self.manager
+            .groups
+            .borrow()
+            .get(&self.name)
+            .map_or(0, HashSet::len)
+    }
+
+    /// Is the group empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Enable every breakpoint of the group.
+    pub fn enable(&self) -> Result<()> {
+        self.set_enabled(true)
+    }
+
+    /// Disable every breakpoint of the group; they stay registered and can be
+    /// enabled again.
+    pub fn disable(&self) -> Result<()> {
+        self.set_enabled(false)
+    }
+
+    fn set_enabled(&self, enabled: bool) -> Result<()> {
+        // N.B: Keep going on failure like `remove_all`.
+        let mut res = Ok(());
+        for guid in self.guids() {
+            res = res.and(self.manager.set_enabled(&guid, enabled));
+        }
+
+        res
+    }
+
+    /// Remove every breakpoint of the group, and the group itself.
+    pub fn remove(self) -> Result<()> {
+        let mut res = Ok(());
+        for guid in self.guids() {
+            res = res.and(self.manager.remove(&guid).map(|_| ()));
+        }
+
+        self.manager.groups.borrow_mut().remove(&self.name);
+
+        res
+    }
+}
+
+impl Drop for BreakpointManager {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watch() {
+        assert_eq!(watch_range(0x1000, 8).unwrap(), (0x1000, 8));
+        assert_eq!(watch_range(0x1004, 4).unwrap(), (0x1004, 4));
+        assert_eq!(watch_range(0x1001, 1).unwrap(), (0x1001, 1));
+        // An unaligned or odd-sized field is covered by a wider range.
+        assert_eq!(watch_range(0x1002, 3).unwrap(), (0x1000, 8));
+        assert_eq!(watch_range(0x1003, 2).unwrap(), (0x1000, 8));
+        assert_eq!(watch_range(0x1001, 2).unwrap(), (0x1000, 4));
+        assert!(watch_range(0x1006, 4).is_err());
+        assert!(watch_range(0x1000, 16).is_err());
+        assert!(watch_range(0x1000, 0).is_err());
+        assert_eq!(watch_range(u64::MAX - 7, 8).unwrap(), (u64::MAX - 7, 8));
+        assert!(watch_range(u64::MAX, 2).is_err());
+        assert!(watch_range(0x1000, u32::MAX).is_err());
+    }
 }
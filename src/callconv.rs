@@ -0,0 +1,129 @@
+//! This contains helpers to read the arguments of a function according to its
+//! calling convention.
+use anyhow::{Context, Result};
+
+use crate::client::DebugClient;
+
+/// A calling convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallConv {
+    /// The Microsoft x64 calling convention: the first four arguments are
+    /// passed in `rcx`, `rdx`, `r8` and `r9`, and the rest on the stack
+    /// after the shadow space.
+    Win64,
+    /// The x86 `__stdcall` calling convention: every argument is passed on the
+    /// stack.
+    Stdcall,
+    /// The x86 `__cdecl` calling convention: every argument is passed on the
+    /// stack.
+    Cdecl,
+    /// The x86 `__fastcall` calling convention: the first two arguments are
+    /// passed in `ecx` and `edx`, and the rest on the stack.
+    Fastcall,
+    /// The x86 `__thiscall` calling convention: the first argument (`this`)
+    /// is passed in `ecx`, and the rest on the stack.
+    Thiscall,
+}
+
+/// Where an argument lives at function entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgLocation {
+    /// The argument is in a register.
+    Register(&'static str),
+    /// The argument is on the stack, at this offset from the stack pointer.
+    Stack(u64),
+}
+
+impl CallConv {
+    /// Get the size of a stack slot.
+    pub fn slot_size(&self) -> u64 {
+        match self {
+            Self::Win64 => 8,
+            Self::Stdcall | Self::Cdecl | Self::Fastcall | Self::Thiscall => 4,
+        }
+    }
+
+    /// Get the registers holding the first arguments.
+    pub fn arg_registers(&self) -> &'static [&'static str] {
+        match self {
+            Self::Win64 => &["rcx", "rdx", "r8", "r9"],
+            Self::Stdcall | Self::Cdecl => &[],
+            Self::Fastcall => &["ecx", "edx"],
+            Self::Thiscall => &["ecx"],
+        }
+    }
+
+    /// Get the name of the stack pointer register.
+    pub fn stack_pointer(&self) -> &'static str {
+        match self {
+            Self::Win64 => "rsp",
+            Self::Stdcall | Self::Cdecl | Self::Fastcall | Self::Thiscall => "esp",
+        }
+    }
+
+    /// Get where the argument at `index` (starting at 0) lives at function
+    /// entry, when the return address is at the top of the stack.
+    pub fn arg_location(&self, index: usize) -> ArgLocation {
+        let registers = self.arg_registers();
+        if let Some(reg) = registers.get(index) {
+            return ArgLocation::Register(reg);
+        }
+
+        // On x64, the caller reserves a shadow space for the arguments passed
+        // in registers, so the stack slots are indexed like the arguments.
+        let slot = match self {
+            Self::Win64 => index,
+            _ => index - registers.len(),
+        };
+
+        // Skip the return address.
+        ArgLocation::Stack(self.slot_size() * (1 + slot as u64))
+    }
+}
+
+/// Read the first `n` arguments of the function the debuggee is stopped at
+/// the entry of, according to the calling convention `conv`.
+pub fn read_args(client: &DebugClient, n: usize, conv: CallConv) -> Result<Vec<u64>> {
+    let sp = client.reg64(conv.stack_pointer())?;
+    (0..n)
+        .map(|index| match conv.arg_location(index) {
+            ArgLocation::Register(reg) => client.reg64(reg),
+            ArgLocation::Stack(offset) => {
+                let addr = sp + offset;
+                let mut buffer = [0; 8];
+                client
+                    .read_virtual_exact(addr, &mut buffer[..conv.slot_size() as usize])
+                    .with_context(|| format!("failed to read argument {index} at {addr:#x}"))?;
+
+                Ok(u64::from_le_bytes(buffer))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arg_location() {
+        let conv = CallConv::Win64;
+        assert_eq!(conv.arg_location(0), ArgLocation::Register("rcx"));
+        assert_eq!(conv.arg_location(3), ArgLocation::Register("r9"));
+        assert_eq!(conv.arg_location(4), ArgLocation::Stack(0x28));
+        assert_eq!(conv.arg_location(5), ArgLocation::Stack(0x30));
+
+        for conv in [CallConv::Stdcall, CallConv::Cdecl] {
+            assert_eq!(conv.arg_location(0), ArgLocation::Stack(4));
+            assert_eq!(conv.arg_location(2), ArgLocation::Stack(0xc));
+        }
+
+        let conv = CallConv::Fastcall;
+        assert_eq!(conv.arg_location(1), ArgLocation::Register("edx"));
+        assert_eq!(conv.arg_location(2), ArgLocation::Stack(4));
+
+        let conv = CallConv::Thiscall;
+        assert_eq!(conv.arg_location(0), ArgLocation::Register("ecx"));
+        assert_eq!(conv.arg_location(1), ArgLocation::Stack(4));
+    }
+}
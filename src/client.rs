@@ -1,30 +1,85 @@
 // Axel '0vercl0k' Souchet - January 21 2024
 //! This contains the main class, [`DebugClient`], which is used to interact
 //! with Microsoft's Debug Engine library via the documented COM objects.
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::ffi::{CString, OsStr};
-use std::mem::MaybeUninit;
-use std::path::PathBuf;
+use std::ffi::{CStr, CString, OsStr};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem::{self, MaybeUninit};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::rc::Rc;
+use std::time::Duration;
 
 use anyhow::{bail, Context, Result};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use windows::core::{IUnknown, Interface};
+use windows::core::{IUnknown, Interface, HSTRING, PCSTR, PCWSTR};
+use windows::Win32::Foundation::{E_NOINTERFACE, S_OK};
 use windows::Win32::System::Diagnostics::Debug::Extensions::{
-    IDebugControl3, IDebugDataSpaces4, IDebugRegisters, IDebugSymbols3, DEBUG_ADDSYNTHMOD_DEFAULT,
-    DEBUG_EXECUTE_DEFAULT, DEBUG_OUTCTL_ALL_CLIENTS, DEBUG_OUTPUT_NORMAL, DEBUG_VALUE,
-    DEBUG_VALUE_FLOAT128, DEBUG_VALUE_FLOAT32, DEBUG_VALUE_FLOAT64, DEBUG_VALUE_FLOAT80,
-    DEBUG_VALUE_INT16, DEBUG_VALUE_INT32, DEBUG_VALUE_INT64, DEBUG_VALUE_INT8,
-    DEBUG_VALUE_VECTOR128, DEBUG_VALUE_VECTOR64,
+    IDebugAdvanced2, IDebugClient, IDebugControl3, IDebugDataSpaces4, IDebugEventCallbacks,
+    IDebugInputCallbacks, IDebugOutputCallbacksWide, IDebugRegisters2, IDebugSymbolGroup2,
+    IDebugSymbols3, IDebugSymbols4, DEBUG_ADDSYNTHMOD_DEFAULT, DEBUG_ADDSYNTHSYM_DEFAULT,
+    DEBUG_ATTACH_DEFAULT, DEBUG_CLASS_USER_WINDOWS, DEBUG_CREATE_PROCESS_OPTIONS,
+    DEBUG_EVENT_EXCEPTION, DEBUG_EXCEPTION_FILTER_PARAMETERS, DEBUG_EXECUTE_DEFAULT,
+    DEBUG_GETFNENT_RAW_ENTRY_ONLY, DEBUG_INTERRUPT_ACTIVE, DEBUG_LAST_EVENT_INFO_EXCEPTION,
+    DEBUG_MODNAME_IMAGE, DEBUG_MODNAME_MODULE, DEBUG_MODULE_AND_ID, DEBUG_MODULE_PARAMETERS,
+    DEBUG_MODULE_SYNTHETIC, DEBUG_OUTCTL_ALL_CLIENTS, DEBUG_OUTCTL_THIS_CLIENT, DEBUG_OUTPUT_ERROR,
+    DEBUG_OUTPUT_NORMAL, DEBUG_OUTPUT_VERBOSE, DEBUG_OUTPUT_WARNING, DEBUG_PHYSICAL_CACHED,
+    DEBUG_PHYSICAL_DEFAULT, DEBUG_PHYSICAL_UNCACHED, DEBUG_PHYSICAL_WRITE_COMBINED,
+    DEBUG_PROC_DESC_DEFAULT, DEBUG_READ_USER_MINIDUMP_STREAM, DEBUG_REGISTER_DESCRIPTION,
+    DEBUG_REGSRC_DEBUGGEE, DEBUG_REQUEST_READ_USER_MINIDUMP_STREAM,
+    DEBUG_SPECIFIC_FILTER_PARAMETERS, DEBUG_STACK_FRAME, DEBUG_SYSVERSTR_BUILD,
+    DEBUG_SYSVERSTR_SERVICE_PACK, DEBUG_VALUE, DEBUG_VALUE_FLOAT128, DEBUG_VALUE_FLOAT32,
+    DEBUG_VALUE_FLOAT64, DEBUG_VALUE_FLOAT80, DEBUG_VALUE_INT16, DEBUG_VALUE_INT32,
+    DEBUG_VALUE_INT64, DEBUG_VALUE_INT8, DEBUG_VALUE_VECTOR128, DEBUG_VALUE_VECTOR64,
+    DEBUG_VSEARCH_DEFAULT, DEBUG_WAIT_DEFAULT,
 };
-use windows::Win32::System::Diagnostics::Debug::IMAGE_NT_HEADERS32;
-use windows::Win32::System::SystemInformation::IMAGE_FILE_MACHINE;
-use windows::Win32::System::SystemServices::{
-    IMAGE_DOS_HEADER, IMAGE_DOS_SIGNATURE, IMAGE_NT_SIGNATURE,
+use windows::Win32::System::Diagnostics::Debug::{
+    CommentStreamA, CommentStreamW, MINIDUMP_STREAM_TYPE,
+};
+use windows::Win32::System::Memory::MEMORY_BASIC_INFORMATION64;
+use windows::Win32::System::SystemInformation::{
+    IMAGE_FILE_MACHINE, IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM64,
+    IMAGE_FILE_MACHINE_ARMNT, IMAGE_FILE_MACHINE_I386,
 };
 
 use crate::as_pcstr::AsPCSTR;
 use crate::bits::Bits;
+use crate::breakpoint::{BreakpointFlags, BreakpointType, DebugBreakpoint};
+use crate::dump::{DumpFormat, DumpKind, StoredEvent};
+use crate::events::{
+    DbgEventCallbacks, EventCallbacks, EventContext, EventDispatcher, EventInterest,
+    ExecutionStatus, ForeignEventCallbacks, PanicPolicy,
+};
+use crate::exception::ExceptionInfo;
+use crate::filter::{
+    EventFilterCounts, ExceptionFilter, FilterContinue, FilterExecution, SpecificFilter,
+};
+use crate::input::{DbgInputCallbacks, InputCallbacks};
+use crate::kernel::KernelConnection;
+use crate::memory::{self, MemoryRegion, MemoryRegions};
+use crate::output::{
+    DbgOutputCallbacks, LogFileFlags, OutputCallbacks, OutputCollector, OutputMask,
+};
+use crate::process::{self, AttachFlags, CreateProcessOptions, EndSession};
+use crate::registers::{
+    FrameContext, RegisterDescription, RegisterIndexCache, Rflags, Vector128, X64Context,
+    X64_CONTEXT_REGISTERS, X64_CONTEXT_SIZE,
+};
+use crate::stack::{StackFrame, StackTrace};
+use crate::symbol::{
+    ModuleInfo, SourceLine, SymbolModule, SymbolName, SymbolOptions, SyntheticSymbol,
+};
+use crate::symbol_group::{ScopeGroup, SymbolGroup};
+use crate::typed::TypedValue;
+use crate::unwind::{RuntimeFunction, UnwindInfo};
+
+/// The maximum amount of bytes read by [`DebugClient::read_cstring_virtual`]
+/// and [`DebugClient::read_wstring_virtual`].
+pub const MAX_STRING_LEN: usize = 0x1_00_00;
 
 /// Extract [`u128`] off a [`DEBUG_VALUE`].
 pub fn u128_from_debugvalue(v: DEBUG_VALUE) -> Result<u128> {
@@ -70,6 +125,51 @@ pub fn u64_from_debugvalue(v: DEBUG_VALUE) -> Result<u64> {
     Ok(value)
 }
 
+/// Invoke a debugger engine API that returns a string via a buffer / size
+/// pair. The API is invoked a first time to get the size of the string, and
+/// a second time to fill the buffer.
+pub(crate) fn engine_string(
+    mut f: impl FnMut(Option<&mut [u8]>, Option<*mut u32>) -> windows::core::Result<()>,
+) -> Result<String> {
+    let mut size = 0;
+    f(None, Some(&mut size))?;
+
+    let mut buffer = vec![0; size as usize];
+    f(Some(&mut buffer), None)?;
+
+    let s = CStr::from_bytes_until_nul(&buffer)
+        .context("the engine returned an invalid string")?
+        .to_string_lossy()
+        .into_owned();
+
+    Ok(s)
+}
+
+/// Build a [`DEBUG_VALUE`] holding a [`u64`].
+pub fn debugvalue_from_u64(value: u64) -> DEBUG_VALUE {
+    let mut v = DEBUG_VALUE {
+        Type: DEBUG_VALUE_INT64,
+        ..Default::default()
+    };
+
+    v.Anonymous.I64Parts32.HighPart = (value >> 32) as u32;
+    v.Anonymous.I64Parts32.LowPart = value as u32;
+
+    v
+}
+
+/// Build a [`DEBUG_VALUE`] holding a [`u128`] vector.
+pub fn debugvalue_from_u128(value: u128) -> DEBUG_VALUE {
+    let mut v = DEBUG_VALUE {
+        Type: DEBUG_VALUE_VECTOR128,
+        ..Default::default()
+    };
+
+    v.Anonymous.VI8 = value.to_le_bytes();
+
+    v
+}
+
 /// Intel x86 segment descriptor.
 #[derive(Default, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -109,6 +209,51 @@ impl Seg {
     }
 }
 
+/// Caching policy used when accessing physical memory.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhysicalCaching {
+    /// Use the default caching policy of the target.
+    #[default]
+    Default,
+    /// Access the memory through the cache.
+    Cached,
+    /// Bypass the cache.
+    Uncached,
+    /// Use write-combining.
+    WriteCombined,
+}
+
+impl PhysicalCaching {
+    fn as_flags(&self) -> u32 {
+        match self {
+            PhysicalCaching::Default => DEBUG_PHYSICAL_DEFAULT,
+            PhysicalCaching::Cached => DEBUG_PHYSICAL_CACHED,
+            PhysicalCaching::Uncached => DEBUG_PHYSICAL_UNCACHED,
+            PhysicalCaching::WriteCombined => DEBUG_PHYSICAL_WRITE_COMBINED,
+        }
+    }
+}
+
+/// The version of the operating system of the target.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SystemVersion {
+    /// The platform ID, like `VER_PLATFORM_WIN32_NT` (2).
+    pub platform_id: u32,
+    /// The major version, like 10.
+    pub major: u32,
+    /// The minor version, like 0.
+    pub minor: u32,
+    /// The build number, like 22621.
+    pub build: u32,
+    /// Is it a checked build?
+    pub checked: bool,
+    /// The service pack, like `Service Pack 1`; empty if there is none.
+    pub service_pack: String,
+    /// The full build string, like `22621.1.amd64fre.ni_release.220506-1250`.
+    pub build_string: String,
+}
+
 /// Macro to make it nicer to invoke [`DebugClient::logln`] /
 /// [`DebugClient::log`] by avoiding to [`format!`] everytime the arguments.
 #[macro_export]
@@ -125,6 +270,80 @@ macro_rules! dlog {
     }};
 }
 
+/// A process running on the machine of the engine.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RunningProcess {
+    /// The system ID of the process (its PID).
+    pub system_id: u32,
+    /// The name of the executable of the process.
+    pub executable: String,
+    /// A description of the process, like its services, user and command
+    /// line.
+    pub description: String,
+}
+
+/// Restores the current process and thread of a [`DebugClient`] when dropped;
+/// see [`DebugClient::switch_thread`] and [`DebugClient::switch_process`].
+#[must_use = "the previous context is restored when the guard is dropped"]
+pub struct ContextGuard {
+    client: DebugClient,
+    process_id: u32,
+    thread_id: u32,
+}
+
+impl ContextGuard {
+    fn new(client: &DebugClient) -> Result<Self> {
+        Ok(Self {
+            client: client.clone(),
+            process_id: client.current_process()?,
+            thread_id: client.current_thread()?,
+        })
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        // N.B: The thread is restored after the process, as switching process
+        // also switches the current thread.
+        let restored = self
+            .client
+            .set_current_process(self.process_id)
+            .and_then(|_| self.client.set_current_thread(self.thread_id));
+
+        if let Err(e) = restored {
+            let _ = dlogln!(self.client, "Failed to restore the context: {e:?}");
+        }
+    }
+}
+
+/// A handle to a [`DebugClient`] that can be sent to another thread, where
+/// [`ClientHandle::create_client`] creates a client for that thread; see
+/// [`DebugClient::handle`].
+pub struct ClientHandle {
+    client: IDebugClient,
+}
+
+// SAFETY: `CreateClient` and `ExitDispatch` are the only methods the engine
+// allows to be called from any thread, and they are the only ones called on
+// `client`.
+unsafe impl Send for ClientHandle {}
+
+impl ClientHandle {
+    /// Create a client for the calling thread.
+    pub fn create_client(&self) -> Result<DebugClient> {
+        let client = unsafe { self.client.CreateClient() }.context("CreateClient failed")?;
+
+        DebugClient::new(&client.cast()?)
+    }
+
+    /// Make [`DebugClient::dispatch_callbacks`] return on the client of this
+    /// handle.
+    pub fn exit_dispatch(&self) -> Result<()> {
+        unsafe { self.client.ExitDispatch(&self.client) }.context("ExitDispatch failed")
+    }
+}
+
 #[derive(Clone)]
 /// A debug client wraps a bunch of COM interfaces and provides higher level
 /// features such as dumping registers, reading the GDT, reading virtual memory,
@@ -132,10 +351,15 @@ macro_rules! dlog {
 pub struct DebugClient {
     client: IDebugClient8,
     control: IDebugControl4,
-    registers: IDebugRegisters,
+    registers: IDebugRegisters2,
     dataspaces: IDebugDataSpaces4,
     symbols: IDebugSymbols3,
     system: IDebugSystemObjects4,
+    /// Cache of the register indices resolved by [`DebugClient::reg_indices`].
+    reg_cache: Rc<RefCell<RegisterIndexCache>>,
+    /// `Some` while an event is being delivered to the event callbacks, with
+    /// the context of the event once it has been queried.
+    event_context: Rc<Cell<Option<Option<EventContext>>>>,
 }
 
 impl DebugClient {
@@ -154,6 +378,8 @@ impl DebugClient {
             dataspaces,
             symbols,
             system,
+            reg_cache: Default::default(),
+            event_context: Default::default(),
         })
     }
 
@@ -166,6 +392,35 @@ impl DebugClient {
         }
     }
 
+    /// Create a new client for the calling thread, connected to the same
+    /// session. The engine only allows a client to be used from the thread
+    /// that created it; to use the engine from another thread, send it a
+    /// [`ClientHandle`] (see [`DebugClient::handle`]) and create its client
+    /// there.
+    pub fn create_client_copy(&self) -> Result<Self> {
+        let client = unsafe { self.client.CreateClient() }.context("CreateClient failed")?;
+
+        Self::new(&client.cast()?)
+    }
+
+    /// Get a handle to this client that can be sent to another thread, like a
+    /// background worker that needs to log to the debugger.
+    ///
+    /// ```ignore
+    /// let handle = client.handle()?;
+    /// std::thread::spawn(move || -> anyhow::Result<()> {
+    ///     let client = handle.create_client()?;
+    ///     client.logln("Hello from a worker")?;
+    ///
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn handle(&self) -> Result<ClientHandle> {
+        Ok(ClientHandle {
+            client: self.client.cast()?,
+        })
+    }
+
     /// Output a message `s`.
     fn output<Str>(&self, mask: u32, s: Str) -> Result<()>
     where
@@ -193,6 +448,85 @@ impl DebugClient {
         self.output(DEBUG_OUTPUT_NORMAL, "\n")
     }
 
+    /// Log a warning followed by a new line in the debugging window.
+    pub fn log_warning<Str>(&self, args: Str) -> Result<()>
+    where
+        Str: Into<Vec<u8>>,
+    {
+        self.output(DEBUG_OUTPUT_WARNING, args)?;
+        self.output(DEBUG_OUTPUT_WARNING, "\n")
+    }
+
+    /// Log an error followed by a new line in the debugging window.
+    pub fn log_error<Str>(&self, args: Str) -> Result<()>
+    where
+        Str: Into<Vec<u8>>,
+    {
+        self.output(DEBUG_OUTPUT_ERROR, args)?;
+        self.output(DEBUG_OUTPUT_ERROR, "\n")
+    }
+
+    /// Log a message followed by a new line in the debugging window, only
+    /// displayed if verbose output is enabled (`.outmask`).
+    pub fn log_verbose<Str>(&self, args: Str) -> Result<()>
+    where
+        Str: Into<Vec<u8>>,
+    {
+        self.output(DEBUG_OUTPUT_VERBOSE, args)?;
+        self.output(DEBUG_OUTPUT_VERBOSE, "\n")
+    }
+
+    /// Start logging the output of the engine to the file at `path`, like
+    /// `.logopen`.
+    pub fn open_log_file(&self, path: impl AsRef<Path>, flags: LogFileFlags) -> Result<()> {
+        let path = path.as_ref();
+        let cstr = path
+            .to_str()
+            .and_then(|p| CString::new(p).ok())
+            .with_context(|| format!("failed to convert {path:?} to CString"))?;
+
+        unsafe { self.control.OpenLogFile2(cstr.as_pcstr(), flags.bits()) }
+            .with_context(|| format!("OpenLogFile2({path:?}) failed"))
+    }
+
+    /// Stop logging the output of the engine, like `.logclose`.
+    pub fn close_log_file(&self) -> Result<()> {
+        unsafe { self.control.CloseLogFile() }.context("CloseLogFile failed")
+    }
+
+    /// Get the path of the log file and how it is written, if there is one.
+    pub fn log_file(&self) -> Result<Option<(PathBuf, LogFileFlags)>> {
+        let mut flags = 0;
+        let path = engine_string(|buffer, size| unsafe {
+            self.control.GetLogFile2(buffer, size, &mut flags)
+        });
+
+        match path {
+            Ok(path) => Ok(Some((path.into(), LogFileFlags::from_bits_retain(flags)))),
+            // `E_NOINTERFACE` signals that there is no log file.
+            Err(e)
+                if e.downcast_ref::<windows::core::Error>()
+                    .is_some_and(|e| e.code() == E_NOINTERFACE) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.context("GetLogFile2 failed")),
+        }
+    }
+
+    /// Get the kinds of output this client receives.
+    pub fn output_mask(&self) -> Result<OutputMask> {
+        let mask = unsafe { self.client.GetOutputMask() }.context("GetOutputMask failed")?;
+
+        Ok(OutputMask::from_bits_retain(mask))
+    }
+
+    /// Set the kinds of output this client receives.
+    pub fn set_output_mask(&self, mask: OutputMask) -> Result<()> {
+        unsafe { self.client.SetOutputMask(mask.bits()) }
+            .with_context(|| format!("SetOutputMask({mask:?}) failed"))
+    }
+
     /// Execute a debugger command.
     pub fn exec<Str>(&self, cmd: Str) -> Result<()>
     where
@@ -209,6 +543,305 @@ impl DebugClient {
         .with_context(|| format!("Execute({:?}) failed", cstr))
     }
 
+    /// Execute a debugger command and return its output instead of sending it
+    /// to the console.
+    pub fn exec_capture<Str>(&self, cmd: Str) -> Result<String>
+    where
+        Str: Into<Vec<u8>>,
+    {
+        let cstr = CString::new(cmd.into())?;
+        self.capture_output(|dbg| {
+            unsafe {
+                dbg.control.Execute(
+                    DEBUG_OUTCTL_THIS_CLIENT,
+                    cstr.as_pcstr(),
+                    DEBUG_EXECUTE_DEFAULT,
+                )
+            }
+            .with_context(|| format!("Execute({:?}) failed", cstr))
+        })
+    }
+
+    /// Invoke `f` and return the output it produced instead of sending it to
+    /// the console.
+    fn capture_output(&self, f: impl FnOnce(&Self) -> Result<()>) -> Result<String> {
+        let previous = unsafe { self.client.GetOutputCallbacksWide() }.ok();
+        let collector = OutputCollector::default();
+        self.set_output_callbacks(collector.clone())?;
+
+        let res = f(self);
+
+        // N.B: Restore the previous callbacks even if `f` failed.
+        unsafe { self.client.SetOutputCallbacksWide(previous.as_ref()) }
+            .context("SetOutputCallbacksWide failed")?;
+
+        res?;
+
+        Ok(collector.take())
+    }
+
+    /// Get the handle of the extension DLL at `path` (like `ext` or
+    /// `C:\sos.dll`), loading it if it isn't already.
+    pub fn extension(&self, path: &str) -> Result<u64> {
+        let cstr = CString::new(path).context("failed to convert path to CString")?;
+        if let Ok(handle) = unsafe { self.control.GetExtensionByPath(cstr.as_pcstr()) } {
+            return Ok(handle);
+        }
+
+        unsafe { self.control.AddExtension(cstr.as_pcstr(), 0) }
+            .with_context(|| format!("AddExtension({path:?}) failed"))
+    }
+
+    /// Unload the extension DLL identified by `handle`.
+    pub fn remove_extension(&self, handle: u64) -> Result<()> {
+        unsafe { self.control.RemoveExtension(handle) }
+            .with_context(|| format!("RemoveExtension({handle:#x}) failed"))
+    }
+
+    /// Invoke the command `function` (like `address`) of the extension
+    /// identified by `handle` with `args`.
+    pub fn call_extension(&self, handle: u64, function: &str, args: &str) -> Result<()> {
+        let function_cstr =
+            CString::new(function).context("failed to convert function to CString")?;
+        let args_cstr = CString::new(args).context("failed to convert arguments to CString")?;
+
+        unsafe {
+            self.control
+                .CallExtension(handle, function_cstr.as_pcstr(), args_cstr.as_pcstr())
+        }
+        .with_context(|| format!("CallExtension({function:?}, {args:?}) failed"))
+    }
+
+    /// Invoke the command `function` of the extension identified by `handle`
+    /// with `args`, and return its output instead of sending it to the
+    /// console.
+    pub fn call_extension_capture(
+        &self,
+        handle: u64,
+        function: &str,
+        args: &str,
+    ) -> Result<String> {
+        self.capture_output(|dbg| dbg.call_extension(handle, function, args))
+    }
+
+    /// Get the execution status of the engine.
+    pub fn execution_status(&self) -> Result<ExecutionStatus> {
+        let status =
+            unsafe { self.control.GetExecutionStatus() }.context("GetExecutionStatus failed")?;
+
+        ExecutionStatus::from_status(status.into())
+            .with_context(|| format!("unknown execution status {status:#x}"))
+    }
+
+    /// Set the execution status of the engine, to resume or step the target.
+    pub fn set_execution_status(&self, status: ExecutionStatus) -> Result<()> {
+        unsafe { self.control.SetExecutionStatus(status.as_status()) }
+            .with_context(|| format!("SetExecutionStatus({status:?}) failed"))
+    }
+
+    /// Wait for the next event of the target, at most `timeout` if specified;
+    /// returns `false` if the wait timed out. This can't be used from event
+    /// callbacks.
+    pub fn wait_for_event(&self, timeout: Option<Duration>) -> Result<bool> {
+        // N.B: `INFINITE` is `u32::MAX`.
+        let timeout = timeout.map_or(u32::MAX, |t| {
+            t.as_millis().try_into().unwrap_or(u32::MAX - 1)
+        });
+
+        // N.B: `WaitForEvent` returns `S_FALSE` when the wait times out, which
+        // the generated wrapper maps to `Ok(())`, so we call through the vtable.
+        let hr = unsafe {
+            (Interface::vtable(&self.control).WaitForEvent)(
+                Interface::as_raw(&self.control),
+                DEBUG_WAIT_DEFAULT,
+                timeout,
+            )
+        };
+
+        hr.ok().context("WaitForEvent failed")?;
+
+        Ok(hr == S_OK)
+    }
+
+    /// Deliver the callbacks registered on this client, at most `timeout` if
+    /// specified; returns `false` if the wait timed out. Only the thread that
+    /// created the client receives its callbacks, so a thread other than the
+    /// one waiting for events needs to call this to receive them.
+    pub fn dispatch_callbacks(&self, timeout: Option<Duration>) -> Result<bool> {
+        // N.B: `INFINITE` is `u32::MAX`.
+        let timeout = timeout.map_or(u32::MAX, |t| {
+            t.as_millis().try_into().unwrap_or(u32::MAX - 1)
+        });
+
+        // N.B: `DispatchCallbacks` returns `S_FALSE` when the wait times out,
+        // like `WaitForEvent`.
+        let hr = unsafe {
+            (Interface::vtable(&self.client).DispatchCallbacks)(
+                Interface::as_raw(&self.client),
+                timeout,
+            )
+        };
+
+        hr.ok().context("DispatchCallbacks failed")?;
+
+        Ok(hr == S_OK)
+    }
+
+    /// Make [`DebugClient::dispatch_callbacks`] return on `client`; this is
+    /// meant to be called from another thread than the one dispatching.
+    pub fn exit_dispatch(&self, client: &DebugClient) -> Result<()> {
+        let client = client.client.cast::<IDebugClient>()?;
+
+        unsafe { self.client.ExitDispatch(&client) }.context("ExitDispatch failed")
+    }
+
+    /// Resume the target and return once it breaks back into the debugger.
+    pub fn go(&self) -> Result<()> {
+        self.run(ExecutionStatus::Go)
+    }
+
+    /// Execute `n` instructions, stepping into calls, and return once done.
+    pub fn step_into(&self, n: usize) -> Result<()> {
+        self.step(ExecutionStatus::StepInto, n)
+    }
+
+    /// Execute `n` instructions, stepping over calls, and return once done.
+    pub fn step_over(&self, n: usize) -> Result<()> {
+        self.step(ExecutionStatus::StepOver, n)
+    }
+
+    /// Run until `n` branch instructions are executed, and return once done.
+    pub fn step_branch(&self, n: usize) -> Result<()> {
+        self.step(ExecutionStatus::StepBranch, n)
+    }
+
+    /// Resume the current thread until it reaches `addr`, and invoke `f`.
+    pub fn run_to<T>(&self, addr: u64, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        self.run_until(addr, |_| Ok(true))?;
+
+        f(self)
+    }
+
+    /// Resume the current thread until the current function returns to its
+    /// caller, and invoke `f`; the return value is in the return register.
+    pub fn run_to_return<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        let ret = self.return_offset()?;
+        let sp = self.sp()?;
+
+        // N.B: A recursive call can reach the return address first, so only stop
+        // once the stack of the current function is unwound.
+        self.run_until(ret, |dbg| Ok(dbg.sp()? > sp))?;
+
+        f(self)
+    }
+
+    /// Resume the current thread until it reaches `addr` and `reached` agrees,
+    /// using a temporary breakpoint.
+    fn run_until(&self, addr: u64, reached: impl Fn(&Self) -> Result<bool>) -> Result<()> {
+        let thread_id =
+            unsafe { self.system.GetCurrentThreadId() }.context("GetCurrentThreadId failed")?;
+
+        // N.B: The breakpoint isn't one-shot as it might be hit several times
+        // before `reached` agrees.
+        let bp = self.add_breakpoint(BreakpointType::Code, None)?;
+        let res = (|| -> Result<()> {
+            bp.set_offset(addr)?;
+            bp.set_match_thread(thread_id)?;
+            bp.set_flags(BreakpointFlags::ENABLED)?;
+
+            loop {
+                self.go()?;
+
+                let ip = self.ip()?;
+                if ip != addr {
+                    bail!("the target stopped at {ip:#x} before reaching {addr:#x}");
+                }
+
+                if reached(self)? {
+                    return Ok(());
+                }
+            }
+        })();
+
+        let removed = self.remove_breakpoint(bp);
+        res?;
+
+        removed
+    }
+
+    /// Set the execution status and wait until the target breaks back into the
+    /// debugger.
+    fn run(&self, status: ExecutionStatus) -> Result<()> {
+        self.set_execution_status(status)?;
+        self.wait_for_event(None)?;
+
+        if self.execution_status()? == ExecutionStatus::NoDebuggee {
+            bail!("the target is gone");
+        }
+
+        Ok(())
+    }
+
+    fn step(&self, status: ExecutionStatus, n: usize) -> Result<()> {
+        for i in 0..n {
+            if self.get_interrupt()? {
+                bail!("interrupted after {i} steps");
+            }
+
+            self.run(status)
+                .with_context(|| format!("failed to step after {i} steps"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Request a break into the debugger, like Ctrl-Break would.
+    pub fn interrupt(&self) -> Result<()> {
+        unsafe { self.control.SetInterrupt(DEBUG_INTERRUPT_ACTIVE) }.context("SetInterrupt failed")
+    }
+
+    /// Was an interrupt (like Ctrl-C) requested by the user? This clears the
+    /// interrupt, so long-running commands can poll it to know when to stop.
+    pub fn get_interrupt(&self) -> Result<bool> {
+        // N.B: `GetInterrupt` returns `S_FALSE` when there is no interrupt, which
+        // the generated wrapper maps to `Ok(())`, so we call through the vtable.
+        let hr = unsafe {
+            (Interface::vtable(&self.control).GetInterrupt)(Interface::as_raw(&self.control))
+        };
+
+        hr.ok().context("GetInterrupt failed")?;
+
+        Ok(hr == S_OK)
+    }
+
+    /// Get the return address of the current function.
+    pub fn return_offset(&self) -> Result<u64> {
+        unsafe { self.control.GetReturnOffset() }.context("GetReturnOffset failed")
+    }
+
+    /// Get the address of the instruction `delta` instructions away from the
+    /// one at `addr`; a negative `delta` walks backwards.
+    pub fn near_instruction(&self, addr: u64, delta: i32) -> Result<u64> {
+        unsafe { self.control.GetNearInstruction(addr, delta) }
+            .with_context(|| format!("GetNearInstruction({delta}) failed at {addr:#x}"))
+    }
+
+    /// Assemble `instr` (like `jmp rax`) and write it in the memory of the
+    /// target at `addr`; the address following the instruction is returned.
+    pub fn assemble(&self, addr: u64, instr: &str) -> Result<u64> {
+        let cstr = CString::new(instr).context("failed to convert instruction to CString")?;
+        unsafe { self.control.Assemble(addr, cstr.as_pcstr()) }
+            .with_context(|| format!("Assemble({instr:?}) failed at {addr:#x}"))
+    }
+
+    /// Assemble `instrs` one after the other starting at `addr`; the address
+    /// following the last instruction is returned.
+    pub fn assemble_all(&self, addr: u64, instrs: &[&str]) -> Result<u64> {
+        instrs
+            .iter()
+            .try_fold(addr, |addr, instr| self.assemble(addr, instr))
+    }
+
     /// Get up to N stack frames in the current debugger context.
     pub fn context_stack_frames(&self, n: usize) -> Result<Vec<DEBUG_STACK_FRAME>> {
         let mut stack = vec![DEBUG_STACK_FRAME::default(); n];
@@ -231,10 +864,76 @@ impl DebugClient {
         Ok(stack)
     }
 
+    /// Get up to N stack frames in the current debugger context, resolving
+    /// their symbols and source lines when possible.
+    pub fn stack_trace(&self, n: usize) -> Result<StackTrace> {
+        let frames = self
+            .context_stack_frames(n)?
+            .iter()
+            .map(|raw| {
+                let mut frame = StackFrame::from_raw(raw);
+                frame.symbol = self.symbol_by_offset(frame.instruction_offset).ok();
+                frame.line = self.line_by_offset(frame.instruction_offset).ok();
+
+                frame
+            })
+            .collect();
+
+        Ok(StackTrace { frames })
+    }
+
+    /// Get up to N stack frames in the current debugger context, along with
+    /// the registers as they were in each frame. Only Intel x64 targets are
+    /// supported.
+    pub fn context_stack_frame_contexts(&self, n: usize) -> Result<Vec<FrameContext>> {
+        if self.processor_type()? != IMAGE_FILE_MACHINE_AMD64 {
+            bail!("frame contexts are only supported on Intel x64 targets");
+        }
+
+        let mut stack = vec![DEBUG_STACK_FRAME::default(); n];
+        let mut contexts = vec![0u8; n * X64_CONTEXT_SIZE];
+        let mut frames_filled = 0;
+        unsafe {
+            self.control.GetContextStackTrace(
+                None,
+                0,
+                Some(&mut stack),
+                Some(contexts.as_mut_ptr().cast()),
+                contexts.len().try_into()?,
+                X64_CONTEXT_SIZE.try_into()?,
+                Some(&mut frames_filled),
+            )
+        }
+        .context("GetContextStackTrace failed")?;
+
+        stack
+            .into_iter()
+            .zip(contexts.chunks_exact(X64_CONTEXT_SIZE))
+            .take(frames_filled.try_into()?)
+            .map(|(frame, raw)| {
+                Ok(FrameContext {
+                    frame,
+                    context: X64Context::from_context_bytes(raw)?,
+                })
+            })
+            .collect()
+    }
+
     /// Setup an object to receive debugger event callbacks.
     pub fn set_event_callbacks<E: EventCallbacks + 'static>(&self, e: E) -> Result<()> {
+        self.set_event_callbacks_with_policy(e, PanicPolicy::default())
+    }
+
+    /// Setup an object to receive debugger event callbacks, and decide what
+    /// happens when one of them panics.
+    pub fn set_event_callbacks_with_policy<E: EventCallbacks + 'static>(
+        &self,
+        e: E,
+        panic_policy: PanicPolicy,
+    ) -> Result<()> {
         let callbacks = Box::new(e);
-        let callbacks: IUnknown = DbgEventCallbacks::new(self.clone(), callbacks).into();
+        let callbacks: IUnknown =
+            DbgEventCallbacks::new(self.clone(), callbacks, panic_policy).into();
 
         unsafe {
             self.client
@@ -243,6 +942,111 @@ impl DebugClient {
         .context("SetEventCallbacks failed")
     }
 
+    /// Get the event callbacks object currently installed on the client, if
+    /// any; it might not come from this crate.
+    pub fn event_callbacks(&self) -> Result<Option<IDebugEventCallbacks>> {
+        match unsafe { self.client.GetEventCallbacks() } {
+            Ok(callbacks) => Ok(Some(callbacks)),
+            // N.B: A NULL interface turns into an empty error.
+            Err(e) if e.code().is_ok() => Ok(None),
+            Err(e) => Err(e).context("GetEventCallbacks failed"),
+        }
+    }
+
+    /// Uninstall the event callbacks of the client.
+    pub fn clear_event_callbacks(&self) -> Result<()> {
+        self.restore_event_callbacks(None)
+    }
+
+    /// Install back event callbacks returned by
+    /// [`DebugClient::event_callbacks`] or
+    /// [`DebugClient::set_event_callbacks_chained`].
+    pub fn restore_event_callbacks(&self, callbacks: Option<&IDebugEventCallbacks>) -> Result<()> {
+        unsafe { self.client.SetEventCallbacks(callbacks) }.context("SetEventCallbacks failed")
+    }
+
+    /// Setup an object to receive debugger event callbacks, while still
+    /// forwarding the events to the event callbacks that were installed
+    /// before, which are returned so that they can be restored with
+    /// [`DebugClient::restore_event_callbacks`].
+    pub fn set_event_callbacks_chained<E: EventCallbacks + 'static>(
+        &self,
+        e: E,
+    ) -> Result<Option<IDebugEventCallbacks>> {
+        let previous = self.event_callbacks()?;
+        let dispatcher = EventDispatcher::new();
+        if let Some(previous) = &previous {
+            dispatcher.register(ForeignEventCallbacks(previous.clone()));
+        }
+
+        dispatcher.register(e);
+        self.set_event_callbacks(dispatcher)?;
+
+        Ok(previous)
+    }
+
+    /// Setup an object to receive the text output by the engine for this
+    /// client.
+    pub fn set_output_callbacks<O: OutputCallbacks + 'static>(&self, o: O) -> Result<()> {
+        let callbacks = Box::new(o);
+        let callbacks: IUnknown = DbgOutputCallbacks::new(callbacks).into();
+
+        unsafe {
+            self.client
+                .SetOutputCallbacksWide(&callbacks.cast::<IDebugOutputCallbacksWide>()?)
+        }
+        .context("SetOutputCallbacksWide failed")
+    }
+
+    /// Setup an object to provide input to the engine when it requests some.
+    pub fn set_input_callbacks<I: InputCallbacks + 'static>(&self, i: I) -> Result<()> {
+        let callbacks = Box::new(i);
+        let callbacks: IUnknown = DbgInputCallbacks::new(self.clone(), callbacks).into();
+
+        unsafe {
+            self.client
+                .SetInputCallbacks(&callbacks.cast::<IDebugInputCallbacks>()?)
+        }
+        .context("SetInputCallbacks failed")
+    }
+
+    /// Provide a line of input to the engine, typically from
+    /// [`InputCallbacks::start_input`].
+    pub fn return_input<Str>(&self, line: Str) -> Result<()>
+    where
+        Str: Into<Vec<u8>>,
+    {
+        let cstr = CString::new(line.into())?;
+        unsafe { self.control.ReturnInput(cstr.as_pcstr()) }.context("ReturnInput failed")
+    }
+
+    /// Display `prompt` and wait for the user to enter a line of input, like
+    /// an answer to a yes / no question.
+    pub fn read_line<Str>(&self, prompt: Str) -> Result<String>
+    where
+        Str: Into<Vec<u8>>,
+    {
+        self.log(prompt)?;
+
+        let mut buffer = vec![0u8; 0x1_000];
+        unsafe { self.control.Input(&mut buffer, None) }.context("Input failed")?;
+
+        let line = CStr::from_bytes_until_nul(&buffer)
+            .context("input is not nul terminated")?
+            .to_string_lossy();
+
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Stop receiving the text output by the engine for this client.
+    pub fn clear_output_callbacks(&self) -> Result<()> {
+        unsafe {
+            self.client
+                .SetOutputCallbacksWide(None::<&IDebugOutputCallbacksWide>)
+        }
+        .context("SetOutputCallbacksWide failed")
+    }
+
     /// Create a new breakpoint.
     pub fn add_breakpoint(
         &self,
@@ -262,6 +1066,18 @@ impl DebugClient {
         DebugBreakpoint::new(bp)
     }
 
+    /// Create an enabled code breakpoint on the first instruction of the
+    /// source line `line` of `file` (like `driver.c`); this requires private
+    /// symbols.
+    pub fn breakpoint_at_line(&self, file: &str, line: u32) -> Result<DebugBreakpoint> {
+        let offset = self.offset_by_line(file, line)?;
+        let bp = self.add_breakpoint(BreakpointType::Code, None)?;
+        bp.set_offset(offset)?;
+        bp.set_flags(BreakpointFlags::ENABLED)?;
+
+        Ok(bp)
+    }
+
     /// Remove a previously created breakpoint.
     pub fn remove_breakpoint(
         &self,
@@ -275,16 +1091,270 @@ impl DebugClient {
         Ok(())
     }
     
-    /// Get the register indices from names.
-    pub fn reg_indices(&self, names: &[&str]) -> Result<Vec<u32>> {
-        let mut indices = Vec::with_capacity(names.len());
-        for name in names {
-            let indice = unsafe {
-                self.registers
+    /// Get the number of event filters of each kind.
+    pub fn event_filter_counts(&self) -> Result<EventFilterCounts> {
+        let mut counts = EventFilterCounts::default();
+        unsafe {
+            self.control.GetNumberEventFilters(
+                &mut counts.specific_events,
+                &mut counts.specific_exceptions,
+                &mut counts.arbitrary_exceptions,
+            )
+        }
+        .context("GetNumberEventFilters failed")?;
+
+        Ok(counts)
+    }
+
+    /// Get the filters of the events that aren't exceptions.
+    pub fn specific_filters(&self) -> Result<Vec<SpecificFilter>> {
+        let counts = self.event_filter_counts()?;
+        let mut params =
+            vec![DEBUG_SPECIFIC_FILTER_PARAMETERS::default(); counts.specific_events as usize];
+        if params.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        unsafe { self.control.GetSpecificFilterParameters(0, &mut params) }
+            .context("GetSpecificFilterParameters failed")?;
+
+        params
+            .iter()
+            .zip(0..)
+            .map(|(params, index)| {
+                let argument = (params.ArgumentSize != 0)
+                    .then(|| {
+                        engine_string(|buffer, size| unsafe {
+                            self.control.GetSpecificFilterArgument(index, buffer, size)
+                        })
+                        .with_context(|| format!("GetSpecificFilterArgument failed for {index}"))
+                    })
+                    .transpose()?;
+
+                Ok(SpecificFilter {
+                    index,
+                    text: self.event_filter_text(index, params.TextSize)?,
+                    execution: FilterExecution::from_option(params.ExecutionOption)?,
+                    continue_option: FilterContinue::from_option(params.ContinueOption)?,
+                    command: self.event_filter_command(index, params.CommandSize)?,
+                    argument,
+                })
+            })
+            .collect()
+    }
+
+    /// Configure the filter at `index` of an event that isn't an exception.
+    pub fn set_specific_filter(
+        &self,
+        index: u32,
+        execution: FilterExecution,
+        continue_option: FilterContinue,
+    ) -> Result<()> {
+        let params = DEBUG_SPECIFIC_FILTER_PARAMETERS {
+            ExecutionOption: execution.as_option(),
+            ContinueOption: continue_option.as_option(),
+            ..Default::default()
+        };
+
+        unsafe { self.control.SetSpecificFilterParameters(index, &[params]) }
+            .with_context(|| format!("SetSpecificFilterParameters failed for {index}"))
+    }
+
+    /// Set the argument of the filter at `index` of an event that isn't an
+    /// exception, like the name of the module for module loads.
+    pub fn set_specific_filter_argument(&self, index: u32, argument: &str) -> Result<()> {
+        let argument = CString::new(argument).context("failed to convert argument to CString")?;
+        unsafe {
+            self.control
+                .SetSpecificFilterArgument(index, argument.as_pcstr())
+        }
+        .with_context(|| format!("SetSpecificFilterArgument failed for {index}"))
+    }
+
+    /// Get the exception filters.
+    pub fn exception_filters(&self) -> Result<Vec<ExceptionFilter>> {
+        let counts = self.event_filter_counts()?;
+        let mut params =
+            vec![DEBUG_EXCEPTION_FILTER_PARAMETERS::default(); counts.exceptions() as usize];
+        if params.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        unsafe {
+            self.control.GetExceptionFilterParameters(
+                params.len() as u32,
+                None,
+                counts.specific_events,
+                params.as_mut_ptr(),
+            )
+        }
+        .context("GetExceptionFilterParameters failed")?;
+
+        params
+            .iter()
+            .zip(counts.specific_events..)
+            .map(|(params, index)| {
+                let second_command = if params.SecondCommandSize != 0 {
+                    engine_string(|buffer, size| unsafe {
+                        self.control
+                            .GetExceptionFilterSecondCommand(index, buffer, size)
+                    })
+                    .with_context(|| {
+                        format!("GetExceptionFilterSecondCommand failed for {index}")
+                    })?
+                } else {
+                    String::new()
+                };
+
+                Ok(ExceptionFilter {
+                    index,
+                    code: params.ExceptionCode,
+                    text: self.event_filter_text(index, params.TextSize)?,
+                    execution: FilterExecution::from_option(params.ExecutionOption)?,
+                    continue_option: FilterContinue::from_option(params.ContinueOption)?,
+                    command: self.event_filter_command(index, params.CommandSize)?,
+                    second_command,
+                })
+            })
+            .collect()
+    }
+
+    /// Configure the filter of the exception `code`, like `0xe06d7363` for
+    /// C++ exceptions; an arbitrary exception filter is added if the engine
+    /// doesn't have one for it.
+    pub fn set_exception_filter(
+        &self,
+        code: u32,
+        execution: FilterExecution,
+        continue_option: FilterContinue,
+    ) -> Result<()> {
+        let params = DEBUG_EXCEPTION_FILTER_PARAMETERS {
+            ExecutionOption: execution.as_option(),
+            ContinueOption: continue_option.as_option(),
+            ExceptionCode: code,
+            ..Default::default()
+        };
+
+        unsafe { self.control.SetExceptionFilterParameters(&[params]) }
+            .with_context(|| format!("SetExceptionFilterParameters failed for {code:#x}"))
+    }
+
+    /// Set the command executed when the event of the filter at `index`
+    /// occurs; for exceptions, it is executed on first-chance.
+    pub fn set_event_filter_command(&self, index: u32, command: &str) -> Result<()> {
+        let command = CString::new(command).context("failed to convert command to CString")?;
+        unsafe {
+            self.control
+                .SetEventFilterCommand(index, command.as_pcstr())
+        }
+        .with_context(|| format!("SetEventFilterCommand failed for {index}"))
+    }
+
+    /// Set the command executed on second-chance exceptions for the exception
+    /// filter at `index`.
+    pub fn set_exception_filter_second_command(&self, index: u32, command: &str) -> Result<()> {
+        let command = CString::new(command).context("failed to convert command to CString")?;
+        unsafe {
+            self.control
+                .SetExceptionFilterSecondCommand(index, command.as_pcstr())
+        }
+        .with_context(|| format!("SetExceptionFilterSecondCommand failed for {index}"))
+    }
+
+    /// Get the description of the event of the filter at `index`; `size` is
+    /// the size reported by the filter parameters.
+    fn event_filter_text(&self, index: u32, size: u32) -> Result<String> {
+        if size == 0 {
+            return Ok(String::new());
+        }
+
+        engine_string(|buffer, size| unsafe {
+            self.control.GetEventFilterText(index, buffer, size)
+        })
+        .with_context(|| format!("GetEventFilterText failed for {index}"))
+    }
+
+    /// Get the command of the filter at `index`; `size` is the size reported
+    /// by the filter parameters.
+    fn event_filter_command(&self, index: u32, size: u32) -> Result<String> {
+        if size == 0 {
+            return Ok(String::new());
+        }
+
+        engine_string(|buffer, size| unsafe {
+            self.control.GetEventFilterCommand(index, buffer, size)
+        })
+        .with_context(|| format!("GetEventFilterCommand failed for {index}"))
+    }
+
+    /// Get the description of every register of the target.
+    pub fn registers(&self) -> Result<Vec<RegisterDescription>> {
+        let number =
+            unsafe { self.registers.GetNumberRegisters() }.context("GetNumberRegisters failed")?;
+
+        (0..number)
+            .map(|index| self.register_description(index))
+            .collect()
+    }
+
+    /// Get the description of the register identified by `index`.
+    pub fn register_description(&self, index: u32) -> Result<RegisterDescription> {
+        let mut desc = DEBUG_REGISTER_DESCRIPTION::default();
+        let mut name_size = 0;
+        unsafe {
+            self.registers
+                .GetDescription(index, None, Some(&mut name_size), Some(&mut desc))
+        }
+        .with_context(|| format!("GetDescription failed for {index}"))?;
+
+        let mut name = vec![0; name_size as usize];
+        unsafe {
+            self.registers
+                .GetDescription(index, Some(&mut name), None, None)
+        }
+        .with_context(|| format!("GetDescription failed for {index}"))?;
+
+        let name = CStr::from_bytes_until_nul(&name)
+            .context("failed to convert register name")?
+            .to_string_lossy()
+            .into_owned();
+
+        Ok(RegisterDescription::new(index, name, &desc))
+    }
+
+    /// Get the CPU context of the current thread of an Intel x64 target; the
+    /// registers are read with a single `GetValues` call.
+    pub fn context(&self) -> Result<X64Context> {
+        let indices = self.reg_indices(&X64_CONTEXT_REGISTERS)?;
+        let values = self.reg_values(&indices)?;
+
+        X64Context::from_values(&values)
+    }
+
+    /// Get the register indices from names. The indices are cached, so only
+    /// the names that haven't been seen yet on the effective processor are
+    /// resolved by the debugger engine.
+    pub fn reg_indices(&self, names: &[&str]) -> Result<Vec<u32>> {
+        let proc_type = unsafe { self.control.GetEffectiveProcessorType() }
+            .context("GetEffectiveProcessorType failed")?;
+
+        let mut cache = self.reg_cache.borrow_mut();
+        cache.sync(proc_type);
+
+        let mut indices = Vec::with_capacity(names.len());
+        for name in names {
+            if let Some(indice) = cache.get(name) {
+                indices.push(indice);
+                continue;
+            }
+
+            let indice = unsafe {
+                self.registers
                     .GetIndexByName(CString::new(*name)?.as_pcstr())
             }
             .with_context(|| format!("GetIndexByName failed for {name}"))?;
 
+            cache.insert(name, indice);
             indices.push(indice);
         }
 
@@ -365,6 +1435,189 @@ impl DebugClient {
         Ok(())
     }
 
+    /// Set the values of a set of registers identified by their names.
+    pub fn set_regs64(&self, regs: &[(&str, u64)]) -> Result<()> {
+        let names = regs.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let indices = self.reg_indices(&names)?;
+        let values = regs
+            .iter()
+            .map(|(_, value)| debugvalue_from_u64(*value))
+            .collect::<Vec<_>>();
+
+        self.set_reg_values(&indices, &values)
+    }
+
+    /// Set the value of multiple registers identified by their indices.
+    pub fn set_reg_values(&self, indices: &[u32], values: &[DEBUG_VALUE]) -> Result<()> {
+        if indices.len() != values.len() {
+            bail!(
+                "expected as many values ({}) as indices ({})",
+                values.len(),
+                indices.len()
+            );
+        }
+
+        unsafe {
+            self.registers.SetValues(
+                indices.len().try_into()?,
+                Some(indices.as_ptr()),
+                0,
+                values.as_ptr(),
+            )
+        }
+        .with_context(|| format!("SetValues failed for {indices:?}"))
+    }
+
+    /// Get the pseudo-register indices from names (`$teb`, `$peb`, `$ra`,
+    /// etc.).
+    pub fn pseudo_reg_indices(&self, names: &[&str]) -> Result<Vec<u32>> {
+        let mut indices = Vec::with_capacity(names.len());
+        for name in names {
+            let indice = unsafe {
+                self.registers
+                    .GetPseudoIndexByName(CString::new(*name)?.as_pcstr())
+            }
+            .with_context(|| format!("GetPseudoIndexByName failed for {name}"))?;
+
+            indices.push(indice);
+        }
+
+        Ok(indices)
+    }
+
+    /// Get the value of multiple pseudo-registers.
+    pub fn pseudo_reg_values(&self, indices: &[u32]) -> Result<Vec<DEBUG_VALUE>> {
+        let mut values = vec![DEBUG_VALUE::default(); indices.len()];
+        unsafe {
+            self.registers.GetPseudoValues(
+                DEBUG_REGSRC_DEBUGGEE,
+                indices.len().try_into()?,
+                Some(indices.as_ptr()),
+                0,
+                values.as_mut_ptr(),
+            )
+        }
+        .with_context(|| format!("GetPseudoValues failed for {indices:?}"))?;
+
+        Ok(values)
+    }
+
+    /// Get the values of a set of pseudo-registers identified by their names.
+    pub fn pseudo_regs64(&self, names: &[&str]) -> Result<Vec<u64>> {
+        let indices = self.pseudo_reg_indices(names)?;
+        let values = self.pseudo_reg_values(&indices)?;
+
+        values.into_iter().map(u64_from_debugvalue).collect()
+    }
+
+    /// Get the value of a pseudo-register identified by its name, like `$teb`.
+    pub fn pseudo_reg64(&self, name: &str) -> Result<u64> {
+        let v = self.pseudo_regs64(&[name])?;
+
+        Ok(v[0])
+    }
+
+    /// Set the value of a pseudo-register identified by its name, like `$t0`.
+    pub fn set_pseudo_reg64(&self, name: &str, value: u64) -> Result<()> {
+        let indices = self.pseudo_reg_indices(&[name])?;
+        let values = [debugvalue_from_u64(value)];
+        unsafe {
+            self.registers.SetPseudoValues(
+                DEBUG_REGSRC_DEBUGGEE,
+                1,
+                Some(indices.as_ptr()),
+                0,
+                values.as_ptr(),
+            )
+        }
+        .with_context(|| format!("SetPseudoValues failed for {name}"))
+    }
+
+    /// Set the [`u128`] value of a register identified by its name.
+    pub fn set_reg128(&self, name: &str, value: u128) -> Result<()> {
+        let indices = self.reg_indices(&[name])?;
+        unsafe {
+            self.registers
+                .SetValue(indices[0], &debugvalue_from_u128(value))
+        }
+        .with_context(|| format!("SetValue failed for {name}"))
+    }
+
+    /// Get the value of a vector register (like `xmm0`) identified by its name.
+    pub fn vector_reg(&self, name: &str) -> Result<Vector128> {
+        let v = self.regs128(&[name])?;
+
+        Ok(v[0].into())
+    }
+
+    /// Set the value of a vector register (like `xmm0`) identified by its name.
+    pub fn set_vector_reg(&self, name: &str, value: Vector128) -> Result<()> {
+        self.set_reg128(name, value.into())
+    }
+
+    /// Get the `rflags` / `eflags` register of the current thread.
+    pub fn rflags(&self) -> Result<Rflags> {
+        Ok(Rflags::from_bits_retain(self.reg64("efl")?))
+    }
+
+    /// Set the `rflags` / `eflags` register of the current thread.
+    pub fn set_rflags(&self, flags: Rflags) -> Result<()> {
+        self.set_reg64("efl", flags.bits())
+    }
+
+    /// Get the instruction pointer of the current thread.
+    pub fn ip(&self) -> Result<u64> {
+        unsafe { self.registers.GetInstructionOffset() }.context("GetInstructionOffset failed")
+    }
+
+    /// Get the stack pointer of the current thread.
+    pub fn sp(&self) -> Result<u64> {
+        unsafe { self.registers.GetStackOffset() }.context("GetStackOffset failed")
+    }
+
+    /// Get the frame pointer of the current thread.
+    pub fn frame_offset(&self) -> Result<u64> {
+        unsafe { self.registers.GetFrameOffset() }.context("GetFrameOffset failed")
+    }
+
+    /// Set the instruction pointer of the current thread.
+    pub fn set_ip(&self, value: u64) -> Result<()> {
+        let (ip, _, _) = self.ip_sp_fp_names()?;
+
+        self.set_reg64(ip, value)
+    }
+
+    /// Set the stack pointer of the current thread.
+    pub fn set_sp(&self, value: u64) -> Result<()> {
+        let (_, sp, _) = self.ip_sp_fp_names()?;
+
+        self.set_reg64(sp, value)
+    }
+
+    /// Set the frame pointer of the current thread.
+    pub fn set_frame_offset(&self, value: u64) -> Result<()> {
+        let (_, _, fp) = self.ip_sp_fp_names()?;
+
+        self.set_reg64(fp, value)
+    }
+
+    /// Get the names of the instruction, stack and frame pointer registers of
+    /// the effective processor.
+    fn ip_sp_fp_names(&self) -> Result<(&'static str, &'static str, &'static str)> {
+        let proc_type = unsafe { self.control.GetEffectiveProcessorType() }
+            .context("GetEffectiveProcessorType failed")?;
+
+        let names = match IMAGE_FILE_MACHINE(proc_type.try_into()?) {
+            IMAGE_FILE_MACHINE_AMD64 => ("rip", "rsp", "rbp"),
+            IMAGE_FILE_MACHINE_I386 => ("eip", "esp", "ebp"),
+            IMAGE_FILE_MACHINE_ARM64 => ("pc", "sp", "fp"),
+            IMAGE_FILE_MACHINE_ARMNT => ("pc", "sp", "r11"),
+            m => bail!("unsupported processor type {:#x}", m.0),
+        };
+
+        Ok(names)
+    }
+
     /// Get the value of a specific MSR.
     pub fn msr(&self, msr: u32) -> Result<u64> {
         unsafe { self.dataspaces.ReadMsr(msr) }.context("ReadMsr failed")
@@ -427,6 +1680,18 @@ impl DebugClient {
         ))
     }
 
+    /// Read a plain-old-data structure (like the ones describing the PE
+    /// format) from virtual memory.
+    pub(crate) fn read_virtual_pod<T: Copy>(&self, vaddr: u64) -> Result<T> {
+        let mut value = MaybeUninit::<T>::zeroed();
+        let buffer = unsafe {
+            std::slice::from_raw_parts_mut(value.as_mut_ptr().cast::<u8>(), mem::size_of::<T>())
+        };
+        self.read_virtual_exact(vaddr, buffer)?;
+
+        Ok(unsafe { value.assume_init() })
+    }
+
     /// Read virtual memory as a field.
     pub fn read_virtual_struct<
         T: zerocopy::AsBytes + zerocopy::FromBytes + zerocopy::FromZeroes,
@@ -440,6 +1705,11 @@ impl DebugClient {
         Ok(buffer)
     }
 
+    /// Write a field to virtual memory.
+    pub fn write_virtual_struct<T: zerocopy::AsBytes>(&self, vaddr: u64, value: &T) -> Result<()> {
+        self.write_virtual_exact(vaddr, value.as_bytes())
+    }
+
     /// Read an exact amount of virtual memory.
     pub fn read_virtual_exact(&self, vaddr: u64, buf: &mut [u8]) -> Result<()> {
         let amount_read = self.read_virtual(vaddr, buf)?;
@@ -470,107 +1740,1188 @@ impl DebugClient {
         Ok(usize::try_from(amount_read)?)
     }
 
-    /// Look up a module by name.
-    pub fn get_sym_module(&self, name: &str) -> Result<SymbolModule> {
-        let name_cstr = CString::new(name).context("failed to wrap module string")?;
-        let mut base = 0u64;
-        unsafe {
-            self.symbols
-                .GetModuleByModuleName(name_cstr.as_pcstr(), 0, None, Some(&mut base))
-        }
-        .context("GetModuleByModuleName failed")?;
-
-        Ok(SymbolModule::new(self.symbols.clone(), base))
-    }
-
-    /// Get the debuggee type.
-    pub fn debuggee_type(&self) -> Result<(u32, u32)> {
-        let mut class = 0;
-        let mut qualifier = 0;
-        unsafe { self.control.GetDebuggeeType(&mut class, &mut qualifier) }?;
+    /// Read `n` pointers at `vaddr`. The pointers are read using the pointer
+    /// size of the target, and are sign-extended to 64-bit on 32-bit targets.
+    pub fn read_pointers(&self, vaddr: u64, n: usize) -> Result<Vec<u64>> {
+        let mut ptrs = vec![0; n];
+        unsafe { self.dataspaces.ReadPointersVirtual(vaddr, &mut ptrs) }
+            .with_context(|| format!("ReadPointersVirtual failed for {vaddr:#x}"))?;
 
-        Ok((class, qualifier))
+        Ok(ptrs)
     }
 
-    /// Get the processor type of the target.
-    pub fn processor_type(&self) -> Result<IMAGE_FILE_MACHINE> {
-        let proc_type = unsafe { self.control.GetActualProcessorType() }
-            .context("GetActualProcessorType failed")?;
-
-        Ok(IMAGE_FILE_MACHINE(proc_type.try_into()?))
-    }
+    /// Read a pointer at `vaddr`.
+    pub fn read_pointer(&self, vaddr: u64) -> Result<u64> {
+        let ptrs = self.read_pointers(vaddr, 1)?;
 
-    /// Get the number of processors in the target.
-    pub fn processor_number(&self) -> Result<u32> {
-        unsafe { self.control.GetNumberProcessors() }.context("GetNumberProcessors failed")
+        Ok(ptrs[0])
     }
 
-    /// Get an address for a named symbol.
-    pub fn get_address_by_name<Str>(&self, symbol: Str) -> Result<u64>
-    where
-        Str: Into<Vec<u8>>,
-    {
-        let symbol_cstr = CString::new(symbol.into())?;
+    /// Write an exact amount of virtual memory.
+    pub fn write_virtual_exact(&self, vaddr: u64, buf: &[u8]) -> Result<()> {
+        let amount_written = self.write_virtual(vaddr, buf)?;
+        if amount_written != buf.len() {
+            bail!(
+                "expected to write_virtual {:#x} bytes, but wrote {:#x}",
+                buf.len(),
+                amount_written
+            );
+        }
 
-        unsafe { self.symbols.GetOffsetByName(symbol_cstr.as_pcstr()) }
-            .context("GetOffsetByName failed")
+        Ok(())
     }
 
-    /// Read a NULL terminated string at `addr`.
-    pub fn read_cstring_virtual(&self, addr: u64) -> Result<String> {
-        let maxbytes = 100;
-        let mut buffer = vec![0; maxbytes];
-        let mut length = 0;
+    /// Write virtual memory.
+    pub fn write_virtual(&self, vaddr: u64, buf: &[u8]) -> Result<usize> {
+        let mut amount_written = 0;
         unsafe {
-            self.dataspaces.ReadMultiByteStringVirtual(
-                addr,
-                maxbytes as u32,
-                Some(buffer.as_mut()),
-                Some(&mut length),
+            self.dataspaces.WriteVirtual(
+                vaddr,
+                buf.as_ptr().cast(),
+                buf.len().try_into()?,
+                Some(&mut amount_written),
             )
-        }?;
+        }
+        .context("WriteVirtual failed")?;
 
-        if length == 0 {
-            bail!("length is zero")
+        Ok(usize::try_from(amount_written)?)
+    }
+
+    /// Dump `len` bytes of virtual memory at `vaddr` into the file at `path`.
+    /// The memory is read in chunks and unreadable pages are written as zeros,
+    /// so that offsets in the file match the offsets in memory. This returns
+    /// the number of bytes that were actually read.
+    pub fn dump_range_to_file(&self, vaddr: u64, len: u64, path: impl AsRef<Path>) -> Result<u64> {
+        const CHUNK_SIZE: u64 = 0x1_00_00;
+        let path = path.as_ref();
+        let end = vaddr.checked_add(len).context("the range overflows")?;
+        let file = File::create(path).with_context(|| format!("failed to create {path:?}"))?;
+        let mut writer = BufWriter::new(file);
+        let mut buffer = vec![0; CHUNK_SIZE as usize];
+        let mut amount_read = 0;
+        let mut addr = vaddr;
+        while addr < end {
+            let size = (end - addr).min(CHUNK_SIZE);
+            let chunk = &mut buffer[..size as usize];
+            amount_read += self.read_virtual_lossy(addr, chunk);
+            writer
+                .write_all(chunk)
+                .with_context(|| format!("failed to write to {path:?}"))?;
+
+            addr += size;
         }
 
-        let length = length as usize;
-        buffer.resize(length - 1, 0);
+        writer
+            .flush()
+            .with_context(|| format!("failed to flush {path:?}"))?;
 
-        Ok(String::from_utf8_lossy(&buffer).into_owned())
+        Ok(amount_read)
     }
 
-    pub fn read_wstring_virtual(&self, addr: u64) -> Result<String> {
-        let maxbytes = 100;
-        let mut buffer = vec![0; maxbytes];
-        let mut length = 0;
-        unsafe {
-            self.dataspaces.ReadUnicodeStringVirtual(
-                addr,
-                maxbytes as u32,
-                65001, // CP_UTF8
-                Some(&mut buffer),
-                Some(&mut length),
-            )
+    /// Fill `buf` with the virtual memory at `vaddr`; unreadable pages are
+    /// filled with zeros. This returns the number of bytes that were actually
+    /// read.
+    pub fn read_virtual_lossy(&self, vaddr: u64, buf: &mut [u8]) -> u64 {
+        const PAGE_SIZE: u64 = 0x1_000;
+        let len = buf.len() as u64;
+        if self.read_virtual_exact(vaddr, buf).is_ok() {
+            return len;
         }
-        .context("ReadUnicodeStringVirtual failed")?;
 
-        if length == 0 {
-            bail!("length is zero")
-        }
+        // Fall back to reading page by page.
+        buf.fill(0);
+        let end = vaddr.saturating_add(len);
+        let mut amount_read = 0;
+        let mut page_addr = vaddr;
+        while page_addr < end {
+            let next = ((page_addr & !(PAGE_SIZE - 1)) + PAGE_SIZE).min(end);
+            let page = &mut buf[(page_addr - vaddr) as usize..(next - vaddr) as usize];
+            if self.read_virtual_exact(page_addr, page).is_ok() {
+                amount_read += next - page_addr;
+            } else {
+                page.fill(0);
+            }
 
-        let length = length as usize;
-        buffer.resize(length - 1, 0);
+            page_addr = next;
+        }
 
-        Ok(String::from_utf8_lossy(&buffer).into_owned())
+        amount_read
     }
 
-    pub fn get_current_process_id(&self) -> Result<u32> {
-        let process_id = unsafe {
-            self.system.GetCurrentProcessSystemId()
+    /// Decode up to `count` instructions at `addr` with `iced-x86`, which
+    /// exposes their operands (memory operands, branch targets, etc.) instead
+    /// of the text of the engine's disassembler. The instructions are decoded
+    /// for the effective processor; the decoding stops early at an invalid
+    /// instruction or at unreadable memory.
+    #[cfg(feature = "iced-x86")]
+    pub fn decode_instructions(
+        &self,
+        addr: u64,
+        count: usize,
+    ) -> Result<Vec<iced_x86::Instruction>> {
+        use iced_x86::{Decoder, DecoderOptions};
+
+        // N.B: An x86 instruction is at most 15 bytes long.
+        const MAX_INSTRUCTION_LEN: usize = 15;
+        let proc_type = unsafe { self.control.GetEffectiveProcessorType() }
+            .context("GetEffectiveProcessorType failed")?;
+
+        let bitness = match IMAGE_FILE_MACHINE(proc_type.try_into()?) {
+            IMAGE_FILE_MACHINE_AMD64 => 64,
+            IMAGE_FILE_MACHINE_I386 => 32,
+            m => bail!("cannot decode instructions for processor type {:#x}", m.0),
+        };
+
+        let mut bytes = vec![0; count.saturating_mul(MAX_INSTRUCTION_LEN)];
+        let amount_read = self
+            .read_virtual(addr, &mut bytes)
+            .with_context(|| format!("failed to read the instructions at {addr:#x}"))?;
+        bytes.truncate(amount_read);
+
+        let mut decoder = Decoder::with_ip(bitness, &bytes, addr, DecoderOptions::NONE);
+        let mut instructions = Vec::with_capacity(count);
+        while instructions.len() < count && decoder.can_decode() {
+            let instruction = decoder.decode();
+            if instruction.is_invalid() {
+                break;
+            }
+
+            instructions.push(instruction);
         }
-        .context("GetCurrentProcessId failed")?;
-        Ok(process_id)
+
+        Ok(instructions)
+    }
+
+    /// Read an exact amount of virtual memory, bypassing the engine's cache.
+    pub fn read_virtual_uncached_exact(&self, vaddr: u64, buf: &mut [u8]) -> Result<()> {
+        let amount_read = self.read_virtual_uncached(vaddr, buf)?;
+        if amount_read != buf.len() {
+            bail!(
+                "expected to read_virtual_uncached {:#x} bytes, but read {:#x}",
+                buf.len(),
+                amount_read
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Read virtual memory, bypassing the engine's cache.
+    pub fn read_virtual_uncached(&self, vaddr: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut amount_read = 0;
+        unsafe {
+            self.dataspaces.ReadVirtualUncached(
+                vaddr,
+                buf.as_mut_ptr().cast(),
+                buf.len().try_into()?,
+                Some(&mut amount_read),
+            )
+        }
+        .context("ReadVirtualUncached failed")?;
+
+        Ok(usize::try_from(amount_read)?)
+    }
+
+    /// Write an exact amount of virtual memory, bypassing the engine's cache.
+    pub fn write_virtual_uncached_exact(&self, vaddr: u64, buf: &[u8]) -> Result<()> {
+        let amount_written = self.write_virtual_uncached(vaddr, buf)?;
+        if amount_written != buf.len() {
+            bail!(
+                "expected to write_virtual_uncached {:#x} bytes, but wrote {:#x}",
+                buf.len(),
+                amount_written
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Write virtual memory, bypassing the engine's cache.
+    pub fn write_virtual_uncached(&self, vaddr: u64, buf: &[u8]) -> Result<usize> {
+        let mut amount_written = 0;
+        unsafe {
+            self.dataspaces.WriteVirtualUncached(
+                vaddr,
+                buf.as_ptr().cast(),
+                buf.len().try_into()?,
+                Some(&mut amount_written),
+            )
+        }
+        .context("WriteVirtualUncached failed")?;
+
+        Ok(usize::try_from(amount_written)?)
+    }
+
+    /// Find the first valid (readable) region of virtual memory in the `size`
+    /// bytes at `base`. This returns `None` if the whole range is invalid.
+    pub fn valid_region_virtual(&self, base: u64, size: u32) -> Result<Option<Range<u64>>> {
+        let mut valid_base = 0;
+        let mut valid_size = 0;
+        unsafe {
+            self.dataspaces
+                .GetValidRegionVirtual(base, size, &mut valid_base, &mut valid_size)
+        }
+        .with_context(|| format!("GetValidRegionVirtual failed for {base:#x} / {size:#x}"))?;
+
+        if valid_size == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(valid_base..(valid_base + u64::from(valid_size))))
+    }
+
+    /// Get the next address after `vaddr` at which the validity of the memory
+    /// changes (from valid to invalid, or the other way around).
+    pub fn next_differently_valid_offset_virtual(&self, vaddr: u64) -> Result<u64> {
+        unsafe { self.dataspaces.GetNextDifferentlyValidOffsetVirtual(vaddr) }
+            .with_context(|| format!("GetNextDifferentlyValidOffsetVirtual failed for {vaddr:#x}"))
+    }
+
+    /// Get every valid (readable) range of virtual memory in the `len` bytes at
+    /// `start`; this is useful to skip the holes of sparse dump files.
+    pub fn valid_regions_virtual(&self, start: u64, len: u64) -> Result<Vec<Range<u64>>> {
+        let end = start.checked_add(len).context("the range overflows")?;
+        let mut regions: Vec<Range<u64>> = Vec::new();
+        let mut addr = start;
+        while addr < end {
+            let size = u32::try_from(end - addr).unwrap_or(u32::MAX);
+            let Some(region) = self.valid_region_virtual(addr, size)? else {
+                addr += u64::from(size);
+                continue;
+            };
+
+            addr = region.end;
+
+            // Merge the region with the previous one if they are contiguous.
+            match regions.last_mut() {
+                Some(last) if last.end == region.start => last.end = region.end,
+                _ => regions.push(region),
+            }
+        }
+
+        Ok(regions)
+    }
+
+    /// Fill `len` bytes of virtual memory at `vaddr` by repeating `pattern`.
+    /// This returns the number of bytes written.
+    pub fn fill_virtual(&self, vaddr: u64, len: usize, pattern: &[u8]) -> Result<usize> {
+        let mut amount_filled = 0;
+        unsafe {
+            self.dataspaces.FillVirtual(
+                vaddr,
+                len.try_into()?,
+                pattern.as_ptr().cast(),
+                pattern.len().try_into()?,
+                Some(&mut amount_filled),
+            )
+        }
+        .with_context(|| format!("FillVirtual failed for {vaddr:#x}"))?;
+
+        Ok(usize::try_from(amount_filled)?)
+    }
+
+    /// Read an exact amount of physical memory.
+    pub fn read_physical_exact(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        caching: PhysicalCaching,
+    ) -> Result<()> {
+        let amount_read = self.read_physical(paddr, buf, caching)?;
+        if amount_read != buf.len() {
+            bail!(
+                "expected to read_physical {:#x} bytes, but read {:#x}",
+                buf.len(),
+                amount_read
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Read physical memory.
+    pub fn read_physical(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        caching: PhysicalCaching,
+    ) -> Result<usize> {
+        let mut amount_read = 0;
+        unsafe {
+            self.dataspaces.ReadPhysical2(
+                paddr,
+                caching.as_flags(),
+                buf.as_mut_ptr().cast(),
+                buf.len().try_into()?,
+                Some(&mut amount_read),
+            )
+        }
+        .context("ReadPhysical2 failed")?;
+
+        Ok(usize::try_from(amount_read)?)
+    }
+
+    /// Write an exact amount of physical memory.
+    pub fn write_physical_exact(
+        &self,
+        paddr: u64,
+        buf: &[u8],
+        caching: PhysicalCaching,
+    ) -> Result<()> {
+        let amount_written = self.write_physical(paddr, buf, caching)?;
+        if amount_written != buf.len() {
+            bail!(
+                "expected to write_physical {:#x} bytes, but wrote {:#x}",
+                buf.len(),
+                amount_written
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Write physical memory.
+    pub fn write_physical(
+        &self,
+        paddr: u64,
+        buf: &[u8],
+        caching: PhysicalCaching,
+    ) -> Result<usize> {
+        let mut amount_written = 0;
+        unsafe {
+            self.dataspaces.WritePhysical2(
+                paddr,
+                caching.as_flags(),
+                buf.as_ptr().cast(),
+                buf.len().try_into()?,
+                Some(&mut amount_written),
+            )
+        }
+        .context("WritePhysical2 failed")?;
+
+        Ok(usize::try_from(amount_written)?)
+    }
+
+    /// Query information about the memory region containing `vaddr`.
+    pub fn query_virtual(&self, vaddr: u64) -> Result<MemoryRegion> {
+        let mut info = MEMORY_BASIC_INFORMATION64::default();
+        unsafe { self.dataspaces.QueryVirtual(vaddr, &mut info) }
+            .with_context(|| format!("QueryVirtual failed for {vaddr:#x}"))?;
+
+        Ok(info.into())
+    }
+
+    /// Get an iterator over the memory regions of the current process.
+    pub fn memory_regions(&self) -> MemoryRegions<'_> {
+        MemoryRegions::new(self)
+    }
+
+    /// Search the `len` bytes of virtual memory at `start` for `pattern`; the
+    /// match has to start at an address aligned on `granularity`. This returns
+    /// the address of the first match.
+    pub fn search_virtual(
+        &self,
+        start: u64,
+        len: u64,
+        pattern: &[u8],
+        granularity: u32,
+    ) -> Result<u64> {
+        unsafe {
+            self.dataspaces.SearchVirtual2(
+                start,
+                len,
+                DEBUG_VSEARCH_DEFAULT,
+                pattern.as_ptr().cast(),
+                pattern.len().try_into()?,
+                granularity,
+            )
+        }
+        .with_context(|| format!("SearchVirtual2 failed for {start:#x} / {len:#x}"))
+    }
+
+    /// Search the `len` bytes of virtual memory at `start` for a `pattern`
+    /// where `None` entries match any byte (see
+    /// [`memory::parse_masked_pattern`]). The memory is read in chunks and
+    /// unreadable pages are skipped. This returns the addresses of every
+    /// match.
+    pub fn search_virtual_masked(
+        &self,
+        start: u64,
+        len: u64,
+        pattern: &[Option<u8>],
+    ) -> Result<Vec<u64>> {
+        const CHUNK_SIZE: usize = 0x1_00_00;
+        if pattern.is_empty() {
+            bail!("the pattern is empty");
+        }
+
+        let end = start
+            .checked_add(len)
+            .context("the search range overflows")?;
+
+        // Every chunk is read with `pattern.len() - 1` extra bytes, so that matches
+        // straddling two chunks are found as well.
+        let mut buffer = vec![0; CHUNK_SIZE + pattern.len() - 1];
+        let mut matches = Vec::new();
+        let mut addr = start;
+        while addr < end {
+            let size = buffer
+                .len()
+                .min((end - addr).try_into().unwrap_or(usize::MAX));
+            let chunk = &mut buffer[..size];
+            let runs = match self.read_virtual(addr, chunk) {
+                Ok(amount_read) if amount_read == size => vec![0..size],
+                // Fall back to reading page by page, and search the readable runs.
+                _ => self.read_virtual_pages(addr, chunk),
+            };
+
+            for run in runs {
+                matches.extend(
+                    memory::find_masked(&chunk[run.clone()], pattern)
+                        .map(|offset| run.start + offset)
+                        .filter(|offset| *offset < CHUNK_SIZE)
+                        .map(|offset| addr + offset as u64),
+                );
+            }
+
+            addr = addr.saturating_add(CHUNK_SIZE as u64);
+        }
+
+        Ok(matches)
+    }
+
+    /// Read the virtual memory at `vaddr` into `buf` page by page. This
+    /// returns the ranges of `buf` that were read, merged when contiguous.
+    fn read_virtual_pages(&self, vaddr: u64, buf: &mut [u8]) -> Vec<Range<usize>> {
+        const PAGE_SIZE: u64 = 0x1_000;
+        let end = vaddr.saturating_add(buf.len() as u64);
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        let mut page_addr = vaddr;
+        while page_addr < end {
+            let next = (page_addr & !(PAGE_SIZE - 1))
+                .saturating_add(PAGE_SIZE)
+                .min(end);
+            let page = (page_addr - vaddr) as usize..(next - vaddr) as usize;
+            if self
+                .read_virtual_exact(page_addr, &mut buf[page.clone()])
+                .is_ok()
+            {
+                match runs.last_mut() {
+                    Some(run) if run.end == page.start => run.end = page.end,
+                    _ => runs.push(page),
+                }
+            }
+
+            page_addr = next;
+        }
+
+        runs
+    }
+
+    /// Look up a module by name.
+    pub fn get_sym_module(&self, name: &str) -> Result<SymbolModule> {
+        let name_cstr = CString::new(name).context("failed to wrap module string")?;
+        let mut base = 0u64;
+        unsafe {
+            self.symbols
+                .GetModuleByModuleName(name_cstr.as_pcstr(), 0, None, Some(&mut base))
+        }
+        .context("GetModuleByModuleName failed")?;
+
+        Ok(SymbolModule::new(self.symbols.clone(), base))
+    }
+
+    /// Get the symbol closest to `addr`, along with the displacement of `addr`
+    /// from it.
+    pub fn symbol_by_offset(&self, addr: u64) -> Result<SymbolName> {
+        let mut displacement = 0;
+        let name = engine_string(|buffer, size| unsafe {
+            self.symbols
+                .GetNameByOffset(addr, buffer, size, Some(&mut displacement))
+        })
+        .with_context(|| format!("GetNameByOffset failed for {addr:#x}"))?;
+
+        Ok(SymbolName { name, displacement })
+    }
+
+    /// Get the address of the first instruction of the source line `line` of
+    /// `file`; this requires private symbols.
+    pub fn offset_by_line(&self, file: &str, line: u32) -> Result<u64> {
+        let file_cstr = CString::new(file).context("failed to convert file to CString")?;
+
+        unsafe { self.symbols.GetOffsetByLine(line, file_cstr.as_pcstr()) }
+            .with_context(|| format!("GetOffsetByLine failed for {file}({line})"))
+    }
+
+    /// Get the source line that `addr` belongs to; this requires private
+    /// symbols.
+    pub fn line_by_offset(&self, addr: u64) -> Result<SourceLine> {
+        let mut line = 0;
+        let mut displacement = 0;
+        let file = engine_string(|buffer, size| unsafe {
+            self.symbols.GetLineByOffset(
+                addr,
+                Some(&mut line),
+                buffer,
+                size,
+                Some(&mut displacement),
+            )
+        })
+        .with_context(|| format!("GetLineByOffset failed for {addr:#x}"))?;
+
+        Ok(SourceLine {
+            file,
+            line,
+            displacement,
+        })
+    }
+
+    /// Get the source line that `addr` belongs to in the inline frame
+    /// identified by `inline_context` (the `InlineFrameContext` field of a
+    /// `DEBUG_STACK_FRAME_EX`). This allows to resolve the lines of functions
+    /// that were inlined into their caller.
+    pub fn line_by_inline_context(&self, addr: u64, inline_context: u32) -> Result<SourceLine> {
+        let symbols = self.symbols.cast::<IDebugSymbols4>()?;
+        let mut line = 0;
+        let mut displacement = 0;
+        let file = engine_string(|buffer, size| unsafe {
+            symbols.GetLineByInlineContext(
+                addr,
+                inline_context,
+                Some(&mut line),
+                buffer,
+                size,
+                Some(&mut displacement),
+            )
+        })
+        .with_context(|| format!("GetLineByInlineContext failed for {addr:#x}"))?;
+
+        Ok(SourceLine {
+            file,
+            line,
+            displacement,
+        })
+    }
+
+    /// Get the symbol options of the engine.
+    pub fn symbol_options(&self) -> Result<SymbolOptions> {
+        let options =
+            unsafe { self.symbols.GetSymbolOptions() }.context("GetSymbolOptions failed")?;
+
+        Ok(SymbolOptions::from_bits_retain(options))
+    }
+
+    /// Replace the symbol options of the engine with `options`.
+    pub fn set_symbol_options(&self, options: SymbolOptions) -> Result<()> {
+        unsafe { self.symbols.SetSymbolOptions(options.bits()) }.context("SetSymbolOptions failed")
+    }
+
+    /// Turn on the symbol options `options`, leaving the others untouched.
+    pub fn add_symbol_options(&self, options: SymbolOptions) -> Result<()> {
+        unsafe { self.symbols.AddSymbolOptions(options.bits()) }.context("AddSymbolOptions failed")
+    }
+
+    /// Turn off the symbol options `options`, leaving the others untouched.
+    pub fn remove_symbol_options(&self, options: SymbolOptions) -> Result<()> {
+        unsafe { self.symbols.RemoveSymbolOptions(options.bits()) }
+            .context("RemoveSymbolOptions failed")
+    }
+
+    /// Add a synthetic symbol named `name` covering the `size` bytes at
+    /// `addr`; it shows up in stack traces and disassembly like any other
+    /// symbol. The address needs to be within a module, which can be a
+    /// synthetic one.
+    pub fn add_synthetic_symbol(
+        &self,
+        addr: u64,
+        size: u32,
+        name: &str,
+    ) -> Result<SyntheticSymbol> {
+        let name_cstr = CString::new(name).context("failed to convert name to CString")?;
+        let mut id = DEBUG_MODULE_AND_ID::default();
+        unsafe {
+            self.symbols.AddSyntheticSymbol(
+                addr,
+                size,
+                name_cstr.as_pcstr(),
+                DEBUG_ADDSYNTHSYM_DEFAULT,
+                Some(&mut id),
+            )
+        }
+        .with_context(|| format!("AddSyntheticSymbol failed for {name}"))?;
+
+        Ok(SyntheticSymbol(id))
+    }
+
+    /// Remove a symbol added with [`DebugClient::add_synthetic_symbol`].
+    pub fn remove_synthetic_symbol(&self, symbol: SyntheticSymbol) -> Result<()> {
+        unsafe { self.symbols.RemoveSyntheticSymbol(&symbol.0) }
+            .context("RemoveSyntheticSymbol failed")
+    }
+
+    /// Set the current scope to the one of the stack frame `frame`, which is
+    /// what [`DebugClient::scope_symbol_group`] enumerates the symbols of.
+    pub fn set_scope(&self, frame: &DEBUG_STACK_FRAME) -> Result<()> {
+        unsafe {
+            self.symbols
+                .SetScope(frame.InstructionOffset, Some(frame), None, 0)
+        }
+        .context("SetScope failed")
+    }
+
+    /// Set the current scope to the one of the stack frame at `index` in the
+    /// current call stack.
+    pub fn set_scope_frame_by_index(&self, index: u32) -> Result<()> {
+        unsafe { self.symbols.SetScopeFrameByIndex(index) }
+            .with_context(|| format!("SetScopeFrameByIndex failed for {index}"))
+    }
+
+    /// Get the arguments and / or the local variables of the function of the
+    /// current scope.
+    pub fn scope_symbol_group(&self, group: ScopeGroup) -> Result<SymbolGroup> {
+        let symbol_group = unsafe {
+            self.symbols
+                .GetScopeSymbolGroup2(group.as_flags(), None::<&IDebugSymbolGroup2>)
+        }
+        .context("GetScopeSymbolGroup2 failed")?;
+
+        Ok(SymbolGroup(symbol_group))
+    }
+
+    /// Get a [`TypedValue`] of type `type_name` (like `nt!_PEB`) living at
+    /// `addr`.
+    pub fn typed_value(&self, type_name: &str, addr: u64) -> Result<TypedValue> {
+        let type_name_cstr =
+            CString::new(type_name).context("failed to convert type name to CString")?;
+        let mut type_id = 0;
+        let mut module = 0;
+        unsafe {
+            self.symbols
+                .GetSymbolTypeId(type_name_cstr.as_pcstr(), &mut type_id, Some(&mut module))
+        }
+        .with_context(|| format!("GetSymbolTypeId failed for {type_name}"))?;
+
+        Ok(TypedValue::new(self.symbols.clone(), module, type_id, addr))
+    }
+
+    /// Get the base address of the module containing `addr`.
+    pub fn module_base_by_offset(&self, addr: u64) -> Result<u64> {
+        let mut base = 0;
+        unsafe {
+            self.symbols
+                .GetModuleByOffset(addr, 0, None, Some(&mut base))
+        }
+        .with_context(|| format!("GetModuleByOffset failed for {addr:#x}"))?;
+
+        Ok(base)
+    }
+
+    /// Get the function table entry of the function containing `addr`, which
+    /// gives its boundaries. Only Intel x64 targets are supported.
+    pub fn function_entry(&self, addr: u64) -> Result<RuntimeFunction> {
+        if self.processor_type()? != IMAGE_FILE_MACHINE_AMD64 {
+            bail!("function entries are only supported on Intel x64 targets");
+        }
+
+        let mut entry = [0u8; RuntimeFunction::SIZE];
+        unsafe {
+            self.symbols.GetFunctionEntryByOffset(
+                addr,
+                DEBUG_GETFNENT_RAW_ENTRY_ONLY,
+                Some(entry.as_mut_ptr().cast()),
+                entry.len() as u32,
+                None,
+            )
+        }
+        .with_context(|| format!("GetFunctionEntryByOffset failed for {addr:#x}"))?;
+
+        RuntimeFunction::parse(self.module_base_by_offset(addr)?, &entry)
+    }
+
+    /// Get the unwind info of `function`, which describes its prologue.
+    pub fn unwind_info(&self, function: &RuntimeFunction) -> Result<UnwindInfo> {
+        let mut header = [0u8; UnwindInfo::HEADER_SIZE];
+        self.read_virtual_exact(function.unwind_info, &mut header)?;
+
+        let mut bytes = vec![0u8; UnwindInfo::size_from_header(&header)];
+        self.read_virtual_exact(function.unwind_info, &mut bytes)?;
+
+        UnwindInfo::parse(self.module_base_by_offset(function.begin)?, &bytes)
+    }
+
+    /// Get information about every loaded module.
+    pub fn modules(&self) -> Result<Vec<ModuleInfo>> {
+        self.modules_where(|_| true)
+    }
+
+    /// Get information about the synthetic modules registered with
+    /// [`DebugClient::add_synthetic_module`].
+    pub fn synthetic_modules(&self) -> Result<Vec<ModuleInfo>> {
+        self.modules_where(|params| (params.Flags & DEBUG_MODULE_SYNTHETIC) != 0)
+    }
+
+    /// Get information about the loaded modules whose parameters satisfy
+    /// `predicate`.
+    fn modules_where(
+        &self,
+        predicate: impl Fn(&DEBUG_MODULE_PARAMETERS) -> bool,
+    ) -> Result<Vec<ModuleInfo>> {
+        let mut loaded = 0;
+        let mut unloaded = 0;
+        unsafe { self.symbols.GetNumberModules(&mut loaded, &mut unloaded) }
+            .context("GetNumberModules failed")?;
+
+        let mut params = vec![DEBUG_MODULE_PARAMETERS::default(); loaded as usize];
+        unsafe {
+            self.symbols
+                .GetModuleParameters(loaded, None, 0, params.as_mut_ptr())
+        }
+        .context("GetModuleParameters failed")?;
+
+        params
+            .iter()
+            .zip(0..loaded)
+            .filter(|(params, _)| predicate(params))
+            .map(|(params, index)| {
+                Ok(ModuleInfo {
+                    name: self.module_name_string(DEBUG_MODNAME_MODULE, index)?,
+                    image_path: self.module_name_string(DEBUG_MODNAME_IMAGE, index)?,
+                    base: params.Base,
+                    size: params.Size,
+                    timestamp: params.TimeDateStamp,
+                    checksum: params.Checksum,
+                })
+            })
+            .collect()
+    }
+
+    /// Register a synthetic module named `name` covering the `size` bytes at
+    /// `base`, so that the engine treats dumped or injected code as a module.
+    /// `image_path` is the path reported for its image.
+    pub fn add_synthetic_module(
+        &self,
+        base: u64,
+        size: u32,
+        image_path: &str,
+        name: &str,
+    ) -> Result<SymbolModule> {
+        let image_path_cstr =
+            CString::new(image_path).context("failed to convert image path to CString")?;
+        let name_cstr = CString::new(name).context("failed to convert name to CString")?;
+        unsafe {
+            self.symbols.AddSyntheticModule(
+                base,
+                size,
+                image_path_cstr.as_pcstr(),
+                name_cstr.as_pcstr(),
+                DEBUG_ADDSYNTHMOD_DEFAULT,
+            )
+        }
+        .with_context(|| format!("AddSyntheticModule failed for {name}"))?;
+
+        Ok(SymbolModule::new(self.symbols.clone(), base))
+    }
+
+    /// Remove a module registered with [`DebugClient::add_synthetic_module`].
+    pub fn remove_synthetic_module(&self, base: u64) -> Result<()> {
+        unsafe { self.symbols.RemoveSyntheticModule(base) }
+            .with_context(|| format!("RemoveSyntheticModule failed for {base:#x}"))
+    }
+
+    /// Get one of the names (`DEBUG_MODNAME_*`) of the module at `index`.
+    fn module_name_string(&self, which: u32, index: u32) -> Result<String> {
+        engine_string(|buffer, size| unsafe {
+            self.symbols
+                .GetModuleNameString(which, index, 0, buffer, size)
+        })
+        .with_context(|| format!("GetModuleNameString failed for {index}"))
+    }
+
+    /// Get the debuggee type.
+    pub fn debuggee_type(&self) -> Result<(u32, u32)> {
+        let mut class = 0;
+        let mut qualifier = 0;
+        unsafe { self.control.GetDebuggeeType(&mut class, &mut qualifier) }?;
+
+        Ok((class, qualifier))
+    }
+
+    /// Get the processor type of the target.
+    pub fn processor_type(&self) -> Result<IMAGE_FILE_MACHINE> {
+        let proc_type = unsafe { self.control.GetActualProcessorType() }
+            .context("GetActualProcessorType failed")?;
+
+        Ok(IMAGE_FILE_MACHINE(proc_type.try_into()?))
+    }
+
+    /// Is the target using 64-bit pointers?
+    pub fn is_pointer_64bit(&self) -> Result<bool> {
+        // N.B: `IsPointer64Bit` returns `S_FALSE` for 32-bit targets, which the
+        // generated wrapper maps to `Ok(())`, so we call through the vtable to
+        // see the actual `HRESULT`.
+        let hr = unsafe {
+            (Interface::vtable(&self.control).IsPointer64Bit)(Interface::as_raw(&self.control))
+        };
+
+        hr.ok().context("IsPointer64Bit failed")?;
+
+        Ok(hr == S_OK)
+    }
+
+    /// Get the number of processors in the target.
+    pub fn processor_number(&self) -> Result<u32> {
+        unsafe { self.control.GetNumberProcessors() }.context("GetNumberProcessors failed")
+    }
+
+    /// Get the event stored in the dump file being debugged, like the crash
+    /// that triggered its creation.
+    pub fn stored_event(&self) -> Result<StoredEvent> {
+        let mut kind = 0;
+        let mut process_id = 0;
+        let mut thread_id = 0;
+        let mut context = vec![0u8; X64_CONTEXT_SIZE];
+        let mut context_used = 0;
+        let mut exception = DEBUG_LAST_EVENT_INFO_EXCEPTION::default();
+        let mut exception_used = 0;
+        unsafe {
+            self.control.GetStoredEventInformation(
+                &mut kind,
+                &mut process_id,
+                &mut thread_id,
+                Some(context.as_mut_ptr().cast()),
+                context.len().try_into()?,
+                Some(&mut context_used),
+                Some(ptr::addr_of_mut!(exception).cast()),
+                mem::size_of_val(&exception).try_into()?,
+                Some(&mut exception_used),
+            )
+        }
+        .context("GetStoredEventInformation failed")?;
+
+        let context = if self.processor_type()? == IMAGE_FILE_MACHINE_AMD64
+            && context_used as usize >= X64_CONTEXT_SIZE
+        {
+            Some(X64Context::from_context_bytes(&context)?)
+        } else {
+            None
+        };
+
+        let exception = (kind == DEBUG_EVENT_EXCEPTION
+            && exception_used as usize >= mem::size_of_val(&exception))
+        .then(|| ExceptionInfo {
+            record: exception.ExceptionRecord.into(),
+            first_chance: exception.FirstChance,
+        });
+
+        Ok(StoredEvent {
+            kind: EventInterest::from_bits_retain(kind),
+            process_id,
+            thread_id,
+            context,
+            exception,
+        })
+    }
+
+    /// Read the stream `stream` (like `CommentStreamW`) of the user-mode
+    /// minidump being debugged.
+    pub fn read_dump_stream(&self, stream: MINIDUMP_STREAM_TYPE) -> Result<Vec<u8>> {
+        const CHUNK_SIZE: usize = 0x1_00_00;
+        let advanced = self.client.cast::<IDebugAdvanced2>()?;
+        let mut data = Vec::new();
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        loop {
+            // N.B: Reading at the very end of a stream fails, which can't be told
+            // apart from a real error; so every chunk after the first one starts
+            // at the last byte already read, and the end is always a short read.
+            let overlap = usize::from(!data.is_empty());
+            let mut request = DEBUG_READ_USER_MINIDUMP_STREAM {
+                StreamType: stream.0 as u32,
+                Offset: (data.len() - overlap) as u64,
+                Buffer: chunk.as_mut_ptr().cast(),
+                BufferSize: CHUNK_SIZE as u32,
+                ..Default::default()
+            };
+
+            // N.B: The engine writes `BufferUsed` back into the input buffer.
+            unsafe {
+                advanced.Request(
+                    DEBUG_REQUEST_READ_USER_MINIDUMP_STREAM,
+                    Some(ptr::addr_of_mut!(request).cast_const().cast()),
+                    mem::size_of_val(&request).try_into()?,
+                    None,
+                    0,
+                    None,
+                )
+            }
+            .with_context(|| format!("failed to read stream {}", stream.0))?;
+
+            let used = (request.BufferUsed as usize).min(CHUNK_SIZE);
+            data.extend_from_slice(&chunk[overlap.min(used)..used]);
+            if used < CHUNK_SIZE {
+                break;
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Get the comment stored in the user-mode minidump being debugged, if
+    /// there is one.
+    pub fn dump_comment(&self) -> Result<Option<String>> {
+        if let Ok(data) = self.read_dump_stream(CommentStreamW) {
+            let wide = data
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&c| c != 0)
+                .collect::<Vec<_>>();
+
+            return Ok(Some(String::from_utf16_lossy(&wide)));
+        }
+
+        match self.read_dump_stream(CommentStreamA) {
+            Ok(data) => {
+                let len = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+
+                Ok(Some(String::from_utf8_lossy(&data[..len]).into_owned()))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Get the version of the operating system of the target.
+    pub fn system_version(&self) -> Result<SystemVersion> {
+        let mut version = SystemVersion::default();
+        let mut kd_major = 0;
+        unsafe {
+            self.control.GetSystemVersionValues(
+                &mut version.platform_id,
+                &mut version.major,
+                &mut version.minor,
+                Some(&mut kd_major),
+                Some(&mut version.build),
+            )
+        }
+        .context("GetSystemVersionValues failed")?;
+
+        // N.B: The major KD version is 0xC for checked builds, and 0xF for free
+        // builds.
+        version.checked = kd_major == 0xC;
+        version.service_pack = self.system_version_string(DEBUG_SYSVERSTR_SERVICE_PACK)?;
+        version.build_string = self.system_version_string(DEBUG_SYSVERSTR_BUILD)?;
+
+        Ok(version)
+    }
+
+    fn system_version_string(&self, which: u32) -> Result<String> {
+        engine_string(|buffer, size| unsafe {
+            self.control.GetSystemVersionString(which, buffer, size)
+        })
+        .context("GetSystemVersionString failed")
+    }
+
+    /// Get an address for a named symbol.
+    pub fn get_address_by_name<Str>(&self, symbol: Str) -> Result<u64>
+    where
+        Str: Into<Vec<u8>>,
+    {
+        let symbol_cstr = CString::new(symbol.into())?;
+
+        unsafe { self.symbols.GetOffsetByName(symbol_cstr.as_pcstr()) }
+            .context("GetOffsetByName failed")
+    }
+
+    /// Read a NULL terminated string at `addr`, growing the amount of memory
+    /// read until the terminator is found (up to [`MAX_STRING_LEN`] bytes).
+    pub fn read_cstring_virtual(&self, addr: u64) -> Result<String> {
+        self.read_string_growing(addr, Self::read_cstring_virtual_with)
+    }
+
+    /// Read a NULL terminated string at `addr`; the terminator has to be within
+    /// the first `max_len` bytes.
+    pub fn read_cstring_virtual_with(&self, addr: u64, max_len: usize) -> Result<String> {
+        let mut buffer = vec![0; max_len];
+        let mut length = 0;
+        unsafe {
+            self.dataspaces.ReadMultiByteStringVirtual(
+                addr,
+                max_len.try_into()?,
+                Some(buffer.as_mut()),
+                Some(&mut length),
+            )
+        }
+        .context("ReadMultiByteStringVirtual failed")?;
+
+        if length == 0 {
+            bail!("length is zero")
+        }
+
+        let length = length as usize;
+        if length > buffer.len() {
+            bail!("the string at {addr:#x} is larger than {max_len:#x} bytes");
+        }
+
+        buffer.resize(length - 1, 0);
+
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
+    /// Read a NULL terminated wide string at `addr`, growing the amount of
+    /// memory read until the terminator is found (up to [`MAX_STRING_LEN`]
+    /// bytes).
+    pub fn read_wstring_virtual(&self, addr: u64) -> Result<String> {
+        self.read_string_growing(addr, Self::read_wstring_virtual_with)
+    }
+
+    /// Read a NULL terminated wide string at `addr`; the terminator has to be
+    /// within the first `max_len` bytes.
+    pub fn read_wstring_virtual_with(&self, addr: u64, max_len: usize) -> Result<String> {
+        // A UTF-16 code unit (2 bytes) is at most 3 bytes once converted to UTF-8.
+        let mut buffer = vec![0; max_len * 2];
+        let mut length = 0;
+        unsafe {
+            self.dataspaces.ReadUnicodeStringVirtual(
+                addr,
+                max_len.try_into()?,
+                65001, // CP_UTF8
+                Some(&mut buffer),
+                Some(&mut length),
+            )
+        }
+        .context("ReadUnicodeStringVirtual failed")?;
+
+        if length == 0 {
+            bail!("length is zero")
+        }
+
+        let length = length as usize;
+        if length > buffer.len() {
+            bail!("the string at {addr:#x} is larger than {max_len:#x} bytes");
+        }
+
+        buffer.resize(length - 1, 0);
+
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
+    /// Read a `_UNICODE_STRING` structure at `addr` and the UTF-16 string it
+    /// points to.
+    pub fn read_unicode_string(&self, addr: u64) -> Result<String> {
+        let (length, buffer) = self.read_counted_string(addr)?;
+        let mut bytes = vec![0; length.into()];
+        self.read_virtual_exact(buffer, &mut bytes)?;
+
+        let wide = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+
+        Ok(String::from_utf16_lossy(&wide))
+    }
+
+    /// Read a `_STRING` (`ANSI_STRING`) structure at `addr` and the string it
+    /// points to.
+    pub fn read_ansi_string(&self, addr: u64) -> Result<String> {
+        let (length, buffer) = self.read_counted_string(addr)?;
+        let mut bytes = vec![0; length.into()];
+        self.read_virtual_exact(buffer, &mut bytes)?;
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Read the `Length` and `Buffer` fields of a `_UNICODE_STRING` / `_STRING`
+    /// structure at `addr`, accounting for the pointer size of the target.
+    fn read_counted_string(&self, addr: u64) -> Result<(u16, u64)> {
+        // 64-bit layout:              32-bit layout:
+        //   +0x0 Length : u16           +0x0 Length : u16
+        //   +0x2 MaximumLength : u16    +0x2 MaximumLength : u16
+        //   +0x8 Buffer : u64           +0x4 Buffer : u32
+        let mut raw = [0; 16];
+        let is_64bit = self.is_pointer_64bit()?;
+        let size = if is_64bit { 16 } else { 8 };
+        self.read_virtual_exact(addr, &mut raw[..size])
+            .with_context(|| format!("failed to read the string structure at {addr:#x}"))?;
+
+        let length = u16::from_le_bytes(raw[0..2].try_into().unwrap());
+        let buffer = if is_64bit {
+            u64::from_le_bytes(raw[8..16].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(raw[4..8].try_into().unwrap()).into()
+        };
+
+        Ok((length, buffer))
+    }
+
+    /// Invoke `read` with a growing maximum length until it succeeds or
+    /// [`MAX_STRING_LEN`] is reached.
+    fn read_string_growing(
+        &self,
+        addr: u64,
+        read: impl Fn(&Self, u64, usize) -> Result<String>,
+    ) -> Result<String> {
+        let mut max_len = 0x1_00;
+        loop {
+            match read(self, addr, max_len) {
+                Err(e) if max_len >= MAX_STRING_LEN => return Err(e),
+                Err(_) => max_len = (max_len * 2).min(MAX_STRING_LEN),
+                s => return s,
+            }
+        }
+    }
+
+    pub fn get_current_process_id(&self) -> Result<u32> {
+        let process_id = unsafe {
+            self.system.GetCurrentProcessSystemId()
+        }
+        .context("GetCurrentProcessId failed")?;
+        Ok(process_id)
+    }
+
+    /// Get the process and the thread the last event happened in. While an
+    /// event is being delivered to the event callbacks, the engine is only
+    /// asked once.
+    pub fn event_context(&self) -> Result<EventContext> {
+        match self.event_context.get() {
+            Some(Some(context)) => Ok(context),
+            Some(None) => {
+                let context = self.query_event_context()?;
+                self.event_context.set(Some(Some(context)));
+
+                Ok(context)
+            }
+            None => self.query_event_context(),
+        }
+    }
+
+    /// Ask the engine for the process and the thread the last event happened
+    /// in.
+    pub(crate) fn query_event_context(&self) -> Result<EventContext> {
+        let engine_process_id =
+            unsafe { self.system.GetEventProcess() }.context("GetEventProcess failed")?;
+        let engine_thread_id =
+            unsafe { self.system.GetEventThread() }.context("GetEventThread failed")?;
+
+        // N.B: The engine makes the thread of the event the current one when the
+        // event happens.
+        let process_id = unsafe { self.system.GetCurrentProcessSystemId() }
+            .context("GetCurrentProcessSystemId failed")?;
+        let thread_id = unsafe { self.system.GetCurrentThreadSystemId() }
+            .context("GetCurrentThreadSystemId failed")?;
+
+        Ok(EventContext {
+            process_id,
+            thread_id,
+            engine_process_id,
+            engine_thread_id,
+        })
+    }
+
+    /// Replace the state of the event being delivered to the event callbacks
+    /// (see the `event_context` field) and return the previous one.
+    pub(crate) fn replace_event_context(
+        &self,
+        context: Option<Option<EventContext>>,
+    ) -> Option<Option<EventContext>> {
+        self.event_context.replace(context)
     }
 
     pub fn get_current_thread_id(&self) -> Result<u32> {
@@ -580,4 +2931,379 @@ impl DebugClient {
         .context("GetCurrentThreadId failed")?;
         Ok(thread_id)
     }
+
+    /// Get the system IDs of the processes running on the machine of the
+    /// engine.
+    pub fn running_process_ids(&self) -> Result<Vec<u32>> {
+        let mut count = 0;
+        unsafe {
+            self.client
+                .GetRunningProcessSystemIds(0, None, Some(&mut count))
+        }
+        .context("GetRunningProcessSystemIds failed")?;
+
+        // N.B: Processes can start between the two calls, in which case the
+        // newest ones are left out.
+        let mut ids = vec![0; count as usize];
+        unsafe {
+            self.client
+                .GetRunningProcessSystemIds(0, Some(&mut ids), Some(&mut count))
+        }
+        .context("GetRunningProcessSystemIds failed")?;
+
+        ids.truncate(count as usize);
+
+        Ok(ids)
+    }
+
+    /// Describe the running process whose system ID is `system_id`.
+    pub fn running_process(&self, system_id: u32) -> Result<RunningProcess> {
+        let describe = |executable: Option<&mut [u8]>,
+                        executable_size: Option<*mut u32>,
+                        description: Option<&mut [u8]>,
+                        description_size: Option<*mut u32>| {
+            unsafe {
+                self.client.GetRunningProcessDescription(
+                    0,
+                    system_id,
+                    DEBUG_PROC_DESC_DEFAULT,
+                    executable,
+                    executable_size,
+                    description,
+                    description_size,
+                )
+            }
+            .with_context(|| format!("GetRunningProcessDescription failed for {system_id}"))
+        };
+
+        let mut executable_size = 0;
+        let mut description_size = 0;
+        describe(
+            None,
+            Some(&mut executable_size),
+            None,
+            Some(&mut description_size),
+        )?;
+
+        let mut executable = vec![0; executable_size as usize];
+        let mut description = vec![0; description_size as usize];
+        describe(Some(&mut executable), None, Some(&mut description), None)?;
+
+        let to_string = |buffer: &[u8]| -> Result<String> {
+            Ok(CStr::from_bytes_until_nul(buffer)
+                .context("the engine returned an invalid string")?
+                .to_string_lossy()
+                .into_owned())
+        };
+
+        Ok(RunningProcess {
+            system_id,
+            executable: to_string(&executable)?,
+            description: to_string(&description)?,
+        })
+    }
+
+    /// Describe the processes running on the machine of the engine; the ones
+    /// that can't be described (like the ones that exited in the meantime)
+    /// are skipped.
+    pub fn running_processes(&self) -> Result<Vec<RunningProcess>> {
+        Ok(self
+            .running_process_ids()?
+            .into_iter()
+            .filter_map(|system_id| self.running_process(system_id).ok())
+            .collect())
+    }
+
+    /// Create the process `command_line` (like `notepad.exe foo.txt`) and
+    /// attach to it. The process is created once the engine waits for events;
+    /// see [`DebugClient::wait_for_event`].
+    pub fn create_process(&self, command_line: &str, options: &CreateProcessOptions) -> Result<()> {
+        let command_line_cstr =
+            CString::new(command_line).context("failed to convert command line to CString")?;
+        let initial_directory = options
+            .initial_directory
+            .as_deref()
+            .map(CString::new)
+            .transpose()
+            .context("failed to convert initial directory to CString")?;
+        let environment = options
+            .environment
+            .as_deref()
+            .map(process::environment_block);
+        let engine_options = DEBUG_CREATE_PROCESS_OPTIONS {
+            CreateFlags: options.create_flags.bits(),
+            EngCreateFlags: options.engine_flags.bits(),
+            ..Default::default()
+        };
+
+        unsafe {
+            self.client.CreateProcessAndAttach2(
+                0,
+                command_line_cstr.as_pcstr(),
+                ptr::addr_of!(engine_options).cast(),
+                mem::size_of_val(&engine_options).try_into()?,
+                initial_directory
+                    .as_ref()
+                    .map_or(PCSTR::null(), |dir| dir.as_pcstr()),
+                environment
+                    .as_ref()
+                    .map_or(PCSTR::null(), |env| PCSTR::from_raw(env.as_ptr())),
+                0,
+                DEBUG_ATTACH_DEFAULT,
+            )
+        }
+        .with_context(|| format!("CreateProcessAndAttach2({command_line:?}) failed"))
+    }
+
+    /// Attach to the process whose system ID is `system_id`. The attach
+    /// completes once the engine waits for events; see
+    /// [`DebugClient::wait_for_event`].
+    pub fn attach_process(&self, system_id: u32, flags: AttachFlags) -> Result<()> {
+        unsafe { self.client.AttachProcess(0, system_id, flags.bits()) }
+            .with_context(|| format!("AttachProcess({system_id}, {flags:?}) failed"))
+    }
+
+    /// Open the crash dump file `path` and wait for the engine to load it, so
+    /// that the target can be inspected once this returns.
+    pub fn open_dump(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        unsafe { self.client.OpenDumpFileWide(&HSTRING::from(path), 0) }
+            .with_context(|| format!("OpenDumpFileWide({}) failed", path.display()))?;
+
+        // N.B: The engine only finishes loading the dump once it waits for
+        // events.
+        self.wait_for_event(None)
+            .with_context(|| format!("failed to load the dump {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Write a dump of the target to `path`.
+    pub fn write_dump(
+        &self,
+        path: impl AsRef<Path>,
+        kind: DumpKind,
+        format: DumpFormat,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        unsafe {
+            self.client.WriteDumpFileWide(
+                &HSTRING::from(path),
+                0,
+                kind.as_qualifier(),
+                format.bits(),
+                PCWSTR::null(),
+            )
+        }
+        .with_context(|| format!("WriteDumpFileWide({}, {kind:?}) failed", path.display()))
+    }
+
+    /// Connect to a kernel target. The connection completes once the engine
+    /// waits for events; see [`DebugClient::wait_for_event`].
+    pub fn attach_kernel(&self, connection: &KernelConnection) -> Result<()> {
+        let options = connection
+            .options()
+            .map(CString::new)
+            .transpose()
+            .context("failed to convert connection options to CString")?;
+
+        unsafe {
+            self.client.AttachKernel(
+                connection.as_flags(),
+                options.as_ref().map_or(PCSTR::null(), |o| o.as_pcstr()),
+            )
+        }
+        .with_context(|| format!("AttachKernel({connection:?}) failed"))
+    }
+
+    /// Connect to a process server (like one started with `dbgsrv -t
+    /// tcp:port=5005`), described by `options` like
+    /// `tcp:server=10.0.0.2,port=5005`; returns the handle of the server.
+    pub fn connect_process_server(&self, options: &str) -> Result<u64> {
+        let cstr = CString::new(options).context("failed to convert options to CString")?;
+        unsafe { self.client.ConnectProcessServer(cstr.as_pcstr()) }
+            .with_context(|| format!("ConnectProcessServer({options:?}) failed"))
+    }
+
+    /// Disconnect from the process server identified by `server`.
+    pub fn disconnect_process_server(&self, server: u64) -> Result<()> {
+        unsafe { self.client.DisconnectProcessServer(server) }
+            .with_context(|| format!("DisconnectProcessServer({server:#x}) failed"))
+    }
+
+    /// Start a process server, like `dbgsrv`, that remote debuggers can
+    /// connect to with the transport described by `options`, like
+    /// `tcp:port=5005`.
+    pub fn start_process_server(&self, options: &str) -> Result<()> {
+        let cstr = CString::new(options).context("failed to convert options to CString")?;
+        unsafe {
+            self.client
+                .StartProcessServer(DEBUG_CLASS_USER_WINDOWS, cstr.as_pcstr(), None)
+        }
+        .with_context(|| format!("StartProcessServer({options:?}) failed"))
+    }
+
+    /// Start a debugging server, like `.server`, that remote debuggers can
+    /// connect to with the transport described by `options`, like
+    /// `tcp:port=5005`.
+    pub fn start_server(&self, options: &str) -> Result<()> {
+        let cstr = CString::new(options).context("failed to convert options to CString")?;
+        unsafe { self.client.StartServer(cstr.as_pcstr()) }
+            .with_context(|| format!("StartServer({options:?}) failed"))
+    }
+
+    /// End the debugging session, releasing the targets as specified by
+    /// `mode`.
+    pub fn end_session(&self, mode: EndSession) -> Result<()> {
+        unsafe { self.client.EndSession(mode.as_flags()) }
+            .with_context(|| format!("EndSession({mode:?}) failed"))
+    }
+
+    /// Detach from the current process, letting it run.
+    pub fn detach_current_process(&self) -> Result<()> {
+        unsafe { self.client.DetachCurrentProcess() }.context("DetachCurrentProcess failed")
+    }
+
+    /// Detach from every process being debugged, letting them run.
+    pub fn detach_processes(&self) -> Result<()> {
+        unsafe { self.client.DetachProcesses() }.context("DetachProcesses failed")
+    }
+
+    /// Terminate the current process.
+    pub fn terminate_current_process(&self) -> Result<()> {
+        unsafe { self.client.TerminateCurrentProcess() }.context("TerminateCurrentProcess failed")
+    }
+
+    /// Terminate every process being debugged.
+    pub fn terminate_processes(&self) -> Result<()> {
+        unsafe { self.client.TerminateProcesses() }.context("TerminateProcesses failed")
+    }
+
+    /// Stop debugging the current process without detaching from it; it can
+    /// later be reattached with [`AttachFlags::EXISTING`].
+    pub fn abandon_current_process(&self) -> Result<()> {
+        unsafe { self.client.AbandonCurrentProcess() }.context("AbandonCurrentProcess failed")
+    }
+
+    /// Get the engine IDs of the threads of the current process.
+    pub fn thread_ids(&self) -> Result<Vec<u32>> {
+        let number =
+            unsafe { self.system.GetNumberThreads() }.context("GetNumberThreads failed")?;
+        let mut ids = vec![0; number as usize];
+        unsafe {
+            self.system
+                .GetThreadIdsByIndex(0, number, Some(ids.as_mut_ptr()), None)
+        }
+        .context("GetThreadIdsByIndex failed")?;
+
+        Ok(ids)
+    }
+
+    /// Temporarily switch the current thread to the thread identified by the
+    /// engine ID `thread_id`, invoke `f` and switch back to the previous
+    /// thread.
+    pub fn with_thread<T>(&self, thread_id: u32, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        let previous = self.current_thread()?;
+        self.set_current_thread(thread_id)?;
+
+        let res = f(self);
+
+        self.set_current_thread(previous)
+            .with_context(|| format!("failed to restore thread {previous}"))?;
+
+        res
+    }
+
+    /// Get the engine ID of the current thread.
+    pub fn current_thread(&self) -> Result<u32> {
+        unsafe { self.system.GetCurrentThreadId() }.context("GetCurrentThreadId failed")
+    }
+
+    /// Switch the current thread to the thread identified by the engine ID
+    /// `thread_id`; the registers are read from this thread.
+    pub fn set_current_thread(&self, thread_id: u32) -> Result<()> {
+        unsafe { self.system.SetCurrentThreadId(thread_id) }
+            .with_context(|| format!("SetCurrentThreadId failed for {thread_id}"))
+    }
+
+    /// Get the engine ID of the current process.
+    pub fn current_process(&self) -> Result<u32> {
+        unsafe { self.system.GetCurrentProcessId() }.context("GetCurrentProcessId failed")
+    }
+
+    /// Switch the current process to the process identified by the engine ID
+    /// `process_id`; the memory is read from this process.
+    pub fn set_current_process(&self, process_id: u32) -> Result<()> {
+        unsafe { self.system.SetCurrentProcessId(process_id) }
+            .with_context(|| format!("SetCurrentProcessId failed for {process_id}"))
+    }
+
+    /// Get the index of the current processor; this is only meaningful for
+    /// kernel targets, where every processor is represented by a thread.
+    pub fn current_processor(&self) -> Result<u32> {
+        let thread_id = self.current_thread()?;
+        for processor in 0..self.processor_number()? {
+            if self.processor_thread(processor)? == thread_id {
+                return Ok(processor);
+            }
+        }
+
+        bail!("the current thread {thread_id} isn't a processor")
+    }
+
+    /// Switch the current thread to the one representing the processor
+    /// `processor`, like `~0s` in a kernel target.
+    pub fn set_current_processor(&self, processor: u32) -> Result<()> {
+        self.set_current_thread(self.processor_thread(processor)?)
+    }
+
+    /// Get the engine ID of the thread representing the processor
+    /// `processor` in a kernel target.
+    fn processor_thread(&self, processor: u32) -> Result<u32> {
+        unsafe { self.system.GetThreadIdByProcessor(processor) }
+            .with_context(|| format!("GetThreadIdByProcessor failed for {processor}"))
+    }
+
+    /// Get the address of the TEB of the current thread (`@$teb`).
+    pub fn teb(&self) -> Result<u64> {
+        unsafe { self.system.GetCurrentThreadTeb() }.context("GetCurrentThreadTeb failed")
+    }
+
+    /// Get the address of the PEB of the current process (`@$peb`).
+    pub fn peb(&self) -> Result<u64> {
+        unsafe { self.system.GetCurrentProcessPeb() }.context("GetCurrentProcessPeb failed")
+    }
+
+    /// Get the path of the executable of the current process.
+    pub fn executable_path(&self) -> Result<PathBuf> {
+        engine_string(|buffer, size| unsafe {
+            self.system.GetCurrentProcessExecutableName(buffer, size)
+        })
+        .map(PathBuf::from)
+        .context("GetCurrentProcessExecutableName failed")
+    }
+
+    /// Switch the current thread to the thread identified by the engine ID
+    /// `thread_id` until the returned guard is dropped.
+    pub fn switch_thread(&self, thread_id: u32) -> Result<ContextGuard> {
+        let guard = ContextGuard::new(self)?;
+        self.set_current_thread(thread_id)?;
+
+        Ok(guard)
+    }
+
+    /// Switch the current process to the process identified by the engine ID
+    /// `process_id` until the returned guard is dropped.
+    pub fn switch_process(&self, process_id: u32) -> Result<ContextGuard> {
+        let guard = ContextGuard::new(self)?;
+        self.set_current_process(process_id)?;
+
+        Ok(guard)
+    }
+
+    /// Get the values of a set of registers of the thread identified by the
+    /// engine ID `thread_id`, without changing the current thread.
+    pub fn thread_regs64(&self, thread_id: u32, names: &[&str]) -> Result<Vec<u64>> {
+        self.with_thread(thread_id, |dbg| dbg.regs64(names))
+    }
 }
@@ -0,0 +1,282 @@
+//! This contains a code coverage collector: a [`Coverage`] sets one-shot
+//! breakpoints on basic blocks (or function entries) and records the ones
+//! that are executed. The result can be exported in the DRCOV format read by
+//! Lighthouse and Cutter.
+//!
+//! ```ignore
+//! let coverage = Rc::new(Coverage::new(client.clone()));
+//! coverage.add_functions("app")?;
+//! client.set_event_callbacks(Rc::clone(&coverage))?;
+//! // ...
+//! coverage.write_drcov(File::create("app.drcov")?)?;
+//! ```
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use windows::core::GUID;
+use windows::Win32::System::Diagnostics::Debug::IMAGE_SCN_MEM_EXECUTE;
+
+use crate::breakpoint::{remove_all, BreakpointFlags, BreakpointType, DebugBreakpoint};
+use crate::client::DebugClient;
+use crate::events::{DebugInstruction, EventCallbacks, EventInterest};
+use crate::exception::ExceptionInfo;
+use crate::pe::PeImage;
+use crate::symbol::ModuleInfo;
+
+/// The size of the blocks written by [`write_drcov`]; as only their first
+/// instruction is known to have executed, a single byte is reported.
+const DRCOV_BLOCK_SIZE: u16 = 1;
+
+/// Collects the basic blocks executed by the target, with a one-shot
+/// breakpoint on each block; a breakpoint is removed by the engine once it
+/// triggers, so the overhead decreases as the coverage grows. Like a
+/// [`BreakpointManager`], it can be shared with a [`std::rc::Rc`]; the
+/// breakpoints of the blocks not hit yet are removed when it is dropped.
+///
+/// [`BreakpointManager`]: crate::breakpoint::BreakpointManager
+pub struct Coverage {
+    client: DebugClient,
+    /// The address of the blocks not hit yet, by breakpoint.
+    pending: RefCell<HashMap<GUID, (DebugBreakpoint, u64)>>,
+    /// The address of the blocks hit.
+    hits: RefCell<BTreeSet<u64>>,
+    /// The address of every block added.
+    blocks: RefCell<HashSet<u64>>,
+}
+
+impl Coverage {
+    pub fn new(client: DebugClient) -> Self {
+        Self {
+            client,
+            pending: RefCell::new(HashMap::new()),
+            hits: RefCell::new(BTreeSet::new()),
+            blocks: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Set a one-shot breakpoint on each of the basic blocks starting at
+    /// `addrs`; the blocks already added are skipped. The number of blocks
+    /// added is returned.
+    pub fn add_blocks(&self, addrs: impl IntoIterator<Item = u64>) -> Result<usize> {
+        let mut added = 0;
+        for addr in addrs {
+            if self.blocks.borrow().contains(&addr) {
+                continue;
+            }
+
+            let bp = self.client.add_breakpoint(BreakpointType::Code, None)?;
+            let res = bp
+                .set_offset(addr)
+                .and_then(|_| bp.set_flags(BreakpointFlags::ENABLED | BreakpointFlags::ONE_SHOT))
+                .and_then(|_| bp.guid());
+
+            let guid = match res {
+                Ok(guid) => guid,
+                Err(e) => {
+                    let _ = self.client.remove_breakpoint(bp);
+                    return Err(e)
+                        .with_context(|| format!("failed to set a breakpoint at {addr:#x}"));
+                }
+            };
+
+            // N.B: The block is only recorded once its breakpoint is set, so that
+            // a failed block can be added again.
+            self.blocks.borrow_mut().insert(addr);
+            self.pending.borrow_mut().insert(guid, (bp, addr));
+            added += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Set a one-shot breakpoint on the entry of every function of the module
+    /// `module` (like `ntdll`), which are the symbols of the module in an
+    /// executable section; this gives function-level coverage. The number of
+    /// functions added is returned.
+    pub fn add_functions(&self, module: &str) -> Result<usize> {
+        let sym_module = self.client.get_sym_module(module)?;
+        let pe = PeImage::parse(&self.client, sym_module.base())?;
+        let is_code = |addr: u64| {
+            pe.va_to_rva(addr)
+                .and_then(|rva| pe.section_by_rva(rva))
+                .is_some_and(|section| section.Characteristics.contains(IMAGE_SCN_MEM_EXECUTE))
+        };
+
+        let mut functions = sym_module
+            .symbols("*")?
+            .into_iter()
+            .map(|symbol| symbol.addr)
+            .filter(|&addr| is_code(addr))
+            .collect::<Vec<_>>();
+
+        functions.sort_unstable();
+        functions.dedup();
+
+        self.add_blocks(functions)
+    }
+
+    /// Get the address of the blocks hit, in increasing order.
+    pub fn hits(&self) -> Vec<u64> {
+        self.hits.borrow().iter().copied().collect()
+    }
+
+    /// Has the block at `addr` been hit?
+    pub fn is_hit(&self, addr: u64) -> bool {
+        self.hits.borrow().contains(&addr)
+    }
+
+    /// Get the number of blocks not hit yet.
+    pub fn pending_len(&self) -> usize {
+        self.pending.borrow().len()
+    }
+
+    /// Get the number of blocks added.
+    pub fn len(&self) -> usize {
+        self.blocks.borrow().len()
+    }
+
+    /// Has no block been added?
+    pub fn is_empty(&self) -> bool {
+        self.blocks.borrow().is_empty()
+    }
+
+    /// Remove the breakpoints of the blocks not hit yet, and forget every
+    /// block.
+    pub fn clear(&self) -> Result<()> {
+        self.hits.borrow_mut().clear();
+        self.blocks.borrow_mut().clear();
+
+        let bps = self.pending.take().into_values().map(|(bp, _)| bp);
+
+        remove_all(&self.client, bps)
+    }
+
+    /// Write the blocks hit in the DRCOV format, against the modules loaded
+    /// now; see [`write_drcov`].
+    pub fn write_drcov<W: Write>(&self, w: W) -> Result<()> {
+        write_drcov(w, &self.client.modules()?, &self.hits())
+    }
+}
+
+/// Write the blocks starting at `hits` in the DRCOV (version 2) format, with
+/// `modules` as the module table; the blocks outside of every module are
+/// skipped.
+pub fn write_drcov<W: Write>(mut w: W, modules: &[ModuleInfo], hits: &[u64]) -> Result<()> {
+    let blocks = hits
+        .iter()
+        .filter_map(|&addr| {
+            modules.iter().enumerate().find_map(|(id, module)| {
+                let offset = addr
+                    .checked_sub(module.base)
+                    .filter(|&offset| offset < u64::from(module.size))?;
+
+                Some((u32::try_from(offset).ok()?, u16::try_from(id).ok()?))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    writeln!(w, "DRCOV VERSION: 2")?;
+    writeln!(w, "DRCOV FLAVOR: drcov")?;
+    writeln!(w, "Module Table: version 2, count {}", modules.len())?;
+    writeln!(
+        w,
+        "Columns: id, base, end, entry, checksum, timestamp, path"
+    )?;
+    for (id, module) in modules.iter().enumerate() {
+        writeln!(
+            w,
+            "{id}, {:#018x}, {:#018x}, {:#018x}, {:#010x}, {:#010x}, {}",
+            module.base,
+            module.base + u64::from(module.size),
+            0,
+            module.checksum,
+            module.timestamp,
+            module.image_path
+        )?;
+    }
+
+    writeln!(w, "BB Table: {} bbs", blocks.len())?;
+    for (offset, id) in blocks {
+        // N.B: Each entry is a `bb_entry_t`: the offset of the block from the
+        // base of its module, its size and the ID of its module.
+        w.write_all(&offset.to_le_bytes())?;
+        w.write_all(&DRCOV_BLOCK_SIZE.to_le_bytes())?;
+        w.write_all(&id.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+impl EventCallbacks for Coverage {
+    fn breakpoint(&self, _client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        let Ok(guid) = bp.guid() else {
+            return DebugInstruction::NoChange;
+        };
+
+        // N.B: The engine removes one-shot breakpoints by itself.
+        let Some((_, addr)) = self.pending.borrow_mut().remove(&guid) else {
+            return DebugInstruction::NoChange;
+        };
+
+        self.hits.borrow_mut().insert(addr);
+
+        DebugInstruction::Go
+    }
+
+    fn exception(&self, _client: &DebugClient, _ei: &ExceptionInfo) -> DebugInstruction {
+        DebugInstruction::NoChange
+    }
+
+    fn change_engine_state(&self, _client: &DebugClient, _flags: u32, _argument: u64) {}
+
+    fn interest_mask(&self) -> EventInterest {
+        EventInterest::BREAKPOINT
+    }
+}
+
+impl Drop for Coverage {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drcov() {
+        let modules = vec![ModuleInfo {
+            name: "app".into(),
+            image_path: "C:\\app.exe".into(),
+            base: 0x1_4000_0000,
+            size: 0x2000,
+            timestamp: 0x1337,
+            checksum: 0,
+        }];
+
+        let mut drcov = Vec::new();
+        write_drcov(&mut drcov, &modules, &[
+            0x1_4000_1010,
+            0x1000,
+            0x1_4000_0000,
+        ])
+        .unwrap();
+
+        let header = concat!(
+            "DRCOV VERSION: 2\n",
+            "DRCOV FLAVOR: drcov\n",
+            "Module Table: version 2, count 1\n",
+            "Columns: id, base, end, entry, checksum, timestamp, path\n",
+            "0, 0x0000000140000000, 0x0000000140002000, 0x0000000000000000, 0x00000000, ",
+            "0x00001337, C:\\app.exe\n",
+            "BB Table: 2 bbs\n",
+        );
+        assert_eq!(&drcov[..header.len()], header.as_bytes());
+        assert_eq!(&drcov[header.len()..], [
+            0x10, 0x10, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0
+        ]);
+    }
+}
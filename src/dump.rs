@@ -0,0 +1,95 @@
+//! This contains types describing what is stored in a dump file, like the
+//! event that triggered its creation (see [`DebugClient::stored_event`]), and
+//! the options used to write one (see [`DebugClient::write_dump`]).
+//!
+//! [`DebugClient::stored_event`]: crate::client::DebugClient::stored_event
+//! [`DebugClient::write_dump`]: crate::client::DebugClient::write_dump
+use bitflags::bitflags;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    DEBUG_DUMP_FULL, DEBUG_DUMP_SMALL, DEBUG_FORMAT_NO_OVERWRITE,
+    DEBUG_FORMAT_USER_SMALL_CODE_SEGMENTS, DEBUG_FORMAT_USER_SMALL_DATA_SEGMENTS,
+    DEBUG_FORMAT_USER_SMALL_FULL_MEMORY, DEBUG_FORMAT_USER_SMALL_FULL_MEMORY_INFO,
+    DEBUG_FORMAT_USER_SMALL_HANDLE_DATA, DEBUG_FORMAT_USER_SMALL_IGNORE_INACCESSIBLE_MEM,
+    DEBUG_FORMAT_USER_SMALL_INDIRECT_MEMORY, DEBUG_FORMAT_USER_SMALL_PRIVATE_READ_WRITE_MEMORY,
+    DEBUG_FORMAT_USER_SMALL_PROCESS_THREAD_DATA, DEBUG_FORMAT_USER_SMALL_THREAD_INFO,
+    DEBUG_FORMAT_USER_SMALL_UNLOADED_MODULES, DEBUG_FORMAT_WRITE_CAB,
+};
+
+use crate::events::EventInterest;
+use crate::exception::ExceptionInfo;
+use crate::registers::X64Context;
+
+/// The event stored in a dump file, like the crash that triggered its
+/// creation.
+pub struct StoredEvent {
+    /// The kind of event, like [`EventInterest::EXCEPTION`].
+    pub kind: EventInterest,
+    /// The engine ID of the process where the event happened.
+    pub process_id: u32,
+    /// The engine ID of the thread where the event happened.
+    pub thread_id: u32,
+    /// The registers of the thread when the event happened; only available
+    /// for Intel x64 targets.
+    pub context: Option<X64Context>,
+    /// The exception, if the event is one.
+    pub exception: Option<ExceptionInfo>,
+}
+
+/// The kind of dump written by [`DebugClient::write_dump`].
+///
+/// [`DebugClient::write_dump`]: crate::client::DebugClient::write_dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DumpKind {
+    /// A minidump (`.dump /m`); what it contains depends on the
+    /// [`DumpFormat`] flags.
+    Mini,
+    /// A full dump (`.dump /f`), with all the memory of the target.
+    Full,
+}
+
+impl DumpKind {
+    pub(crate) fn as_qualifier(&self) -> u32 {
+        match self {
+            Self::Mini => DEBUG_DUMP_SMALL,
+            Self::Full => DEBUG_DUMP_FULL,
+        }
+    }
+}
+
+bitflags! {
+    /// What is written in a dump; most flags only apply to user-mode
+    /// minidumps.
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct DumpFormat: u32 {
+        /// Include all the accessible memory of the process (`/ma`).
+        const FULL_MEMORY = DEBUG_FORMAT_USER_SMALL_FULL_MEMORY;
+        /// Include the handle table (`/mh`).
+        const HANDLE_DATA = DEBUG_FORMAT_USER_SMALL_HANDLE_DATA;
+        /// Include the list of unloaded modules (`/mu`).
+        const UNLOADED_MODULES = DEBUG_FORMAT_USER_SMALL_UNLOADED_MODULES;
+        /// Include the memory referenced by the stacks and the registers
+        /// (`/mi`).
+        const INDIRECT_MEMORY = DEBUG_FORMAT_USER_SMALL_INDIRECT_MEMORY;
+        /// Include the data sections of the modules (`/md`).
+        const DATA_SEGMENTS = DEBUG_FORMAT_USER_SMALL_DATA_SEGMENTS;
+        /// Include the private read-write memory.
+        const PRIVATE_READ_WRITE_MEMORY = DEBUG_FORMAT_USER_SMALL_PRIVATE_READ_WRITE_MEMORY;
+        /// Include the PEB and the TEBs (`/mp`).
+        const PROCESS_THREAD_DATA = DEBUG_FORMAT_USER_SMALL_PROCESS_THREAD_DATA;
+        /// Include the layout of the virtual memory (`/mF`).
+        const FULL_MEMORY_INFO = DEBUG_FORMAT_USER_SMALL_FULL_MEMORY_INFO;
+        /// Include the times of the threads (`/mt`).
+        const THREAD_INFO = DEBUG_FORMAT_USER_SMALL_THREAD_INFO;
+        /// Include the code sections of the modules (`/mc`).
+        const CODE_SEGMENTS = DEBUG_FORMAT_USER_SMALL_CODE_SEGMENTS;
+        /// Skip the memory that can't be read instead of failing (`/mA`).
+        const IGNORE_INACCESSIBLE_MEMORY = DEBUG_FORMAT_USER_SMALL_IGNORE_INACCESSIBLE_MEM;
+        /// Package the dump in a CAB file (`/b`).
+        const WRITE_CAB = DEBUG_FORMAT_WRITE_CAB;
+        /// Fail instead of overwriting an existing file.
+        const NO_OVERWRITE = DEBUG_FORMAT_NO_OVERWRITE;
+    }
+}
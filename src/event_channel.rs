@@ -0,0 +1,195 @@
+//! This contains an adapter that turns the debugger events into messages sent
+//! on a channel, so that they can be consumed in a loop instead of in
+//! [`EventCallbacks`] implementations.
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use windows::core::GUID;
+
+use crate::breakpoint::DebugBreakpoint;
+use crate::client::DebugClient;
+use crate::events::{DebugInstruction, EventCallbacks, EventInterest};
+use crate::exception::ExceptionInfo;
+use crate::symbol::ModuleInfo;
+
+/// A debugger event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugEvent {
+    /// A breakpoint triggered.
+    Breakpoint {
+        /// The engine ID of the breakpoint.
+        id: u32,
+        /// The globally unique ID of the breakpoint.
+        guid: GUID,
+    },
+    /// An exception was raised.
+    Exception {
+        /// The exception code.
+        code: i32,
+        /// The address where the exception happened.
+        address: u64,
+        /// The parameters of the exception.
+        parameters: Vec<u64>,
+        /// Is it a first-chance exception?
+        first_chance: bool,
+    },
+    /// The state of the engine changed.
+    ChangeEngineState {
+        /// What changed (`DEBUG_CES_*`).
+        flags: u32,
+        /// The new value, whose meaning depends on `flags`.
+        argument: u64,
+    },
+    /// A module was loaded.
+    LoadModule(ModuleInfo),
+}
+
+impl DebugEvent {
+    /// Get the kind of the event.
+    fn interest(&self) -> EventInterest {
+        match self {
+            Self::Breakpoint { .. } => EventInterest::BREAKPOINT,
+            Self::Exception { .. } => EventInterest::EXCEPTION,
+            Self::ChangeEngineState { .. } => EventInterest::CHANGE_ENGINE_STATE,
+            Self::LoadModule(_) => EventInterest::LOAD_MODULE,
+        }
+    }
+}
+
+/// The events that have a [`DebugEvent`] variant.
+const SUPPORTED_INTEREST: EventInterest = EventInterest::BREAKPOINT
+    .union(EventInterest::EXCEPTION)
+    .union(EventInterest::CHANGE_ENGINE_STATE)
+    .union(EventInterest::LOAD_MODULE);
+
+/// [`EventCallbacks`] that send the events on a channel; build them with
+/// [`event_channel`].
+///
+/// ```ignore
+/// let (callbacks, events) = event_channel();
+/// client.set_event_callbacks(callbacks)?;
+/// for event in events.try_iter() {
+///     // ...
+/// }
+/// ```
+pub struct ChannelEventCallbacks {
+    sender: Sender<DebugEvent>,
+    instruction: DebugInstruction,
+    interest: EventInterest,
+}
+
+impl ChannelEventCallbacks {
+    /// Set the instruction returned to the engine for breakpoints and
+    /// exceptions; it is [`DebugInstruction::NoChange`] by default.
+    pub fn with_instruction(mut self, instruction: DebugInstruction) -> Self {
+        self.instruction = instruction;
+
+        self
+    }
+
+    /// Set the events to send on the channel; the events without a
+    /// [`DebugEvent`] variant are ignored.
+    pub fn with_interest(mut self, interest: EventInterest) -> Self {
+        self.interest = interest & SUPPORTED_INTEREST;
+
+        self
+    }
+
+    /// Send `event` on the channel if it is one of the events of interest;
+    /// an [`EventDispatcher`] delivers every event to every callbacks.
+    ///
+    /// [`EventDispatcher`]: crate::events::EventDispatcher
+    fn send(&self, event: DebugEvent) {
+        if self.interest.contains(event.interest()) {
+            // N.B: Sending fails if the receiver is gone, in which case there is
+            // nobody left to care about the event.
+            let _ = self.sender.send(event);
+        }
+    }
+}
+
+/// Create [`EventCallbacks`] that send the events on a channel, and the
+/// receiving end of the channel.
+pub fn event_channel() -> (ChannelEventCallbacks, Receiver<DebugEvent>) {
+    let (sender, receiver) = mpsc::channel();
+    let callbacks = ChannelEventCallbacks {
+        sender,
+        instruction: DebugInstruction::NoChange,
+        interest: SUPPORTED_INTEREST,
+    };
+
+    (callbacks, receiver)
+}
+
+impl EventCallbacks for ChannelEventCallbacks {
+    fn breakpoint(&self, _client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        if let (Ok(id), Ok(guid)) = (bp.id(), bp.guid()) {
+            self.send(DebugEvent::Breakpoint { id, guid });
+        }
+
+        self.instruction
+    }
+
+    fn exception(&self, _client: &DebugClient, ei: &ExceptionInfo) -> DebugInstruction {
+        let count =
+            (ei.record.number_parameters as usize).min(ei.record.exception_information.len());
+        self.send(DebugEvent::Exception {
+            code: ei.record.exception_code.0,
+            address: ei.record.exception_address,
+            parameters: ei.record.exception_information[..count].to_vec(),
+            first_chance: ei.first_chance != 0,
+        });
+
+        self.instruction
+    }
+
+    fn change_engine_state(&self, _client: &DebugClient, flags: u32, argument: u64) {
+        self.send(DebugEvent::ChangeEngineState { flags, argument });
+    }
+
+    fn load_module(&self, _client: &DebugClient, module: &ModuleInfo) {
+        self.send(DebugEvent::LoadModule(module.clone()));
+    }
+
+    fn interest_mask(&self) -> EventInterest {
+        self.interest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interest() {
+        let (callbacks, events) = event_channel();
+        assert_eq!(callbacks.interest_mask(), SUPPORTED_INTEREST);
+
+        let callbacks = callbacks.with_interest(EventInterest::all());
+        assert_eq!(callbacks.interest_mask(), SUPPORTED_INTEREST);
+
+        let callbacks = callbacks
+            .with_interest(EventInterest::CHANGE_ENGINE_STATE | EventInterest::CREATE_THREAD);
+        assert_eq!(
+            callbacks.interest_mask(),
+            EventInterest::CHANGE_ENGINE_STATE
+        );
+
+        callbacks.send(DebugEvent::LoadModule(ModuleInfo::default()));
+        callbacks.send(DebugEvent::ChangeEngineState {
+            flags: 1,
+            argument: 2,
+        });
+        assert_eq!(events.try_iter().collect::<Vec<_>>(), vec![
+            DebugEvent::ChangeEngineState {
+                flags: 1,
+                argument: 2
+            }
+        ]);
+
+        drop(events);
+        callbacks.send(DebugEvent::ChangeEngineState {
+            flags: 1,
+            argument: 2,
+        });
+    }
+}
@@ -1,19 +1,37 @@
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+use std::fmt;
 use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+use std::sync::Once;
 
-use windows::core::{implement, HRESULT};
+use bitflags::bitflags;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::core::{implement, Interface, HRESULT, PCSTR};
 use windows::Win32::System::Diagnostics::Debug::Extensions::{
     IDebugBreakpoint, IDebugEventCallbacks, IDebugEventCallbacks_Impl, 
-    DEBUG_EVENT_BREAKPOINT, DEBUG_EVENT_CHANGE_ENGINE_STATE, DEBUG_EVENT_EXCEPTION,
-    DEBUG_STATUS_BREAK, DEBUG_STATUS_GO, DEBUG_STATUS_GO_HANDLED, DEBUG_STATUS_GO_NOT_HANDLED, 
-    DEBUG_STATUS_IGNORE_EVENT, DEBUG_STATUS_NO_CHANGE, DEBUG_STATUS_RESTART_REQUESTED, 
-    DEBUG_STATUS_STEP_BRANCH, DEBUG_STATUS_STEP_INTO, DEBUG_STATUS_STEP_OVER
+    DEBUG_EVENT_BREAKPOINT, DEBUG_EVENT_CHANGE_DEBUGGEE_STATE, DEBUG_EVENT_CHANGE_ENGINE_STATE,
+    DEBUG_EVENT_CHANGE_SYMBOL_STATE, DEBUG_EVENT_CREATE_PROCESS, DEBUG_EVENT_CREATE_THREAD,
+    DEBUG_EVENT_EXCEPTION, DEBUG_EVENT_EXIT_PROCESS, DEBUG_EVENT_EXIT_THREAD,
+    DEBUG_EVENT_LOAD_MODULE, DEBUG_EVENT_SESSION_STATUS, DEBUG_EVENT_SYSTEM_ERROR,
+    DEBUG_EVENT_UNLOAD_MODULE,
+    DEBUG_CES_EXECUTION_STATUS, DEBUG_STATUS_BREAK, DEBUG_STATUS_GO, DEBUG_STATUS_GO_HANDLED,
+    DEBUG_STATUS_GO_NOT_HANDLED, DEBUG_STATUS_IGNORE_EVENT, DEBUG_STATUS_MASK,
+    DEBUG_STATUS_NO_CHANGE, DEBUG_STATUS_NO_DEBUGGEE, DEBUG_STATUS_OUT_OF_SYNC,
+    DEBUG_STATUS_RESTART_REQUESTED, DEBUG_STATUS_REVERSE_GO, DEBUG_STATUS_REVERSE_STEP_BRANCH,
+    DEBUG_STATUS_REVERSE_STEP_INTO, DEBUG_STATUS_REVERSE_STEP_OVER, DEBUG_STATUS_STEP_BRANCH,
+    DEBUG_STATUS_STEP_INTO, DEBUG_STATUS_STEP_OVER, DEBUG_STATUS_TIMEOUT, DEBUG_STATUS_WAIT_INPUT
 };
 use windows::Win32::System::Diagnostics::Debug::EXCEPTION_RECORD64;
 
+use crate::as_pcstr::AsPCSTR;
 use crate::breakpoint::DebugBreakpoint;
 use crate::exception::ExceptionInfo;
 use crate::client::DebugClient;
 use crate::dlogln;
+use crate::symbol::ModuleInfo;
 
 /// An instruction for the debugger to follow.
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
@@ -59,33 +77,774 @@ impl DebugInstruction {
             DebugInstruction::NoChange => DEBUG_STATUS_NO_CHANGE,
         }
     }
+
+    fn from_status(status: u32) -> Self {
+        match status {
+            DEBUG_STATUS_BREAK => DebugInstruction::Break,
+            DEBUG_STATUS_STEP_INTO => DebugInstruction::StepInto,
+            DEBUG_STATUS_STEP_BRANCH => DebugInstruction::StepBranch,
+            DEBUG_STATUS_STEP_OVER => DebugInstruction::StepOver,
+            DEBUG_STATUS_GO_NOT_HANDLED => DebugInstruction::GoNotHandled,
+            DEBUG_STATUS_GO_HANDLED => DebugInstruction::GoHandled,
+            DEBUG_STATUS_GO => DebugInstruction::Go,
+            DEBUG_STATUS_IGNORE_EVENT => DebugInstruction::IgnoreEvent,
+            DEBUG_STATUS_RESTART_REQUESTED => DebugInstruction::Restart,
+            _ => DebugInstruction::NoChange,
+        }
+    }
+
+    /// The precedence of the instruction; the highest wins when several
+    /// handlers answer the same event.
+    fn precedence(&self) -> u8 {
+        match self {
+            DebugInstruction::NoChange => 0,
+            DebugInstruction::IgnoreEvent => 1,
+            DebugInstruction::Go => 2,
+            DebugInstruction::GoHandled => 3,
+            DebugInstruction::GoNotHandled => 4,
+            DebugInstruction::StepOver => 5,
+            DebugInstruction::StepBranch => 6,
+            DebugInstruction::StepInto => 7,
+            DebugInstruction::Break => 8,
+            DebugInstruction::Restart => 9,
+        }
+    }
+
+    /// Merge the instructions of two handlers of the same event, the same way
+    /// the engine does: the one with the highest precedence wins. From the
+    /// highest to the lowest: `Restart`, `Break`, `StepInto`, `StepBranch`,
+    /// `StepOver`, `GoNotHandled`, `GoHandled`, `Go`, `IgnoreEvent` and
+    /// `NoChange`.
+    pub fn merge(self, other: Self) -> Self {
+        if other.precedence() > self.precedence() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// The execution status of the engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExecutionStatus {
+    /// The target is running.
+    Go,
+    /// The target is running, and the last event is flagged as handled.
+    GoHandled,
+    /// The target is running, and the last event is flagged as unhandled.
+    GoNotHandled,
+    /// The target executes a single instruction, stepping over calls.
+    StepOver,
+    /// The target executes a single instruction.
+    StepInto,
+    /// The target runs until the next branch instruction.
+    StepBranch,
+    /// The target is suspended.
+    Break,
+    /// There is no target.
+    NoDebuggee,
+    /// The last event is ignored.
+    IgnoreEvent,
+    /// A restart of the target was requested.
+    RestartRequested,
+    /// The target runs backwards.
+    ReverseGo,
+    /// The target runs backwards until the previous branch instruction.
+    ReverseStepBranch,
+    /// The target executes a single instruction backwards, stepping over
+    /// calls.
+    ReverseStepOver,
+    /// The target executes a single instruction backwards.
+    ReverseStepInto,
+    /// The engine is out of sync with the target.
+    OutOfSync,
+    /// The engine waits for input.
+    WaitInput,
+    /// The engine timed out.
+    Timeout,
+}
+
+impl ExecutionStatus {
+    pub(crate) fn as_status(&self) -> u32 {
+        match self {
+            Self::Go => DEBUG_STATUS_GO,
+            Self::GoHandled => DEBUG_STATUS_GO_HANDLED,
+            Self::GoNotHandled => DEBUG_STATUS_GO_NOT_HANDLED,
+            Self::StepOver => DEBUG_STATUS_STEP_OVER,
+            Self::StepInto => DEBUG_STATUS_STEP_INTO,
+            Self::StepBranch => DEBUG_STATUS_STEP_BRANCH,
+            Self::Break => DEBUG_STATUS_BREAK,
+            Self::NoDebuggee => DEBUG_STATUS_NO_DEBUGGEE,
+            Self::IgnoreEvent => DEBUG_STATUS_IGNORE_EVENT,
+            Self::RestartRequested => DEBUG_STATUS_RESTART_REQUESTED,
+            Self::ReverseGo => DEBUG_STATUS_REVERSE_GO,
+            Self::ReverseStepBranch => DEBUG_STATUS_REVERSE_STEP_BRANCH,
+            Self::ReverseStepOver => DEBUG_STATUS_REVERSE_STEP_OVER,
+            Self::ReverseStepInto => DEBUG_STATUS_REVERSE_STEP_INTO,
+            Self::OutOfSync => DEBUG_STATUS_OUT_OF_SYNC,
+            Self::WaitInput => DEBUG_STATUS_WAIT_INPUT,
+            Self::Timeout => DEBUG_STATUS_TIMEOUT,
+        }
+    }
+
+    /// Convert a `DEBUG_STATUS_*` value; the flags outside of
+    /// `DEBUG_STATUS_MASK` (like `DEBUG_STATUS_INSIDE_WAIT`) are ignored.
+    pub fn from_status(status: u64) -> Option<Self> {
+        Some(match status as u32 & DEBUG_STATUS_MASK {
+            DEBUG_STATUS_GO => Self::Go,
+            DEBUG_STATUS_GO_HANDLED => Self::GoHandled,
+            DEBUG_STATUS_GO_NOT_HANDLED => Self::GoNotHandled,
+            DEBUG_STATUS_STEP_OVER => Self::StepOver,
+            DEBUG_STATUS_STEP_INTO => Self::StepInto,
+            DEBUG_STATUS_STEP_BRANCH => Self::StepBranch,
+            DEBUG_STATUS_BREAK => Self::Break,
+            DEBUG_STATUS_NO_DEBUGGEE => Self::NoDebuggee,
+            DEBUG_STATUS_IGNORE_EVENT => Self::IgnoreEvent,
+            DEBUG_STATUS_RESTART_REQUESTED => Self::RestartRequested,
+            DEBUG_STATUS_REVERSE_GO => Self::ReverseGo,
+            DEBUG_STATUS_REVERSE_STEP_BRANCH => Self::ReverseStepBranch,
+            DEBUG_STATUS_REVERSE_STEP_OVER => Self::ReverseStepOver,
+            DEBUG_STATUS_REVERSE_STEP_INTO => Self::ReverseStepInto,
+            DEBUG_STATUS_OUT_OF_SYNC => Self::OutOfSync,
+            DEBUG_STATUS_WAIT_INPUT => Self::WaitInput,
+            DEBUG_STATUS_TIMEOUT => Self::Timeout,
+            _ => return None,
+        })
+    }
+
+    /// Get the new execution status from the arguments of
+    /// [`EventCallbacks::change_engine_state`], if that's what changed.
+    pub fn from_engine_state(flags: u32, argument: u64) -> Option<Self> {
+        if flags & DEBUG_CES_EXECUTION_STATUS == 0 {
+            return None;
+        }
+
+        Self::from_status(argument)
+    }
+}
+
+bitflags! {
+    /// The events an [`EventCallbacks`] implementation wants to be notified of.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EventInterest: u32 {
+        /// A breakpoint triggered.
+        const BREAKPOINT = DEBUG_EVENT_BREAKPOINT;
+        /// An exception was raised.
+        const EXCEPTION = DEBUG_EVENT_EXCEPTION;
+        /// A thread was created.
+        const CREATE_THREAD = DEBUG_EVENT_CREATE_THREAD;
+        /// A thread exited.
+        const EXIT_THREAD = DEBUG_EVENT_EXIT_THREAD;
+        /// A process was created.
+        const CREATE_PROCESS = DEBUG_EVENT_CREATE_PROCESS;
+        /// A process exited.
+        const EXIT_PROCESS = DEBUG_EVENT_EXIT_PROCESS;
+        /// A module was loaded.
+        const LOAD_MODULE = DEBUG_EVENT_LOAD_MODULE;
+        /// A module was unloaded.
+        const UNLOAD_MODULE = DEBUG_EVENT_UNLOAD_MODULE;
+        /// A system error occurred.
+        const SYSTEM_ERROR = DEBUG_EVENT_SYSTEM_ERROR;
+        /// The status of the debugging session changed.
+        const SESSION_STATUS = DEBUG_EVENT_SESSION_STATUS;
+        /// The state of the target changed (memory or registers).
+        const CHANGE_DEBUGGEE_STATE = DEBUG_EVENT_CHANGE_DEBUGGEE_STATE;
+        /// The state of the engine changed.
+        const CHANGE_ENGINE_STATE = DEBUG_EVENT_CHANGE_ENGINE_STATE;
+        /// The state of the symbols changed.
+        const CHANGE_SYMBOL_STATE = DEBUG_EVENT_CHANGE_SYMBOL_STATE;
+    }
 }
 
 pub trait EventCallbacks {
     fn breakpoint(&self, _client: &DebugClient, _bp: &DebugBreakpoint) -> DebugInstruction;
     fn exception(&self, _client: &DebugClient, _ei: &ExceptionInfo) -> DebugInstruction;
     fn change_engine_state(&self, _client: &DebugClient, _flags: u32, _argument: u64);
+    /// A thread has been created by the target; `start_offset` is its entry
+    /// point.
+    fn create_thread(
+        &self,
+        _client: &DebugClient,
+        _handle: u64,
+        _data_offset: u64,
+        _start_offset: u64,
+    ) {
+    }
+    /// A thread of the target has exited.
+    fn exit_thread(&self, _client: &DebugClient, _exit_code: u32) {}
+    /// A process has been created by the target; `module` is its image.
+    fn create_process(&self, _client: &DebugClient, _module: &ModuleInfo) {}
+    /// A process of the target has exited.
+    fn exit_process(&self, _client: &DebugClient, _exit_code: u32) {}
+    /// A module has been loaded by the target.
+    fn load_module(&self, _client: &DebugClient, _module: &ModuleInfo) {}
+    /// The module `image_base_name` mapped at `base` has been unloaded by the
+    /// target.
+    fn unload_module(&self, _client: &DebugClient, _image_base_name: &str, _base: u64) {}
+    /// A system error has occurred in the target.
+    fn system_error(&self, _client: &DebugClient, _error: u32, _level: u32) {}
+    /// The status of the session has changed (`DEBUG_SESSION_*`).
+    fn session_status(&self, _client: &DebugClient, _status: u32) {}
+    /// The memory or the registers of the target have changed
+    /// (`DEBUG_CDS_*`).
+    fn change_debuggee_state(&self, _client: &DebugClient, _flags: u32, _argument: u64) {}
+    /// The symbols have changed (`DEBUG_CSS_*`).
+    fn change_symbol_state(&self, _client: &DebugClient, _flags: u32, _argument: u64) {}
+
+    /// The events to be notified of; override it to receive the events of the
+    /// callbacks that aren't part of the default.
+    fn interest_mask(&self) -> EventInterest {
+        EventInterest::BREAKPOINT | EventInterest::EXCEPTION | EventInterest::CHANGE_ENGINE_STATE
+    }
+}
+
+impl<T: EventCallbacks + ?Sized> EventCallbacks for Rc<T> {
+    fn breakpoint(&self, client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        (**self).breakpoint(client, bp)
+    }
+
+    fn exception(&self, client: &DebugClient, ei: &ExceptionInfo) -> DebugInstruction {
+        (**self).exception(client, ei)
+    }
+
+    fn change_engine_state(&self, client: &DebugClient, flags: u32, argument: u64) {
+        (**self).change_engine_state(client, flags, argument)
+    }
+
+    fn create_thread(
+        &self,
+        client: &DebugClient,
+        handle: u64,
+        data_offset: u64,
+        start_offset: u64,
+    ) {
+        (**self).create_thread(client, handle, data_offset, start_offset)
+    }
+
+    fn exit_thread(&self, client: &DebugClient, exit_code: u32) {
+        (**self).exit_thread(client, exit_code)
+    }
+
+    fn create_process(&self, client: &DebugClient, module: &ModuleInfo) {
+        (**self).create_process(client, module)
+    }
+
+    fn exit_process(&self, client: &DebugClient, exit_code: u32) {
+        (**self).exit_process(client, exit_code)
+    }
+
+    fn load_module(&self, client: &DebugClient, module: &ModuleInfo) {
+        (**self).load_module(client, module)
+    }
+
+    fn unload_module(&self, client: &DebugClient, image_base_name: &str, base: u64) {
+        (**self).unload_module(client, image_base_name, base)
+    }
+
+    fn system_error(&self, client: &DebugClient, error: u32, level: u32) {
+        (**self).system_error(client, error, level)
+    }
+
+    fn session_status(&self, client: &DebugClient, status: u32) {
+        (**self).session_status(client, status)
+    }
+
+    fn change_debuggee_state(&self, client: &DebugClient, flags: u32, argument: u64) {
+        (**self).change_debuggee_state(client, flags, argument)
+    }
+
+    fn change_symbol_state(&self, client: &DebugClient, flags: u32, argument: u64) {
+        (**self).change_symbol_state(client, flags, argument)
+    }
+
+    fn interest_mask(&self) -> EventInterest {
+        (**self).interest_mask()
+    }
+}
+
+/// Fans the events out to several [`EventCallbacks`], which allows independent
+/// subsystems to share the single event callbacks slot of a client; the
+/// instructions they return are combined with [`DebugInstruction::merge`].
+///
+/// The engine reads the interest mask when the dispatcher is installed, so
+/// the callbacks should be registered before that.
+#[derive(Default)]
+pub struct EventDispatcher {
+    callbacks: RefCell<Vec<(usize, Rc<dyn EventCallbacks>)>>,
+    next_id: Cell<usize>,
+}
+
+impl EventDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `callbacks`; the returned ID can be used to unregister them.
+    pub fn register<E: EventCallbacks + 'static>(&self, callbacks: E) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.callbacks.borrow_mut().push((id, Rc::new(callbacks)));
+
+        id
+    }
+
+    /// Unregister the callbacks identified by `id`; `false` is returned if
+    /// they aren't registered.
+    pub fn unregister(&self, id: usize) -> bool {
+        let mut callbacks = self.callbacks.borrow_mut();
+        let len = callbacks.len();
+        callbacks.retain(|(cid, _)| *cid != id);
+
+        callbacks.len() != len
+    }
+
+    /// Get the number of registered callbacks.
+    pub fn len(&self) -> usize {
+        self.callbacks.borrow().len()
+    }
+
+    /// Is the dispatcher empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the registered callbacks.
+    // N.B: This is a snapshot so that the callbacks can register or unregister
+    // callbacks while being invoked.
+    fn snapshot(&self) -> Vec<Rc<dyn EventCallbacks>> {
+        self.callbacks
+            .borrow()
+            .iter()
+            .map(|(_, callbacks)| Rc::clone(callbacks))
+            .collect()
+    }
+}
+
+impl EventCallbacks for EventDispatcher {
+    fn breakpoint(&self, client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        self.snapshot()
+            .iter()
+            .fold(DebugInstruction::NoChange, |instruction, callbacks| {
+                instruction.merge(callbacks.breakpoint(client, bp))
+            })
+    }
+
+    fn exception(&self, client: &DebugClient, ei: &ExceptionInfo) -> DebugInstruction {
+        self.snapshot()
+            .iter()
+            .fold(DebugInstruction::NoChange, |instruction, callbacks| {
+                instruction.merge(callbacks.exception(client, ei))
+            })
+    }
+
+    fn change_engine_state(&self, client: &DebugClient, flags: u32, argument: u64) {
+        for callbacks in self.snapshot() {
+            callbacks.change_engine_state(client, flags, argument);
+        }
+    }
+
+    fn create_thread(
+        &self,
+        client: &DebugClient,
+        handle: u64,
+        data_offset: u64,
+        start_offset: u64,
+    ) {
+        for callbacks in self.snapshot() {
+            callbacks.create_thread(client, handle, data_offset, start_offset);
+        }
+    }
+
+    fn exit_thread(&self, client: &DebugClient, exit_code: u32) {
+        for callbacks in self.snapshot() {
+            callbacks.exit_thread(client, exit_code);
+        }
+    }
+
+    fn create_process(&self, client: &DebugClient, module: &ModuleInfo) {
+        for callbacks in self.snapshot() {
+            callbacks.create_process(client, module);
+        }
+    }
+
+    fn exit_process(&self, client: &DebugClient, exit_code: u32) {
+        for callbacks in self.snapshot() {
+            callbacks.exit_process(client, exit_code);
+        }
+    }
+
+    fn load_module(&self, client: &DebugClient, module: &ModuleInfo) {
+        for callbacks in self.snapshot() {
+            callbacks.load_module(client, module);
+        }
+    }
+
+    fn unload_module(&self, client: &DebugClient, image_base_name: &str, base: u64) {
+        for callbacks in self.snapshot() {
+            callbacks.unload_module(client, image_base_name, base);
+        }
+    }
+
+    fn system_error(&self, client: &DebugClient, error: u32, level: u32) {
+        for callbacks in self.snapshot() {
+            callbacks.system_error(client, error, level);
+        }
+    }
+
+    fn session_status(&self, client: &DebugClient, status: u32) {
+        for callbacks in self.snapshot() {
+            callbacks.session_status(client, status);
+        }
+    }
+
+    fn change_debuggee_state(&self, client: &DebugClient, flags: u32, argument: u64) {
+        for callbacks in self.snapshot() {
+            callbacks.change_debuggee_state(client, flags, argument);
+        }
+    }
+
+    fn change_symbol_state(&self, client: &DebugClient, flags: u32, argument: u64) {
+        for callbacks in self.snapshot() {
+            callbacks.change_symbol_state(client, flags, argument);
+        }
+    }
+
+    fn interest_mask(&self) -> EventInterest {
+        self.snapshot()
+            .iter()
+            .fold(EventInterest::empty(), |mask, callbacks| {
+                mask | callbacks.interest_mask()
+            })
+    }
+}
+
+/// The process and the thread an event happened in; see
+/// [`DebugClient::event_context`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EventContext {
+    /// The system ID of the process.
+    pub process_id: u32,
+    /// The system ID of the thread.
+    pub thread_id: u32,
+    /// The engine ID of the process.
+    pub engine_process_id: u32,
+    /// The engine ID of the thread.
+    pub engine_thread_id: u32,
+}
+
+/// A panic that happened in an event callback.
+#[derive(Debug)]
+pub struct CallbackPanic {
+    /// The name of the callback, like `breakpoint`.
+    pub callback: &'static str,
+    /// The message of the panic.
+    pub message: String,
+    /// Where the panic happened.
+    pub backtrace: Option<Backtrace>,
+}
+
+impl fmt::Display for CallbackPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panic in {} callback: {}", self.callback, self.message)
+    }
+}
+
+/// The type of the handlers of [`PanicPolicy::Custom`].
+pub type PanicHandler = dyn Fn(&DebugClient, &CallbackPanic) -> DebugInstruction;
+
+/// What to do when an event callback panics; the panic is logged in the
+/// debugger window first, except for [`PanicPolicy::Custom`].
+#[derive(Default)]
+pub enum PanicPolicy {
+    /// Suspend the target.
+    Break,
+    /// Continue execution.
+    Go,
+    /// Continue execution and set the event as unhandled.
+    #[default]
+    GoNotHandled,
+    /// Let a handler decide, for example to report the backtrace; if the
+    /// handler panics too, execution continues with the event unhandled.
+    Custom(Box<PanicHandler>),
+    /// Abort the debugger process.
+    Abort,
+}
+
+impl PanicPolicy {
+    fn handle(&self, client: &DebugClient, panic: &CallbackPanic) -> DebugInstruction {
+        if let Self::Custom(handler) = self {
+            // N.B: A panic can't unwind through the COM callback, as that
+            // aborts the debugger.
+            return std::panic::catch_unwind(AssertUnwindSafe(|| handler(client, panic)))
+                .unwrap_or(DebugInstruction::GoNotHandled);
+        }
+
+        let _ = dlogln!(client, "{panic}");
+        match self {
+            Self::Break => DebugInstruction::Break,
+            Self::Go => DebugInstruction::Go,
+            Self::GoNotHandled => DebugInstruction::GoNotHandled,
+            Self::Abort => std::process::abort(),
+            Self::Custom(_) => unreachable!(),
+        }
+    }
+}
+
+thread_local! {
+    /// Is an event callback running?
+    static CAPTURE_BACKTRACE: Cell<bool> = const { Cell::new(false) };
+    /// The backtrace of the last panic in an event callback.
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// Install a panic hook that records the backtrace of the panics happening
+/// in event callbacks, as it isn't available anymore once the panic is
+/// caught. The previous hook still runs.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if CAPTURE_BACKTRACE.with(Cell::get) {
+                BACKTRACE.with(|backtrace| {
+                    *backtrace.borrow_mut() = Some(Backtrace::force_capture());
+                });
+            }
+
+            previous(info);
+        }));
+    });
+}
+
+/// Forwards the events to an event callbacks object that wasn't installed by
+/// this crate, like the one of another extension returned by
+/// [`DebugClient::event_callbacks`]. Registering it in an [`EventDispatcher`]
+/// chains to it.
+pub struct ForeignEventCallbacks(pub IDebugEventCallbacks);
+
+impl ForeignEventCallbacks {
+    fn instruction(hr: HRESULT) -> DebugInstruction {
+        // N.B: The status is smuggled in the HRESULT, see
+        // `DbgEventCallbacks::Breakpoint`; failures map to `NoChange`.
+        DebugInstruction::from_status(hr.0 as u32)
+    }
+
+    /// Convert the name and the image path of `module`; `None` is returned if
+    /// they contain a NUL byte.
+    fn module_names(module: &ModuleInfo) -> Option<(CString, CString)> {
+        Some((
+            CString::new(module.name.as_str()).ok()?,
+            CString::new(module.image_path.as_str()).ok()?,
+        ))
+    }
+}
+
+impl EventCallbacks for ForeignEventCallbacks {
+    fn breakpoint(&self, _client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        let Ok(bp) = bp.0.cast::<IDebugBreakpoint>() else {
+            return DebugInstruction::NoChange;
+        };
+
+        // N.B: The generated wrapper maps every non-negative HRESULT to `Ok(())`,
+        // which loses the `DEBUG_STATUS_*` returned by the callback, so we call
+        // through the vtable.
+        let hr = unsafe {
+            (Interface::vtable(&self.0).Breakpoint)(
+                Interface::as_raw(&self.0),
+                Interface::as_raw(&bp),
+            )
+        };
+
+        Self::instruction(hr)
+    }
+
+    fn exception(&self, _client: &DebugClient, ei: &ExceptionInfo) -> DebugInstruction {
+        let record = EXCEPTION_RECORD64 {
+            ExceptionCode: ei.record.exception_code,
+            ExceptionFlags: ei.record.exception_flag,
+            ExceptionRecord: ei.record.exception_record,
+            ExceptionAddress: ei.record.exception_address,
+            NumberParameters: ei.record.number_parameters,
+            ExceptionInformation: ei.record.exception_information,
+            ..Default::default()
+        };
+
+        // N.B: Call through the vtable to see the status, like in `breakpoint`.
+        let hr = unsafe {
+            (Interface::vtable(&self.0).Exception)(
+                Interface::as_raw(&self.0),
+                &record,
+                ei.first_chance,
+            )
+        };
+
+        Self::instruction(hr)
+    }
+
+    fn change_engine_state(&self, _client: &DebugClient, flags: u32, argument: u64) {
+        let _ = unsafe { self.0.ChangeEngineState(flags, argument) };
+    }
+
+    fn create_thread(
+        &self,
+        _client: &DebugClient,
+        handle: u64,
+        data_offset: u64,
+        start_offset: u64,
+    ) {
+        let _ = unsafe { self.0.CreateThread(handle, data_offset, start_offset) };
+    }
+
+    fn exit_thread(&self, _client: &DebugClient, exit_code: u32) {
+        let _ = unsafe { self.0.ExitThread(exit_code) };
+    }
+
+    fn create_process(&self, _client: &DebugClient, module: &ModuleInfo) {
+        let Some((name, image_path)) = Self::module_names(module) else {
+            return;
+        };
+
+        let _ = unsafe {
+            self.0.CreateProcessA(
+                0,
+                0,
+                module.base,
+                module.size,
+                name.as_pcstr(),
+                image_path.as_pcstr(),
+                module.checksum,
+                module.timestamp,
+                0,
+                0,
+                0,
+            )
+        };
+    }
+
+    fn exit_process(&self, _client: &DebugClient, exit_code: u32) {
+        let _ = unsafe { self.0.ExitProcess(exit_code) };
+    }
+
+    fn load_module(&self, _client: &DebugClient, module: &ModuleInfo) {
+        let Some((name, image_path)) = Self::module_names(module) else {
+            return;
+        };
+
+        let _ = unsafe {
+            self.0.LoadModule(
+                0,
+                module.base,
+                module.size,
+                name.as_pcstr(),
+                image_path.as_pcstr(),
+                module.checksum,
+                module.timestamp,
+            )
+        };
+    }
+
+    fn unload_module(&self, _client: &DebugClient, image_base_name: &str, base: u64) {
+        let Ok(image_base_name) = CString::new(image_base_name) else {
+            return;
+        };
+
+        let _ = unsafe { self.0.UnloadModule(image_base_name.as_pcstr(), base) };
+    }
+
+    fn system_error(&self, _client: &DebugClient, error: u32, level: u32) {
+        let _ = unsafe { self.0.SystemError(error, level) };
+    }
+
+    fn session_status(&self, _client: &DebugClient, status: u32) {
+        let _ = unsafe { self.0.SessionStatus(status) };
+    }
+
+    fn change_debuggee_state(&self, _client: &DebugClient, flags: u32, argument: u64) {
+        let _ = unsafe { self.0.ChangeDebuggeeState(flags, argument) };
+    }
+
+    fn change_symbol_state(&self, _client: &DebugClient, flags: u32, argument: u64) {
+        let _ = unsafe { self.0.ChangeSymbolState(flags, argument) };
+    }
+
+    fn interest_mask(&self) -> EventInterest {
+        let mask = unsafe { self.0.GetInterestMask() }.unwrap_or_default();
+
+        EventInterest::from_bits_truncate(mask)
+    }
+}
+
+/// Convert a string passed to an event callback; NULL maps to an empty string.
+fn pcstr_to_string(s: &PCSTR) -> String {
+    if s.is_null() {
+        String::new()
+    } else {
+        String::from_utf8_lossy(unsafe { s.as_bytes() }).into_owned()
+    }
 }
 
 #[implement(IDebugEventCallbacks)]
 pub(crate) struct DbgEventCallbacks {
     client: DebugClient,
     callbacks: Box<dyn EventCallbacks>,
+    panic_policy: PanicPolicy,
 }
 
 impl DbgEventCallbacks {
-    pub(crate) fn new(client: DebugClient, callbacks: Box<dyn EventCallbacks + 'static>) -> Self {
-        Self { client, callbacks }
+    pub(crate) fn new(
+        client: DebugClient,
+        callbacks: Box<dyn EventCallbacks + 'static>,
+        panic_policy: PanicPolicy,
+    ) -> Self {
+        install_panic_hook();
+
+        Self {
+            client,
+            callbacks,
+            panic_policy,
+        }
+    }
+
+    /// Invoke the `name` callback via `f`, and apply the panic policy if it
+    /// panics.
+    fn guard(&self, name: &'static str, f: impl FnOnce() -> DebugInstruction) -> DebugInstruction {
+        // N.B: The context of the event is only queried if a callback asks for
+        // it, and the one of an outer callback is restored when a nested one
+        // returns.
+        let previous = self.client.replace_event_context(Some(None));
+        let capturing = CAPTURE_BACKTRACE.with(|capture| capture.replace(true));
+        let res = std::panic::catch_unwind(AssertUnwindSafe(f));
+        CAPTURE_BACKTRACE.with(|capture| capture.set(capturing));
+        self.client.replace_event_context(previous);
+
+        let payload = match res {
+            Ok(i) => return i,
+            Err(payload) => payload,
+        };
+
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "<unknown payload>".to_string()
+        };
+
+        let panic = CallbackPanic {
+            callback: name,
+            message,
+            backtrace: BACKTRACE.with(|backtrace| backtrace.take()),
+        };
+
+        self.panic_policy.handle(&self.client, &panic)
     }
 }
 
 impl IDebugEventCallbacks_Impl for DbgEventCallbacks {
     fn GetInterestMask(&self) -> windows::core::Result<u32> {
-        Ok(
-            DEBUG_EVENT_BREAKPOINT | 
-            DEBUG_EVENT_EXCEPTION | 
-            DEBUG_EVENT_CHANGE_ENGINE_STATE
-        )
+        Ok(self.callbacks.interest_mask().bits())
     }
 
     fn Breakpoint(
@@ -98,19 +857,10 @@ impl IDebugEventCallbacks_Impl for DbgEventCallbacks {
 
         // N.B: The breakpoint must be represented as "borrowed" because it could be
         // invalid after this callback returns.
-        let res = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let res = self.guard("breakpoint", || {
             self.callbacks
                 .breakpoint(&self.client, &DebugBreakpoint::new(bp).unwrap())
-        }));
-
-        let res = match res {
-            Ok(i) => i,
-            Err(panic) => {
-                // If the callback panics, we'll just ignore it and continue.
-                let _ = dlogln!(self.client, "panic in breakpoint callback: {:?}", panic);
-                DebugInstruction::GoNotHandled
-            }
-        };
+        });
 
         // N.B: This is pretty lame; the API is declared to return a HRESULT, but it
         // does not actually return a HRESULT. We'll need to shim our return
@@ -130,19 +880,9 @@ impl IDebugEventCallbacks_Impl for DbgEventCallbacks {
             first_chance: firstchance
         };
        
-        let res = std::panic::catch_unwind(AssertUnwindSafe(|| {
-            self.callbacks
-                .exception(&self.client, &exception_info)
-        }));
-
-        let res = match res {
-            Ok(i) => i,
-            Err(panic) => {
-                // If the callback panics, we'll just ignore it and continue.
-                let _ = dlogln!(self.client, "panic in exception callback: {:?}", panic);
-                DebugInstruction::GoNotHandled
-            }
-        };
+        let res = self.guard("exception", || {
+            self.callbacks.exception(&self.client, &exception_info)
+        });
 
         // N.B: This is pretty lame; the API is declared to return a HRESULT, but it
         // does not actually return a HRESULT. We'll need to shim our return
@@ -154,16 +894,25 @@ impl IDebugEventCallbacks_Impl for DbgEventCallbacks {
 
     fn CreateThread(
         &self,
-        _handle: u64,
-        _dataoffset: u64,
-        _startoffset: u64,
+        handle: u64,
+        dataoffset: u64,
+        startoffset: u64,
     ) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: CreateThread");
+        self.guard("create_thread", || {
+            self.callbacks
+                .create_thread(&self.client, handle, dataoffset, startoffset);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
-    fn ExitThread(&self, _exitcode: u32) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: ExitThreat");
+    fn ExitThread(&self, exitcode: u32) -> windows::core::Result<()> {
+        self.guard("exit_thread", || {
+            self.callbacks.exit_thread(&self.client, exitcode);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
@@ -171,73 +920,145 @@ impl IDebugEventCallbacks_Impl for DbgEventCallbacks {
         &self,
         _imagefilehandle: u64,
         _handle: u64,
-        _baseoffset: u64,
-        _modulesize: u32,
-        _modulename: &windows::core::PCSTR,
-        _imagename: &windows::core::PCSTR,
-        _checksum: u32,
-        _timedatestamp: u32,
+        baseoffset: u64,
+        modulesize: u32,
+        modulename: &windows::core::PCSTR,
+        imagename: &windows::core::PCSTR,
+        checksum: u32,
+        timedatestamp: u32,
         _initialthreadhandle: u64,
         _threaddataoffset: u64,
         _startoffset: u64,
     ) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: CreateProcessA");
+        let module = ModuleInfo {
+            name: pcstr_to_string(modulename),
+            image_path: pcstr_to_string(imagename),
+            base: baseoffset,
+            size: modulesize,
+            timestamp: timedatestamp,
+            checksum,
+        };
+
+        self.guard("create_process", || {
+            self.callbacks.create_process(&self.client, &module);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
-    fn ExitProcess(&self, _exitcode: u32) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: ExitProcess");
+    fn ExitProcess(&self, exitcode: u32) -> windows::core::Result<()> {
+        self.guard("exit_process", || {
+            self.callbacks.exit_process(&self.client, exitcode);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
     fn LoadModule(
         &self,
         _imagefilehandle: u64,
-        _baseoffset: u64,
-        _modulesize: u32,
-        _modulename: &windows::core::PCSTR,
-        _imagename: &windows::core::PCSTR,
-        _checksum: u32,
-        _timedatestamp: u32,
+        baseoffset: u64,
+        modulesize: u32,
+        modulename: &windows::core::PCSTR,
+        imagename: &windows::core::PCSTR,
+        checksum: u32,
+        timedatestamp: u32,
     ) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: LoadModule");
+        let module = ModuleInfo {
+            name: pcstr_to_string(modulename),
+            image_path: pcstr_to_string(imagename),
+            base: baseoffset,
+            size: modulesize,
+            timestamp: timedatestamp,
+            checksum,
+        };
+
+        self.guard("load_module", || {
+            self.callbacks.load_module(&self.client, &module);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
     fn UnloadModule(
         &self,
-        _imagebasename: &windows::core::PCSTR,
-        _baseoffset: u64,
+        imagebasename: &windows::core::PCSTR,
+        baseoffset: u64,
     ) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: UnloadModule");
+        let image_base_name = pcstr_to_string(imagebasename);
+        self.guard("unload_module", || {
+            self.callbacks
+                .unload_module(&self.client, &image_base_name, baseoffset);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
-    fn SystemError(&self, _error: u32, _level: u32) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: SystemError");
+    fn SystemError(&self, error: u32, level: u32) -> windows::core::Result<()> {
+        self.guard("system_error", || {
+            self.callbacks.system_error(&self.client, error, level);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
-    fn SessionStatus(&self, _status: u32) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: SessionStatus");
+    fn SessionStatus(&self, status: u32) -> windows::core::Result<()> {
+        self.guard("session_status", || {
+            self.callbacks.session_status(&self.client, status);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
-    fn ChangeDebuggeeState(&self, _flags: u32, _argument: u64) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: ChangeDebuggeeState");
+    fn ChangeDebuggeeState(&self, flags: u32, argument: u64) -> windows::core::Result<()> {
+        self.guard("change_debuggee_state", || {
+            self.callbacks
+                .change_debuggee_state(&self.client, flags, argument);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
     fn ChangeEngineState(&self, flags: u32, argument: u64) -> windows::core::Result<()> {
-         let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        self.guard("change_engine_state", || {
             self.callbacks
-                .change_engine_state(&self.client, flags, argument)
-        }));
+                .change_engine_state(&self.client, flags, argument);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 
-    fn ChangeSymbolState(&self, _flags: u32, _argument: u64) -> windows::core::Result<()> {
-        let _ = dlogln!(self.client, "Event: ChangeSymbolState");
+    fn ChangeSymbolState(&self, flags: u32, argument: u64) -> windows::core::Result<()> {
+        self.guard("change_symbol_state", || {
+            self.callbacks
+                .change_symbol_state(&self.client, flags, argument);
+            DebugInstruction::NoChange
+        });
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DebugInstruction;
+
+    #[test]
+    fn merge() {
+        use DebugInstruction::*;
+        assert_eq!(NoChange.merge(Go), Go);
+        assert_eq!(Go.merge(NoChange), Go);
+        assert_eq!(GoHandled.merge(GoNotHandled), GoNotHandled);
+        assert_eq!(Break.merge(StepInto), Break);
+        assert_eq!(StepOver.merge(StepBranch).merge(StepInto), StepInto);
+        assert_eq!(Restart.merge(Break), Restart);
+    }
+}
@@ -0,0 +1,137 @@
+//! This contains types describing the event filters of the engine, which
+//! decide what happens when an event (like a module load) or an exception
+//! occurs; they are what the `sx*` commands configure.
+use anyhow::{bail, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    DEBUG_FILTER_BREAK, DEBUG_FILTER_GO_HANDLED, DEBUG_FILTER_GO_NOT_HANDLED, DEBUG_FILTER_IGNORE,
+    DEBUG_FILTER_OUTPUT, DEBUG_FILTER_REMOVE, DEBUG_FILTER_SECOND_CHANCE_BREAK,
+};
+
+/// What the engine does when the event of a filter occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FilterExecution {
+    /// Break into the debugger (`sxe`).
+    Break,
+    /// Break into the debugger on second-chance exceptions only (`sxd`).
+    SecondChanceBreak,
+    /// Display a message and continue (`sxn`).
+    Output,
+    /// Continue silently (`sxi`).
+    Ignore,
+    /// Remove the filter; only valid for arbitrary exception filters (`sxr`).
+    Remove,
+}
+
+impl FilterExecution {
+    pub(crate) fn as_option(&self) -> u32 {
+        match self {
+            Self::Break => DEBUG_FILTER_BREAK,
+            Self::SecondChanceBreak => DEBUG_FILTER_SECOND_CHANCE_BREAK,
+            Self::Output => DEBUG_FILTER_OUTPUT,
+            Self::Ignore => DEBUG_FILTER_IGNORE,
+            Self::Remove => DEBUG_FILTER_REMOVE,
+        }
+    }
+
+    pub(crate) fn from_option(option: u32) -> Result<Self> {
+        Ok(match option {
+            DEBUG_FILTER_BREAK => Self::Break,
+            DEBUG_FILTER_SECOND_CHANCE_BREAK => Self::SecondChanceBreak,
+            DEBUG_FILTER_OUTPUT => Self::Output,
+            DEBUG_FILTER_IGNORE => Self::Ignore,
+            DEBUG_FILTER_REMOVE => Self::Remove,
+            _ => bail!("unknown filter execution option {option:#x}"),
+        })
+    }
+}
+
+/// How the target resumes after an exception.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FilterContinue {
+    /// The exception is marked as handled.
+    GoHandled,
+    /// The exception is marked as not handled.
+    GoNotHandled,
+}
+
+impl FilterContinue {
+    pub(crate) fn as_option(&self) -> u32 {
+        match self {
+            Self::GoHandled => DEBUG_FILTER_GO_HANDLED,
+            Self::GoNotHandled => DEBUG_FILTER_GO_NOT_HANDLED,
+        }
+    }
+
+    pub(crate) fn from_option(option: u32) -> Result<Self> {
+        Ok(match option {
+            DEBUG_FILTER_GO_HANDLED => Self::GoHandled,
+            DEBUG_FILTER_GO_NOT_HANDLED => Self::GoNotHandled,
+            _ => bail!("unknown filter continue option {option:#x}"),
+        })
+    }
+}
+
+/// The number of filters of each kind. The filters are indexed in that
+/// order: the specific events first, then the specific exceptions and the
+/// arbitrary exceptions.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EventFilterCounts {
+    /// The number of filters for events that aren't exceptions, like thread
+    /// creation.
+    pub specific_events: u32,
+    /// The number of filters for the exceptions known by the engine.
+    pub specific_exceptions: u32,
+    /// The number of filters for other exceptions added by the user.
+    pub arbitrary_exceptions: u32,
+}
+
+impl EventFilterCounts {
+    /// Get the number of exception filters.
+    pub fn exceptions(&self) -> u32 {
+        self.specific_exceptions + self.arbitrary_exceptions
+    }
+}
+
+/// The filter of an event that isn't an exception.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpecificFilter {
+    /// The index of the filter.
+    pub index: u32,
+    /// The description of the event, like `Create thread`.
+    pub text: String,
+    /// What the engine does when the event occurs.
+    pub execution: FilterExecution,
+    /// How the target resumes after the event.
+    pub continue_option: FilterContinue,
+    /// The command executed when the event occurs.
+    pub command: String,
+    /// The argument of the filter, like the name of the module for module
+    /// loads, if the filter takes one.
+    pub argument: Option<String>,
+}
+
+/// The filter of an exception.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExceptionFilter {
+    /// The index of the filter.
+    pub index: u32,
+    /// The exception code, like `0xe06d7363` for C++ exceptions.
+    pub code: u32,
+    /// The description of the exception.
+    pub text: String,
+    /// What the engine does when the exception occurs.
+    pub execution: FilterExecution,
+    /// How the target resumes after the exception.
+    pub continue_option: FilterContinue,
+    /// The command executed on first-chance exceptions.
+    pub command: String,
+    /// The command executed on second-chance exceptions.
+    pub second_command: String,
+}
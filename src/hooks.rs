@@ -0,0 +1,612 @@
+//! This contains a function-hooking framework: a [`Hook`] invokes closures
+//! when a function is entered and when it returns, which is what most
+//! tracing extensions need.
+//!
+//! ```ignore
+//! let hooks = Rc::new(HookManager::new(client.clone()));
+//! hooks.install(
+//!     Hook::on("KERNELBASE!VirtualAlloc")
+//!         .enter(|ctx| {
+//!             let args = ctx.args(4, CallConv::Win64)?;
+//!             dlogln!(ctx.client(), "VirtualAlloc({:#x}, {:#x})", args[0], args[1])
+//!         })
+//!         .exit(|ctx| dlogln!(ctx.client(), "-> {:#x}", ctx.return_value()?)),
+//! )?;
+//! client.set_event_callbacks(Rc::clone(&hooks))?;
+//! ```
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::Result;
+use windows::core::GUID;
+
+use crate::breakpoint::{
+    remove_all, BreakpointFlags, BreakpointTarget, BreakpointType, DebugBreakpoint,
+};
+use crate::callconv::{self, CallConv};
+use crate::client::DebugClient;
+use crate::dlogln;
+use crate::events::{DebugInstruction, EventCallbacks, EventInterest};
+use crate::exception::ExceptionInfo;
+
+/// The closure invoked when a hooked function is entered or returns.
+type HookCallback = dyn FnMut(&HookContext) -> Result<()>;
+
+/// What a [`Hook`] callback knows about the call it is invoked for.
+pub struct HookContext<'a> {
+    client: &'a DebugClient,
+    name: &'a str,
+    thread_id: u32,
+    stack_pointer: u64,
+    return_address: u64,
+    depth: usize,
+}
+
+impl HookContext<'_> {
+    /// Get the client the event is delivered to.
+    pub fn client(&self) -> &DebugClient {
+        self.client
+    }
+
+    /// Get the target of the hook, like `KERNELBASE!VirtualAlloc`.
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Get the engine ID of the thread making the call.
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    /// Get the stack pointer when the function was entered.
+    pub fn stack_pointer(&self) -> u64 {
+        self.stack_pointer
+    }
+
+    /// Get the address the function returns to.
+    pub fn return_address(&self) -> u64 {
+        self.return_address
+    }
+
+    /// Get the number of calls to the same function that are still running
+    /// on the thread below this one; it is 0 unless the function is
+    /// reentered, like when it is recursive.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Read the first `n` arguments of the call according to the calling
+    /// convention `conv`; only meaningful when the function is entered.
+    pub fn args(&self, n: usize, conv: CallConv) -> Result<Vec<u64>> {
+        callconv::read_args(self.client, n, conv)
+    }
+
+    /// Read the value returned by the function (`rax` or `eax`); only
+    /// meaningful when the function returns.
+    pub fn return_value(&self) -> Result<u64> {
+        if self.client.is_pointer_64bit()? {
+            self.client.reg64("rax")
+        } else {
+            self.client.reg64("eax")
+        }
+    }
+}
+
+/// A function hook, installed with [`HookManager::install`].
+pub struct Hook {
+    target: BreakpointTarget,
+    // N.B: The callbacks are reference counted so that they can run without
+    // borrowing the registry, which allows them to install or remove hooks.
+    enter: Option<Rc<RefCell<HookCallback>>>,
+    exit: Option<Rc<RefCell<HookCallback>>>,
+}
+
+impl Hook {
+    /// Hook the function at `target`, like `KERNELBASE!VirtualAlloc`.
+    pub fn on<T: Into<BreakpointTarget>>(target: T) -> Self {
+        Self {
+            target: target.into(),
+            enter: None,
+            exit: None,
+        }
+    }
+
+    /// Invoke `callback` when the function is entered.
+    pub fn enter<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&HookContext) -> Result<()> + 'static,
+    {
+        self.enter = Some(Rc::new(RefCell::new(callback)));
+
+        self
+    }
+
+    /// Invoke `callback` when the function returns.
+    pub fn exit<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&HookContext) -> Result<()> + 'static,
+    {
+        self.exit = Some(Rc::new(RefCell::new(callback)));
+
+        self
+    }
+}
+
+/// A hook installed in a [`HookManager`].
+struct InstalledHook {
+    bp: DebugBreakpoint,
+    name: String,
+    enter: Option<Rc<RefCell<HookCallback>>>,
+    exit: Option<Rc<RefCell<HookCallback>>>,
+}
+
+/// A call to a hooked function that hasn't returned yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PendingCall {
+    hook: GUID,
+    stack_pointer: u64,
+    return_address: u64,
+}
+
+/// A breakpoint on a return address, shared by the pending calls of a thread
+/// returning there.
+struct ReturnBreakpoint {
+    bp: DebugBreakpoint,
+    thread_id: u32,
+    address: u64,
+}
+
+/// What happened to the pending calls of a thread when it hit a return
+/// breakpoint; see [`CallTracker::exit`].
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CallExit {
+    /// The calls that returned to the breakpoint, from the innermost; their
+    /// exit callback is invoked.
+    returned: Vec<PendingCall>,
+    /// The return addresses no pending call of the thread returns to anymore,
+    /// whose breakpoint can be removed.
+    released: Vec<u64>,
+}
+
+/// The calls of the hooked functions that haven't returned yet, by thread,
+/// and the number of them returning to each address, which tells when a
+/// return breakpoint is needed.
+#[derive(Debug, Default)]
+struct CallTracker {
+    /// The pending calls of each engine thread, from the outermost.
+    calls: HashMap<u32, Vec<PendingCall>>,
+    /// The number of pending calls by engine thread and return address.
+    returns: HashMap<(u32, u64), usize>,
+}
+
+impl CallTracker {
+    /// Get the number of pending calls of the thread `thread_id` returning
+    /// to `address`.
+    fn returning(&self, thread_id: u32, address: u64) -> usize {
+        self.returns
+            .get(&(thread_id, address))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Track `call`, made by the thread `thread_id`.
+    fn push(&mut self, thread_id: u32, call: PendingCall) {
+        *self
+            .returns
+            .entry((thread_id, call.return_address))
+            .or_default() += 1;
+        self.calls.entry(thread_id).or_default().push(call);
+    }
+
+    /// Handle the thread `thread_id` reaching the return address `ip` with
+    /// the stack pointer `sp`. The calls whose frame is below the stack
+    /// pointer are over: either they returned here, or their frame got
+    /// unwound (like by an exception) and their exit callback is skipped.
+    fn exit(&mut self, thread_id: u32, ip: u64, sp: u64) -> CallExit {
+        let mut exit = CallExit::default();
+        let Some(calls) = self.calls.get_mut(&thread_id) else {
+            return exit;
+        };
+
+        while calls.last().is_some_and(|call| call.stack_pointer < sp) {
+            let call = calls.pop().expect("the call was just seen");
+            let key = (thread_id, call.return_address);
+            if let Some(count) = self.returns.get_mut(&key) {
+                *count -= 1;
+                if *count == 0 {
+                    self.returns.remove(&key);
+                    exit.released.push(call.return_address);
+                }
+            }
+
+            if call.return_address == ip {
+                exit.returned.push(call);
+            }
+        }
+
+        if calls.is_empty() {
+            self.calls.remove(&thread_id);
+        }
+
+        exit
+    }
+
+    /// Get the number of pending calls of the hook `hook` on the thread
+    /// `thread_id`.
+    fn depth(&self, thread_id: u32, hook: GUID) -> usize {
+        self.calls.get(&thread_id).map_or(0, |calls| {
+            calls.iter().filter(|call| call.hook == hook).count()
+        })
+    }
+
+    /// Get the number of pending calls, on every thread.
+    fn len(&self) -> usize {
+        self.calls.values().map(Vec::len).sum()
+    }
+
+    /// Forget every call.
+    fn clear(&mut self) {
+        self.calls.clear();
+        self.returns.clear();
+    }
+}
+
+/// A registry of [`Hook`]s.
+///
+/// The manager implements [`EventCallbacks`]: it sets a breakpoint on every
+/// hooked function and, for the hooks with an exit callback, a breakpoint on
+/// the return address of every call, restricted to the calling thread. Like a
+/// [`BreakpointManager`], it can be shared with a [`Rc`] and removes its
+/// breakpoints when dropped.
+///
+/// [`BreakpointManager`]: crate::breakpoint::BreakpointManager
+pub struct HookManager {
+    client: DebugClient,
+    hooks: RefCell<HashMap<GUID, InstalledHook>>,
+    returns: RefCell<HashMap<GUID, ReturnBreakpoint>>,
+    calls: RefCell<CallTracker>,
+}
+
+impl HookManager {
+    pub fn new(client: DebugClient) -> Self {
+        Self {
+            client,
+            hooks: RefCell::new(HashMap::new()),
+            returns: RefCell::new(HashMap::new()),
+            calls: RefCell::new(CallTracker::default()),
+        }
+    }
+
+    /// Install `hook`; the returned GUID identifies it. An expression target
+    /// whose module isn't loaded yet is resolved by the engine once it is.
+    pub fn install(&self, hook: Hook) -> Result<GUID> {
+        let name = match &hook.target {
+            BreakpointTarget::Address(addr) => format!("{addr:#x}"),
+            BreakpointTarget::Expression(expression) => expression.clone(),
+        };
+
+        let bp = self.client.add_breakpoint(BreakpointType::Code, None)?;
+        let res = match hook.target {
+            BreakpointTarget::Address(addr) => bp.set_offset(addr),
+            BreakpointTarget::Expression(expression) => bp.set_offset_expression(expression),
+        }
+        .and_then(|_| bp.set_flags(BreakpointFlags::ENABLED))
+        .and_then(|_| bp.guid());
+
+        let guid = match res {
+            Ok(guid) => guid,
+            Err(e) => {
+                let _ = self.client.remove_breakpoint(bp);
+                return Err(e);
+            }
+        };
+
+        self.hooks.borrow_mut().insert(guid, InstalledHook {
+            bp,
+            name,
+            enter: hook.enter,
+            exit: hook.exit,
+        });
+
+        Ok(guid)
+    }
+
+    /// Remove the hook identified by `guid`; `false` is returned if the
+    /// manager doesn't know about it. The exit callback isn't invoked for the
+    /// calls that haven't returned yet.
+    pub fn remove(&self, guid: &GUID) -> Result<bool> {
+        let Some(hook) = self.hooks.borrow_mut().remove(guid) else {
+            return Ok(false);
+        };
+
+        self.client.remove_breakpoint(hook.bp)?;
+
+        Ok(true)
+    }
+
+    /// Remove every hook and every return breakpoint.
+    pub fn clear(&self) -> Result<()> {
+        self.calls.borrow_mut().clear();
+
+        let hooks = self.hooks.take().into_values().map(|hook| hook.bp);
+        let returns = self.returns.take().into_values().map(|ret| ret.bp);
+
+        remove_all(&self.client, hooks.chain(returns))
+    }
+
+    /// Get the number of hooks.
+    pub fn len(&self) -> usize {
+        self.hooks.borrow().len()
+    }
+
+    /// Is the manager empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the number of calls that haven't returned yet, on every thread.
+    pub fn pending_calls(&self) -> usize {
+        self.calls.borrow().len()
+    }
+
+    /// Handle the breakpoint of the hook identified by `guid`.
+    fn enter(&self, client: &DebugClient, guid: GUID) -> Result<()> {
+        let Some((name, enter, has_exit)) = self.hooks.borrow().get(&guid).map(|hook| {
+            (
+                hook.name.clone(),
+                hook.enter.as_ref().map(Rc::clone),
+                hook.exit.is_some(),
+            )
+        }) else {
+            return Ok(());
+        };
+
+        let thread_id = client.current_thread()?;
+        let stack_pointer = client.sp()?;
+        let return_address = client.return_offset()?;
+        let depth = self.calls.borrow().depth(thread_id, guid);
+
+        // N.B: The call is tracked before invoking the callback, so that the exit
+        // callback runs even if the enter one fails.
+        if has_exit {
+            self.track(thread_id, PendingCall {
+                hook: guid,
+                stack_pointer,
+                return_address,
+            })?;
+        }
+
+        if let Some(enter) = enter {
+            let ctx = HookContext {
+                client,
+                name: &name,
+                thread_id,
+                stack_pointer,
+                return_address,
+                depth,
+            };
+
+            (*enter.borrow_mut())(&ctx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle a return breakpoint.
+    fn exit(&self, client: &DebugClient) -> Result<()> {
+        let thread_id = client.current_thread()?;
+        let ip = client.ip()?;
+        let sp = client.sp()?;
+
+        let exit = self.calls.borrow_mut().exit(thread_id, ip, sp);
+        let mut res = Ok(());
+        for address in exit.released {
+            res = res.and(self.release(thread_id, address));
+        }
+
+        for call in exit.returned {
+            let Some((name, exit)) = self
+                .hooks
+                .borrow()
+                .get(&call.hook)
+                .map(|hook| (hook.name.clone(), hook.exit.as_ref().map(Rc::clone)))
+            else {
+                continue;
+            };
+
+            let Some(exit) = exit else {
+                continue;
+            };
+
+            let ctx = HookContext {
+                client,
+                name: &name,
+                thread_id,
+                stack_pointer: call.stack_pointer,
+                return_address: call.return_address,
+                depth: self.calls.borrow().depth(thread_id, call.hook),
+            };
+
+            res = res.and((*exit.borrow_mut())(&ctx));
+        }
+
+        res
+    }
+
+    /// Track `call`, and make sure a breakpoint catches it returning.
+    fn track(&self, thread_id: u32, call: PendingCall) -> Result<()> {
+        let address = call.return_address;
+        if self.calls.borrow().returning(thread_id, address) == 0 {
+            let bp = self.client.add_breakpoint(BreakpointType::Code, None)?;
+            let res = bp
+                .set_offset(address)
+                .and_then(|_| bp.set_match_thread(thread_id))
+                .and_then(|_| bp.set_flags(BreakpointFlags::ENABLED))
+                .and_then(|_| bp.guid());
+
+            let guid = match res {
+                Ok(guid) => guid,
+                Err(e) => {
+                    let _ = self.client.remove_breakpoint(bp);
+                    return Err(e);
+                }
+            };
+
+            self.returns.borrow_mut().insert(guid, ReturnBreakpoint {
+                bp,
+                thread_id,
+                address,
+            });
+        }
+
+        self.calls.borrow_mut().push(thread_id, call);
+
+        Ok(())
+    }
+
+    /// Remove the breakpoint catching the calls of the thread `thread_id`
+    /// returning to `address`, as no call needs it anymore.
+    fn release(&self, thread_id: u32, address: u64) -> Result<()> {
+        let mut returns = self.returns.borrow_mut();
+        let Some(guid) = returns
+            .iter()
+            .find(|(_, ret)| ret.thread_id == thread_id && ret.address == address)
+            .map(|(guid, _)| *guid)
+        else {
+            return Ok(());
+        };
+
+        let ret = returns
+            .remove(&guid)
+            .expect("the breakpoint was just found");
+
+        self.client.remove_breakpoint(ret.bp)
+    }
+}
+
+impl EventCallbacks for HookManager {
+    fn breakpoint(&self, client: &DebugClient, bp: &DebugBreakpoint) -> DebugInstruction {
+        let Ok(guid) = bp.guid() else {
+            return DebugInstruction::NoChange;
+        };
+
+        let res = if self.hooks.borrow().contains_key(&guid) {
+            self.enter(client, guid)
+        } else if self.returns.borrow().contains_key(&guid) {
+            self.exit(client)
+        } else {
+            return DebugInstruction::NoChange;
+        };
+
+        if let Err(e) = res {
+            let _ = dlogln!(client, "Error in hook callback: {e:?}");
+        }
+
+        DebugInstruction::Go
+    }
+
+    fn exception(&self, _client: &DebugClient, _ei: &ExceptionInfo) -> DebugInstruction {
+        DebugInstruction::NoChange
+    }
+
+    fn change_engine_state(&self, _client: &DebugClient, _flags: u32, _argument: u64) {}
+
+    fn interest_mask(&self) -> EventInterest {
+        EventInterest::BREAKPOINT
+    }
+}
+
+impl Drop for HookManager {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const F: GUID = GUID::from_u128(1);
+    const G: GUID = GUID::from_u128(2);
+
+    fn call(hook: GUID, stack_pointer: u64, return_address: u64) -> PendingCall {
+        PendingCall {
+            hook,
+            stack_pointer,
+            return_address,
+        }
+    }
+
+    #[test]
+    fn recursion() {
+        // `F` is called from 0x500, and calls itself twice from 0x600.
+        let mut tracker = CallTracker::default();
+        tracker.push(1, call(F, 0x1000, 0x500));
+        tracker.push(1, call(F, 0xf00, 0x600));
+        tracker.push(1, call(F, 0xe00, 0x600));
+        assert_eq!(tracker.depth(1, F), 3);
+        assert_eq!(tracker.depth(1, G), 0);
+        assert_eq!(tracker.returning(1, 0x600), 2);
+
+        // The innermost call returns; the breakpoint on 0x600 is still needed.
+        assert_eq!(tracker.exit(1, 0x600, 0xe08), CallExit {
+            returned: vec![call(F, 0xe00, 0x600)],
+            released: Vec::new(),
+        });
+        assert_eq!(tracker.depth(1, F), 2);
+
+        // A return to 0x600 from a deeper frame doesn't end any call.
+        assert_eq!(tracker.exit(1, 0x600, 0xd08), CallExit::default());
+
+        assert_eq!(tracker.exit(1, 0x600, 0xf08), CallExit {
+            returned: vec![call(F, 0xf00, 0x600)],
+            released: vec![0x600],
+        });
+        assert_eq!(tracker.exit(1, 0x500, 0x1008), CallExit {
+            returned: vec![call(F, 0x1000, 0x500)],
+            released: vec![0x500],
+        });
+        assert_eq!(tracker.len(), 0);
+    }
+
+    #[test]
+    fn unwinding() {
+        // `G` is called by `F`, and an exception unwinds both frames up to the
+        // caller of `F`, which reaches the return address of `F` later.
+        let mut tracker = CallTracker::default();
+        tracker.push(1, call(F, 0x1000, 0x500));
+        tracker.push(1, call(G, 0xf00, 0x700));
+        tracker.push(2, call(G, 0x2000, 0x700));
+
+        assert_eq!(tracker.exit(1, 0x500, 0x1008), CallExit {
+            returned: vec![call(F, 0x1000, 0x500)],
+            released: vec![0x700, 0x500],
+        });
+
+        // The call of the other thread is untouched.
+        assert_eq!(tracker.len(), 1);
+        assert_eq!(tracker.returning(2, 0x700), 1);
+    }
+
+    #[test]
+    fn shared() {
+        // `F` tail-calls `G`, so both calls return to 0x500 with the same stack
+        // pointer and share a return breakpoint.
+        let mut tracker = CallTracker::default();
+        tracker.push(1, call(F, 0x1000, 0x500));
+        tracker.push(1, call(G, 0x1000, 0x500));
+        assert_eq!(tracker.returning(1, 0x500), 2);
+
+        assert_eq!(tracker.exit(1, 0x500, 0x1008), CallExit {
+            returned: vec![call(G, 0x1000, 0x500), call(F, 0x1000, 0x500)],
+            released: vec![0x500],
+        });
+        assert_eq!(tracker.returning(1, 0x500), 0);
+
+        tracker.push(1, call(F, 0x1000, 0x500));
+        tracker.clear();
+        assert_eq!(tracker.len(), 0);
+        assert_eq!(tracker.exit(1, 0x500, 0x1008), CallExit::default());
+    }
+}
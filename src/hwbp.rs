@@ -0,0 +1,233 @@
+//! This contains helpers to program the hardware debug registers (`dr0`-`dr3`
+//! & `dr7`) of the current thread directly. Unlike the data breakpoints of the
+//! engine, those aren't tracked by the debugger and don't show up in `bl`.
+use anyhow::{bail, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::bits::Bits;
+use crate::client::DebugClient;
+
+/// The number of hardware breakpoint slots.
+pub const HW_SLOTS: usize = 4;
+
+/// The names of the address registers of the slots.
+const DR_NAMES: [&str; HW_SLOTS] = ["dr0", "dr1", "dr2", "dr3"];
+
+/// The kind of access that triggers a hardware breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HwCondition {
+    /// Break on instruction execution.
+    Execute,
+    /// Break on data writes.
+    Write,
+    /// Break on I/O reads or writes.
+    Io,
+    /// Break on data reads or writes.
+    ReadWrite,
+}
+
+impl HwCondition {
+    fn as_bits(&self) -> u64 {
+        match self {
+            Self::Execute => 0b00,
+            Self::Write => 0b01,
+            Self::Io => 0b10,
+            Self::ReadWrite => 0b11,
+        }
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        match bits & 0b11 {
+            0b00 => Self::Execute,
+            0b01 => Self::Write,
+            0b10 => Self::Io,
+            _ => Self::ReadWrite,
+        }
+    }
+}
+
+/// A hardware breakpoint programmed in one of the slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HwBreakpoint {
+    /// The slot (0 to 3) the breakpoint uses.
+    pub slot: usize,
+    /// The address watched by the breakpoint.
+    pub addr: u64,
+    /// The kind of access that triggers the breakpoint.
+    pub condition: HwCondition,
+    /// The number of bytes watched (1, 2, 4 or 8).
+    pub len: u64,
+}
+
+/// The debug control register, which enables the slots and configures them.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Dr7(pub u64);
+
+impl Dr7 {
+    /// Is the slot enabled, either locally or globally?
+    pub fn is_enabled(&self, slot: usize) -> bool {
+        assert!(slot < HW_SLOTS, "there are only {HW_SLOTS} slots");
+        self.0.bits(2 * slot..=2 * slot + 1) != 0
+    }
+
+    /// Get the kind of access that triggers the slot.
+    pub fn condition(&self, slot: usize) -> HwCondition {
+        assert!(slot < HW_SLOTS, "there are only {HW_SLOTS} slots");
+        HwCondition::from_bits(self.0.bits(16 + 4 * slot..=17 + 4 * slot))
+    }
+
+    /// Get the number of bytes watched by the slot.
+    pub fn length(&self, slot: usize) -> u64 {
+        assert!(slot < HW_SLOTS, "there are only {HW_SLOTS} slots");
+        match self.0.bits(18 + 4 * slot..=19 + 4 * slot) {
+            0b00 => 1,
+            0b01 => 2,
+            0b10 => 8,
+            _ => 4,
+        }
+    }
+
+    /// Locally enable the slot to trigger on `condition` accesses to `len`
+    /// bytes.
+    pub fn enable(&mut self, slot: usize, condition: HwCondition, len: u64) -> Result<()> {
+        assert!(slot < HW_SLOTS, "there are only {HW_SLOTS} slots");
+        let len_bits = match len {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            4 => 0b11,
+            _ => bail!("{len} isn't a valid hardware breakpoint length"),
+        };
+
+        if condition == HwCondition::Execute && len != 1 {
+            bail!("execute hardware breakpoints must have a length of 1");
+        }
+
+        let config_shift = 16 + 4 * slot;
+        self.0 &= !(0b1111 << config_shift);
+        self.0 |= (condition.as_bits() | (len_bits << 2)) << config_shift;
+        self.0 |= 1 << (2 * slot);
+
+        Ok(())
+    }
+
+    /// Disable the slot.
+    pub fn disable(&mut self, slot: usize) {
+        assert!(slot < HW_SLOTS, "there are only {HW_SLOTS} slots");
+        self.0 &= !(0b11 << (2 * slot));
+    }
+
+    /// Get the slots that aren't enabled.
+    pub fn free_slots(&self) -> Vec<usize> {
+        (0..HW_SLOTS)
+            .filter(|&slot| !self.is_enabled(slot))
+            .collect()
+    }
+}
+
+/// Read the debug control register of the current thread.
+pub fn dr7(client: &DebugClient) -> Result<Dr7> {
+    client.reg64("dr7").map(Dr7)
+}
+
+/// Get the slots of the current thread that are free.
+pub fn free_hw_slots(client: &DebugClient) -> Result<Vec<usize>> {
+    Ok(dr7(client)?.free_slots())
+}
+
+/// Get the hardware breakpoints programmed for the current thread.
+pub fn hw_breakpoints(client: &DebugClient) -> Result<Vec<HwBreakpoint>> {
+    let dr7 = dr7(client)?;
+    let enabled = (0..HW_SLOTS)
+        .filter(|&slot| dr7.is_enabled(slot))
+        .collect::<Vec<_>>();
+
+    let names = enabled
+        .iter()
+        .map(|&slot| DR_NAMES[slot])
+        .collect::<Vec<_>>();
+    let addrs = client.regs64(&names)?;
+
+    Ok(enabled
+        .into_iter()
+        .zip(addrs)
+        .map(|(slot, addr)| HwBreakpoint {
+            slot,
+            addr,
+            condition: dr7.condition(slot),
+            len: dr7.length(slot),
+        })
+        .collect())
+}
+
+/// Program a hardware breakpoint for the current thread in the first free
+/// slot.
+pub fn set_hw_breakpoint(
+    client: &DebugClient,
+    addr: u64,
+    condition: HwCondition,
+    len: u64,
+) -> Result<HwBreakpoint> {
+    if len != 0 && addr % len != 0 {
+        bail!("{addr:#x} isn't aligned on {len} bytes");
+    }
+
+    let mut dr7 = dr7(client)?;
+    let Some(&slot) = dr7.free_slots().first() else {
+        bail!("every hardware breakpoint slot is used");
+    };
+
+    dr7.enable(slot, condition, len)?;
+    client.set_regs64(&[(DR_NAMES[slot], addr), ("dr7", dr7.0)])?;
+
+    Ok(HwBreakpoint {
+        slot,
+        addr,
+        condition,
+        len,
+    })
+}
+
+/// Clear the hardware breakpoint programmed in `slot` for the current thread.
+pub fn clear_hw_breakpoint(client: &DebugClient, slot: usize) -> Result<()> {
+    if slot >= HW_SLOTS {
+        bail!("there are only {HW_SLOTS} slots");
+    }
+
+    let mut dr7 = dr7(client)?;
+    dr7.disable(slot);
+    client.set_regs64(&[(DR_NAMES[slot], 0), ("dr7", dr7.0)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dr7() {
+        let mut dr7 = Dr7::default();
+        assert_eq!(dr7.free_slots(), vec![0, 1, 2, 3]);
+
+        dr7.enable(1, HwCondition::Write, 8).unwrap();
+        dr7.enable(3, HwCondition::Execute, 1).unwrap();
+        assert_eq!(dr7.0, 0x90_00_44);
+        assert_eq!(dr7.free_slots(), vec![0, 2]);
+        assert_eq!(dr7.condition(1), HwCondition::Write);
+        assert_eq!(dr7.length(1), 8);
+        assert_eq!(dr7.condition(3), HwCondition::Execute);
+        assert_eq!(dr7.length(3), 1);
+
+        dr7.enable(1, HwCondition::ReadWrite, 4).unwrap();
+        assert_eq!(dr7.condition(1), HwCondition::ReadWrite);
+        assert_eq!(dr7.length(1), 4);
+
+        dr7.disable(1);
+        assert!(!dr7.is_enabled(1));
+        assert!(dr7.enable(0, HwCondition::Execute, 4).is_err());
+        assert!(dr7.enable(0, HwCondition::Write, 3).is_err());
+    }
+}
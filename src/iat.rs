@@ -0,0 +1,321 @@
+//! This contains helpers to recover the import address table of a module
+//! dumped from memory (see [`crate::pe::dump_module`]), like an unpacked
+//! image whose import directory was destroyed: the pointers stored in the
+//! image are resolved against the exports of the other loaded modules.
+//!
+//! ```ignore
+//! let imports = iat::reconstruct_imports(&client, base)?;
+//! for module in &imports.modules {
+//!     println!("{} @ {:#x}", module.name, module.first_thunk);
+//! }
+//! ```
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::System::Diagnostics::Debug::IMAGE_DIRECTORY_ENTRY_IAT;
+
+use crate::client::DebugClient;
+use crate::pe::PeImage;
+
+/// The smallest number of resolved pointers making a run of pointers look
+/// like an IAT, when scanning outside of the IAT directory.
+const MIN_RESOLVED: usize = 2;
+
+/// A function resolved from an export of a loaded module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResolvedExport {
+    /// The image name of the exporting module, like `KERNEL32.DLL`.
+    pub module: String,
+    /// The name of the export, if it isn't exported by ordinal only.
+    pub name: Option<String>,
+    /// The ordinal of the export (biased by the ordinal base).
+    pub ordinal: u32,
+}
+
+/// An IAT slot recovered by [`reconstruct_imports`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ImportedFunction {
+    /// The relative virtual address of the slot.
+    pub slot: u32,
+    /// The address stored in the slot.
+    pub value: u64,
+    /// The name of the function, if it isn't imported by ordinal only.
+    pub name: Option<String>,
+    /// The ordinal of the function.
+    pub ordinal: u32,
+}
+
+/// A run of contiguous IAT slots importing from the same module; it maps to
+/// an import descriptor whose `FirstThunk` is `first_thunk`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ImportedModule {
+    /// The image name of the module, like `KERNEL32.DLL`.
+    pub name: String,
+    /// The relative virtual address of the first slot.
+    pub first_thunk: u32,
+    /// The slots, in order.
+    pub functions: Vec<ImportedFunction>,
+}
+
+/// The imports recovered by [`reconstruct_imports`].
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ImportTable {
+    /// The imported modules, by increasing `first_thunk`.
+    pub modules: Vec<ImportedModule>,
+}
+
+impl ImportTable {
+    /// Get the total number of imported functions.
+    pub fn len(&self) -> usize {
+        self.modules
+            .iter()
+            .map(|module| module.functions.len())
+            .sum()
+    }
+
+    /// Is the table empty?
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+}
+
+/// The exports of the loaded modules, by address.
+#[derive(Default, Debug, Clone)]
+pub struct ExportMap {
+    exports: HashMap<u64, ResolvedExport>,
+}
+
+impl ExportMap {
+    /// Index the exports of every loaded module but the one at `skip_base`;
+    /// the modules whose export directory can't be read are ignored.
+    pub fn new(client: &DebugClient, skip_base: Option<u64>) -> Result<Self> {
+        let mut map = Self::default();
+        for module in client.modules()? {
+            if Some(module.base) == skip_base {
+                continue;
+            }
+
+            let Ok(exports) = client
+                .get_sym_module(&module.name)
+                .and_then(|sym_module| sym_module.exports())
+            else {
+                continue;
+            };
+
+            let image_name = Path::new(&module.image_path).file_name().map_or_else(
+                || format!("{}.dll", module.name),
+                |name| name.to_string_lossy().into_owned(),
+            );
+
+            for export in exports {
+                map.insert(module.base + u64::from(export.rva), ResolvedExport {
+                    module: image_name.clone(),
+                    name: export.name,
+                    ordinal: export.ordinal,
+                });
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Add an export at `addr`. When several exports share an address, the
+    /// first named one is kept.
+    pub fn insert(&mut self, addr: u64, export: ResolvedExport) {
+        let entry = self.exports.entry(addr).or_insert_with(|| export.clone());
+        if entry.name.is_none() && export.name.is_some() {
+            *entry = export;
+        }
+    }
+
+    /// Resolve the address `addr`.
+    pub fn resolve(&self, addr: u64) -> Option<&ResolvedExport> {
+        self.exports.get(&addr)
+    }
+
+    /// Get the number of indexed addresses.
+    pub fn len(&self) -> usize {
+        self.exports.len()
+    }
+
+    /// Is the map empty?
+    pub fn is_empty(&self) -> bool {
+        self.exports.is_empty()
+    }
+}
+
+/// Find the IAT slots in `values`, the pointers stored from the relative
+/// virtual address `rva`. A run of slots is made of resolved pointers and of
+/// NULL pointers terminating the slots of a module; a run ends at a pointer
+/// that doesn't resolve, and is kept if it has at least `min_resolved`
+/// resolved pointers.
+fn scan_slots(
+    rva: u32,
+    pointer_size: u32,
+    values: &[u64],
+    exports: &ExportMap,
+    min_resolved: usize,
+) -> Vec<ImportedModule> {
+    let mut modules = Vec::new();
+    let mut run: Vec<ImportedModule> = Vec::new();
+    let mut resolved = 0;
+    // Is the last module of the run terminated by a NULL pointer?
+    let mut terminated = true;
+    let mut flush = |run: &mut Vec<ImportedModule>, resolved: &mut usize| {
+        if *resolved >= min_resolved {
+            modules.append(run);
+        }
+
+        run.clear();
+        *resolved = 0;
+    };
+
+    for (idx, &value) in values.iter().enumerate() {
+        let slot = rva + idx as u32 * pointer_size;
+        if value == 0 {
+            terminated = true;
+            continue;
+        }
+
+        let Some(export) = exports.resolve(value) else {
+            flush(&mut run, &mut resolved);
+            terminated = true;
+            continue;
+        };
+
+        let function = ImportedFunction {
+            slot,
+            value,
+            name: export.name.clone(),
+            ordinal: export.ordinal,
+        };
+
+        resolved += 1;
+        match run.last_mut() {
+            // Packers don't always terminate the slots of each module, so a
+            // new module also starts a new descriptor.
+            Some(module) if !terminated && module.name == export.module => {
+                module.functions.push(function);
+            }
+            _ => run.push(ImportedModule {
+                name: export.module.clone(),
+                first_thunk: slot,
+                functions: vec![function],
+            }),
+        }
+
+        terminated = false;
+    }
+
+    flush(&mut run, &mut resolved);
+
+    modules
+}
+
+/// Recover the imports of the module mapped at `base` by scanning its memory
+/// for pointers to the exports of the other loaded modules. If the module has
+/// an IAT directory, only that range is scanned; otherwise, every section is,
+/// and only the runs with a few resolved pointers are kept to limit the false
+/// positives.
+pub fn reconstruct_imports(client: &DebugClient, base: u64) -> Result<ImportTable> {
+    let pe = PeImage::parse(client, base)?;
+    let exports = ExportMap::new(client, Some(base))?;
+    let pointer_size: u32 = if pe.is_64bit() { 8 } else { 4 };
+
+    let (ranges, min_resolved) = match pe.data_directory(IMAGE_DIRECTORY_ENTRY_IAT) {
+        Some(iat) => (vec![(iat.VirtualAddress, iat.Size)], 1),
+        None => (
+            pe.sections()
+                .iter()
+                .map(|section| (section.VirtualAddress, unsafe { section.Misc.VirtualSize }))
+                .collect(),
+            MIN_RESOLVED,
+        ),
+    };
+
+    let mut table = ImportTable::default();
+    for (rva, size) in ranges {
+        let mut bytes = vec![0; usize::try_from(size)?];
+        client.read_virtual_lossy(pe.rva_to_va(rva), &mut bytes);
+
+        let values = bytes
+            .chunks_exact(pointer_size as usize)
+            .map(|chunk| match *chunk {
+                [a, b, c, d] => u64::from(u32::from_le_bytes([a, b, c, d])),
+                _ => u64::from_le_bytes(chunk.try_into().unwrap()),
+            })
+            .collect::<Vec<_>>();
+
+        table.modules.extend(scan_slots(
+            rva,
+            pointer_size,
+            &values,
+            &exports,
+            min_resolved,
+        ));
+    }
+
+    table.modules.sort_by_key(|module| module.first_thunk);
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(module: &str, name: &str, ordinal: u32) -> ResolvedExport {
+        ResolvedExport {
+            module: module.into(),
+            name: Some(name.into()),
+            ordinal,
+        }
+    }
+
+    #[test]
+    fn scan() {
+        let mut exports = ExportMap::default();
+        exports.insert(0x1000, export("KERNEL32.DLL", "Sleep", 1));
+        exports.insert(0x1010, export("KERNEL32.DLL", "ExitProcess", 2));
+        exports.insert(0x2000, export("ntdll.dll", "NtClose", 3));
+        exports.insert(0x2000, ResolvedExport {
+            module: "ntdll.dll".into(),
+            name: None,
+            ordinal: 4,
+        });
+
+        let values = [
+            0x1000, 0x1010, 0, 0x2000, 0x1000, 0, 0xdead, 0x2000, 0, 0xbeef, 0x1000, 0x1010,
+        ];
+        let modules = scan_slots(0x3000, 8, &values, &exports, 2);
+        let summary = modules
+            .iter()
+            .map(|module| {
+                let names = module
+                    .functions
+                    .iter()
+                    .map(|function| function.name.as_deref().unwrap())
+                    .collect::<Vec<_>>();
+
+                (module.name.as_str(), module.first_thunk, names)
+            })
+            .collect::<Vec<_>>();
+
+        // The lone pointer after `0xdead` isn't enough to make a run.
+        assert_eq!(summary, vec![
+            ("KERNEL32.DLL", 0x3000, vec!["Sleep", "ExitProcess"]),
+            ("ntdll.dll", 0x3018, vec!["NtClose"]),
+            ("KERNEL32.DLL", 0x3020, vec!["Sleep"]),
+            ("KERNEL32.DLL", 0x3050, vec!["Sleep", "ExitProcess"]),
+        ]);
+        assert_eq!(modules[1].functions[0].ordinal, 3);
+        assert_eq!(scan_slots(0x3000, 8, &values[6..9], &exports, 1).len(), 1);
+    }
+}
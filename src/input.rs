@@ -0,0 +1,67 @@
+//! This contains the plumbing to provide input to the engine when it requests
+//! some, for example when an extension calls [`DebugClient::read_line`].
+//!
+//! [`DebugClient::read_line`]: crate::client::DebugClient::read_line
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+
+use windows::core::implement;
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    IDebugInputCallbacks, IDebugInputCallbacks_Impl,
+};
+
+use crate::client::DebugClient;
+use crate::dlogln;
+
+pub trait InputCallbacks {
+    /// The engine requests a line of input of at most `buffer_size` bytes;
+    /// returning `None` lets another client provide it.
+    fn start_input(&self, client: &DebugClient, buffer_size: u32) -> Option<String>;
+    /// The engine doesn't wait for input anymore.
+    fn end_input(&self, _client: &DebugClient) {}
+}
+
+impl<T: InputCallbacks + ?Sized> InputCallbacks for Rc<T> {
+    fn start_input(&self, client: &DebugClient, buffer_size: u32) -> Option<String> {
+        (**self).start_input(client, buffer_size)
+    }
+
+    fn end_input(&self, client: &DebugClient) {
+        (**self).end_input(client)
+    }
+}
+
+#[implement(IDebugInputCallbacks)]
+pub(crate) struct DbgInputCallbacks {
+    client: DebugClient,
+    callbacks: Box<dyn InputCallbacks>,
+}
+
+impl DbgInputCallbacks {
+    pub(crate) fn new(client: DebugClient, callbacks: Box<dyn InputCallbacks + 'static>) -> Self {
+        Self { client, callbacks }
+    }
+}
+
+impl IDebugInputCallbacks_Impl for DbgInputCallbacks {
+    fn StartInput(&self, buffersize: u32) -> windows::core::Result<()> {
+        let res = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            self.callbacks.start_input(&self.client, buffersize)
+        }));
+
+        if let Ok(Some(line)) = res {
+            if let Err(e) = self.client.return_input(line) {
+                let _ = dlogln!(self.client, "Failed to return input: {e:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn EndInput(&self) -> windows::core::Result<()> {
+        let _ =
+            std::panic::catch_unwind(AssertUnwindSafe(|| self.callbacks.end_input(&self.client)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+//! This contains the options used to connect to a kernel target; see
+//! [`DebugClient::attach_kernel`].
+//!
+//! [`DebugClient::attach_kernel`]: crate::client::DebugClient::attach_kernel
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    DEBUG_ATTACH_EXDI_DRIVER, DEBUG_ATTACH_KERNEL_CONNECTION, DEBUG_ATTACH_LOCAL_KERNEL,
+};
+
+/// The options of a network kernel connection, like the ones configured on
+/// the target with `bcdedit /dbgsettings net`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NetOptions {
+    /// The port the debugger listens on, like `50000`.
+    pub port: u16,
+    /// The key shared with the target, like `1.2.3.4`.
+    pub key: String,
+    /// The address of the target, if the debugger shouldn't wait for it to
+    /// connect.
+    pub target: Option<String>,
+}
+
+impl fmt::Display for NetOptions {
+    /// Format the options like the engine expects them, like
+    /// `net:port=50000,key=1.2.3.4`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "net:port={},key={}", self.port, self.key)?;
+        if let Some(target) = &self.target {
+            write!(f, ",target={target}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How to connect to a kernel target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KernelConnection {
+    /// Debug the kernel of the local machine (`kd -kl`).
+    Local,
+    /// Connect to a target over the network (`kd -k net:...`).
+    Net(NetOptions),
+    /// Connect with a transport string, like
+    /// `com:port=COM1,baud=115200` or `1394:channel=1`.
+    Transport(String),
+    /// Connect through an EXDI driver, like `CLSID={...},Kd=Guess`.
+    Exdi(String),
+}
+
+impl KernelConnection {
+    pub(crate) fn as_flags(&self) -> u32 {
+        match self {
+            Self::Local => DEBUG_ATTACH_LOCAL_KERNEL,
+            Self::Net(_) | Self::Transport(_) => DEBUG_ATTACH_KERNEL_CONNECTION,
+            Self::Exdi(_) => DEBUG_ATTACH_EXDI_DRIVER,
+        }
+    }
+
+    /// Get the connection string passed to the engine, if any.
+    pub(crate) fn options(&self) -> Option<String> {
+        match self {
+            Self::Local => None,
+            Self::Net(options) => Some(options.to_string()),
+            Self::Transport(options) | Self::Exdi(options) => Some(options.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn net_options() {
+        let mut options = NetOptions {
+            port: 50000,
+            key: "1.2.3.4".into(),
+            target: None,
+        };
+
+        assert_eq!(options.to_string(), "net:port=50000,key=1.2.3.4");
+        options.target = Some("10.0.0.2".into());
+        assert_eq!(
+            KernelConnection::Net(options).options().as_deref(),
+            Some("net:port=50000,key=1.2.3.4,target=10.0.0.2")
+        );
+        assert_eq!(KernelConnection::Local.options(), None);
+    }
+}
@@ -1,7 +1,29 @@
 // Axel '0vercl0k' Souchet - March 16 2024
+pub mod allocations;
 pub mod as_pcstr;
 pub mod bits;
+pub mod callconv;
 pub mod client;
+pub mod coverage;
+pub mod dump;
+pub mod event_channel;
+pub mod filter;
+pub mod hooks;
+pub mod hwbp;
+pub mod iat;
+pub mod input;
+pub mod kernel;
+pub mod memory;
+pub mod output;
+pub mod pe;
+pub mod process;
+pub mod registers;
+pub mod snapshot;
+pub mod stack;
+pub mod symbol_group;
+pub mod trace;
+pub mod typed;
+pub mod unwind;
 
 #[allow(non_snake_case)]
 #[inline(always)]
@@ -0,0 +1,624 @@
+//! This contains types describing the memory of the target.
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use anyhow::{bail, Context, Result};
+use windows::Win32::System::Memory::{
+    MEMORY_BASIC_INFORMATION64, MEM_COMMIT, MEM_FREE, MEM_RESERVE, PAGE_EXECUTE, PAGE_EXECUTE_READ,
+    PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_PROTECTION_FLAGS,
+    PAGE_READWRITE, PAGE_TYPE, PAGE_WRITECOPY, VIRTUAL_ALLOCATION_TYPE,
+};
+
+use crate::client::DebugClient;
+
+/// A region of virtual memory as described by the debugger engine; this mirrors
+/// [`MEMORY_BASIC_INFORMATION64`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Base address of the region.
+    pub base: u64,
+    /// Base address of the allocation the region belongs to.
+    pub allocation_base: u64,
+    /// Protection the allocation was initially created with.
+    pub allocation_protect: PAGE_PROTECTION_FLAGS,
+    /// Size of the region in bytes.
+    pub size: u64,
+    /// State of the pages in the region (committed, reserved, free).
+    pub state: VIRTUAL_ALLOCATION_TYPE,
+    /// Current protection of the pages in the region.
+    pub protect: PAGE_PROTECTION_FLAGS,
+    /// Type of the pages in the region (image, mapped, private).
+    pub ty: PAGE_TYPE,
+}
+
+impl MemoryRegion {
+    /// Address of the first byte past the end of the region.
+    pub fn end(&self) -> u64 {
+        self.base.saturating_add(self.size)
+    }
+
+    /// Is `addr` inside the region?
+    pub fn contains(&self, addr: u64) -> bool {
+        (self.base..self.end()).contains(&addr)
+    }
+
+    /// Are the pages of the region committed?
+    pub fn is_committed(&self) -> bool {
+        self.state == MEM_COMMIT
+    }
+
+    /// Are the pages of the region reserved?
+    pub fn is_reserved(&self) -> bool {
+        self.state == MEM_RESERVE
+    }
+
+    /// Are the pages of the region free?
+    pub fn is_free(&self) -> bool {
+        self.state == MEM_FREE
+    }
+
+    /// Are the pages of the region guard pages?
+    pub fn is_guard(&self) -> bool {
+        self.protect.contains(PAGE_GUARD)
+    }
+
+    /// Are the pages of the region writable?
+    pub fn is_writable(&self) -> bool {
+        [
+            PAGE_READWRITE,
+            PAGE_WRITECOPY,
+            PAGE_EXECUTE_READWRITE,
+            PAGE_EXECUTE_WRITECOPY,
+        ]
+        .iter()
+        .any(|p| self.protect.contains(*p))
+    }
+
+    /// Are the pages of the region executable?
+    pub fn is_executable(&self) -> bool {
+        [
+            PAGE_EXECUTE,
+            PAGE_EXECUTE_READ,
+            PAGE_EXECUTE_READWRITE,
+            PAGE_EXECUTE_WRITECOPY,
+        ]
+        .iter()
+        .any(|p| self.protect.contains(*p))
+    }
+}
+
+impl From<MEMORY_BASIC_INFORMATION64> for MemoryRegion {
+    fn from(value: MEMORY_BASIC_INFORMATION64) -> Self {
+        Self {
+            base: value.BaseAddress,
+            allocation_base: value.AllocationBase,
+            allocation_protect: value.AllocationProtect,
+            size: value.RegionSize,
+            state: value.State,
+            protect: value.Protect,
+            ty: value.Type,
+        }
+    }
+}
+
+/// Iterator walking the address space of the current process, region by
+/// region. It is created by [`DebugClient::memory_regions`].
+pub struct MemoryRegions<'a> {
+    /// The client used to query the regions.
+    client: &'a DebugClient,
+    /// The address of the next region to query, if any.
+    next: Option<u64>,
+}
+
+impl<'a> MemoryRegions<'a> {
+    pub(crate) fn new(client: &'a DebugClient) -> Self {
+        Self {
+            client,
+            next: Some(0),
+        }
+    }
+}
+
+impl Iterator for MemoryRegions<'_> {
+    type Item = MemoryRegion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let addr = self.next.take()?;
+
+        // `QueryVirtual` fails once we walk past the highest address of the address
+        // space, which is how we know we are done.
+        let region = self.client.query_virtual(addr).ok()?;
+
+        // Guard against a zero-sized region / a wrap-around which would make us
+        // loop forever.
+        let end = region.end();
+        if end > addr {
+            self.next = Some(end);
+        }
+
+        Some(region)
+    }
+}
+
+/// Parse a byte pattern such as `"48 8b ?? 05"` where `??` (or `?`) is a
+/// wildcard matching any byte.
+pub fn parse_masked_pattern(pattern: &str) -> Result<Vec<Option<u8>>> {
+    let pattern = pattern
+        .split_whitespace()
+        .map(|byte| match byte {
+            "?" | "??" => Ok(None),
+            _ => u8::from_str_radix(byte, 16)
+                .map(Some)
+                .with_context(|| format!("failed to parse {byte:?} as a byte")),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if pattern.is_empty() {
+        bail!("the pattern is empty");
+    }
+
+    Ok(pattern)
+}
+
+/// Find the offsets in `haystack` at which `pattern` matches; `None` entries
+/// in the pattern match any byte.
+pub fn find_masked<'a>(
+    haystack: &'a [u8],
+    pattern: &'a [Option<u8>],
+) -> impl Iterator<Item = usize> + 'a {
+    haystack
+        .windows(pattern.len().max(1))
+        .enumerate()
+        .filter(move |(_, window)| {
+            !pattern.is_empty()
+                && window
+                    .iter()
+                    .zip(pattern)
+                    .all(|(b, p)| p.is_none() || *p == Some(*b))
+        })
+        .map(|(offset, _)| offset)
+}
+
+/// The size of a page; memory is captured at this granularity when a range
+/// is partially unreadable.
+const PAGE_SIZE: u64 = 0x1_000;
+
+/// A contiguous chunk of memory captured by a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotRegion {
+    /// Address of the first byte of the chunk.
+    pub base: u64,
+    /// The captured bytes.
+    pub data: Vec<u8>,
+}
+
+impl SnapshotRegion {
+    /// Address of the first byte past the end of the chunk.
+    pub fn end(&self) -> u64 {
+        self.base + self.data.len() as u64
+    }
+}
+
+/// A contiguous range of bytes that differs between two [`Snapshot`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryChange {
+    /// Address of the first byte that changed.
+    pub address: u64,
+    /// The bytes in the older snapshot.
+    pub old: Vec<u8>,
+    /// The bytes in the newer snapshot.
+    pub new: Vec<u8>,
+}
+
+/// A copy of ranges of the target's virtual memory that can be re-captured
+/// later and compared with [`Snapshot::diff`].
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// The ranges that were asked to be captured.
+    ranges: Vec<Range<u64>>,
+    /// The chunks of memory that were readable, sorted by address.
+    regions: Vec<SnapshotRegion>,
+}
+
+impl Snapshot {
+    /// Capture the memory of `ranges`. Unreadable pages are skipped.
+    pub fn capture(client: &DebugClient, ranges: &[Range<u64>]) -> Result<Self> {
+        let mut regions = Vec::new();
+        for range in ranges {
+            capture_range(client, range.clone(), &mut regions)?;
+        }
+
+        regions.sort_by_key(|r| r.base);
+
+        Ok(Self {
+            ranges: ranges.to_vec(),
+            regions,
+        })
+    }
+
+    /// Capture every committed and writable region of the current process.
+    pub fn capture_writable(client: &DebugClient) -> Result<Self> {
+        let ranges = client
+            .memory_regions()
+            .filter(|r| r.is_committed() && r.is_writable() && !r.is_guard())
+            .map(|r| r.base..r.end())
+            .collect::<Vec<_>>();
+
+        Self::capture(client, &ranges)
+    }
+
+    /// Capture the same ranges again.
+    pub fn recapture(&self, client: &DebugClient) -> Result<Self> {
+        Self::capture(client, &self.ranges)
+    }
+
+    /// Write the captured bytes back, so that the ranges hold what they did
+    /// when they were captured; only the bytes that changed since are written.
+    /// The number of bytes written is returned.
+    pub fn restore(&self, client: &DebugClient) -> Result<usize> {
+        let mut restored = 0;
+        for change in self.diff(&self.recapture(client)?) {
+            client
+                .write_virtual_exact(change.address, &change.old)
+                .with_context(|| format!("failed to restore {:#x}", change.address))?;
+
+            restored += change.old.len();
+        }
+
+        Ok(restored)
+    }
+
+    /// The chunks of memory that were captured.
+    pub fn regions(&self) -> &[SnapshotRegion] {
+        &self.regions
+    }
+
+    /// Read the captured bytes at `addr`, if they have been captured.
+    pub fn read(&self, addr: u64, len: usize) -> Option<&[u8]> {
+        let region = self
+            .regions
+            .iter()
+            .find(|r| (r.base..r.end()).contains(&addr))?;
+        let offset = usize::try_from(addr - region.base).ok()?;
+
+        region.data.get(offset..offset.checked_add(len)?)
+    }
+
+    /// Compare this snapshot against a `newer` one and return the ranges of
+    /// bytes that changed. Memory captured in only one of the snapshots is
+    /// ignored.
+    pub fn diff(&self, newer: &Snapshot) -> Vec<MemoryChange> {
+        let mut changes = Vec::new();
+        for old in &self.regions {
+            for new in &newer.regions {
+                let start = old.base.max(new.base);
+                let end = old.end().min(new.end());
+                if start >= end {
+                    continue;
+                }
+
+                let old_bytes = &old.data[(start - old.base) as usize..(end - old.base) as usize];
+                let new_bytes = &new.data[(start - new.base) as usize..(end - new.base) as usize];
+                changes.extend(diff_bytes(start, old_bytes, new_bytes));
+            }
+        }
+
+        changes.sort_by_key(|c| c.address);
+
+        changes
+    }
+}
+
+/// Read `range` into `regions`. If the range can't be read at once, it is read
+/// page by page and the unreadable pages are skipped.
+fn capture_range(
+    client: &DebugClient,
+    range: Range<u64>,
+    regions: &mut Vec<SnapshotRegion>,
+) -> Result<()> {
+    let len = usize::try_from(range.end.saturating_sub(range.start))?;
+    let mut data = vec![0; len];
+    if client.read_virtual_exact(range.start, &mut data).is_ok() {
+        regions.push(SnapshotRegion {
+            base: range.start,
+            data,
+        });
+
+        return Ok(());
+    }
+
+    let mut current: Option<SnapshotRegion> = None;
+    let mut addr = range.start;
+    while addr < range.end {
+        // Read up to the next page boundary.
+        let next = ((addr & !(PAGE_SIZE - 1)) + PAGE_SIZE).min(range.end);
+        let mut page = vec![0; usize::try_from(next - addr)?];
+        if client.read_virtual_exact(addr, &mut page).is_ok() {
+            match current.as_mut() {
+                Some(region) => region.data.extend_from_slice(&page),
+                None => {
+                    current = Some(SnapshotRegion {
+                        base: addr,
+                        data: page,
+                    })
+                }
+            }
+        } else if let Some(region) = current.take() {
+            regions.push(region);
+        }
+
+        addr = next;
+    }
+
+    regions.extend(current);
+
+    Ok(())
+}
+
+/// Compare `old` and `new` (which are both located at `base`) and coalesce the
+/// bytes that differ into [`MemoryChange`]s.
+pub fn diff_bytes(base: u64, old: &[u8], new: &[u8]) -> Vec<MemoryChange> {
+    let mut changes = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    for (idx, (o, n)) in old.iter().zip(new).enumerate() {
+        match (o != n, current) {
+            (true, None) => current = Some((idx, idx + 1)),
+            (true, Some((start, _))) => current = Some((start, idx + 1)),
+            (false, Some((start, end))) => {
+                changes.push(MemoryChange {
+                    address: base + start as u64,
+                    old: old[start..end].to_vec(),
+                    new: new[start..end].to_vec(),
+                });
+                current = None;
+            }
+            (false, None) => {}
+        }
+    }
+
+    if let Some((start, end)) = current {
+        changes.push(MemoryChange {
+            address: base + start as u64,
+            old: old[start..end].to_vec(),
+            new: new[start..end].to_vec(),
+        });
+    }
+
+    changes
+}
+
+/// The granularity at which a [`HexDump`] displays the memory.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexDumpWidth {
+    /// Display bytes, like `db`.
+    #[default]
+    Byte,
+    /// Display 16-bit words, like `dw`.
+    Word,
+    /// Display 32-bit double words, like `dd`.
+    Dword,
+    /// Display 64-bit quad words, like `dq`.
+    Qword,
+}
+
+impl HexDumpWidth {
+    /// The size in bytes of a single value.
+    pub fn size(&self) -> usize {
+        match self {
+            HexDumpWidth::Byte => 1,
+            HexDumpWidth::Word => 2,
+            HexDumpWidth::Dword => 4,
+            HexDumpWidth::Qword => 8,
+        }
+    }
+}
+
+/// A row of a [`HexDump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexDumpRow {
+    /// Address of the first byte of the row.
+    pub address: u64,
+    /// The bytes of the row.
+    pub bytes: Vec<u8>,
+}
+
+impl HexDumpRow {
+    /// The values of the row, read as little-endian integers of `width` bytes.
+    /// Trailing bytes that don't make up a full value are ignored.
+    pub fn values(&self, width: HexDumpWidth) -> Vec<u64> {
+        self.bytes
+            .chunks_exact(width.size())
+            .map(|c| {
+                let mut value = [0; 8];
+                value[..c.len()].copy_from_slice(c);
+
+                u64::from_le_bytes(value)
+            })
+            .collect()
+    }
+
+    /// The printable ASCII representation of the row; non printable bytes are
+    /// displayed as `.`.
+    pub fn ascii(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    }
+}
+
+/// A `db`/`dw`/`dd`/`dq`-style dump of memory. It can be used as structured
+/// data via [`HexDump::rows`], or displayed with [`Display`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexDump {
+    /// The granularity of the values.
+    width: HexDumpWidth,
+    /// The rows of the dump.
+    rows: Vec<HexDumpRow>,
+}
+
+impl HexDump {
+    /// The number of bytes displayed per row.
+    pub const ROW_SIZE: usize = 16;
+
+    /// Build a dump of `data` which is located at `address`.
+    pub fn new(address: u64, data: &[u8], width: HexDumpWidth) -> Self {
+        let rows = data
+            .chunks(Self::ROW_SIZE)
+            .enumerate()
+            .map(|(idx, bytes)| HexDumpRow {
+                address: address + (idx * Self::ROW_SIZE) as u64,
+                bytes: bytes.to_vec(),
+            })
+            .collect();
+
+        Self { width, rows }
+    }
+
+    /// Read `len` bytes of virtual memory at `address` and build a dump of it.
+    pub fn read(
+        client: &DebugClient,
+        address: u64,
+        len: usize,
+        width: HexDumpWidth,
+    ) -> Result<Self> {
+        let mut data = vec![0; len];
+        client.read_virtual_exact(address, &mut data)?;
+
+        Ok(Self::new(address, &data, width))
+    }
+
+    /// The rows of the dump.
+    pub fn rows(&self) -> &[HexDumpRow] {
+        &self.rows
+    }
+
+    /// The granularity of the values.
+    pub fn width(&self) -> HexDumpWidth {
+        self.width
+    }
+}
+
+impl Display for HexDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = self.width.size();
+        let values_per_row = Self::ROW_SIZE / size;
+        for (idx, row) in self.rows.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "{:08x}`{:08x} ", row.address >> 32, row.address as u32)?;
+            let values = row.values(self.width);
+            for n in 0..values_per_row {
+                // Like WinDbg, separate the two halves of a row of bytes with a `-`.
+                let separator = if self.width == HexDumpWidth::Byte && n == values_per_row / 2 {
+                    '-'
+                } else {
+                    ' '
+                };
+
+                match values.get(n) {
+                    Some(v) if size == 8 => {
+                        write!(f, "{separator}{:08x}`{:08x}", v >> 32, *v as u32)?
+                    }
+                    Some(v) => write!(f, "{separator}{v:0width$x}", width = size * 2)?,
+                    None if size == 8 => write!(f, " {:17}", "")?,
+                    None => write!(f, " {:width$}", "", width = size * 2)?,
+                }
+            }
+
+            write!(f, "  {}", row.ascii())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_bytes, find_masked, parse_masked_pattern, HexDump, HexDumpWidth};
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_masked_pattern("48 8b ?? 05 ?").unwrap(), vec![
+            Some(0x48),
+            Some(0x8b),
+            None,
+            Some(0x05),
+            None
+        ]);
+        assert!(parse_masked_pattern("").is_err());
+        assert!(parse_masked_pattern("48 zz").is_err());
+        assert!(parse_masked_pattern("488b").is_err());
+    }
+
+    #[test]
+    fn find() {
+        let haystack = [0x90, 0x48, 0x8b, 0x05, 0x48, 0x8b, 0xc1, 0x48];
+        let pattern = parse_masked_pattern("48 8b ??").unwrap();
+        assert_eq!(find_masked(&haystack, &pattern).collect::<Vec<_>>(), vec![
+            1, 4
+        ]);
+
+        let pattern = parse_masked_pattern("48").unwrap();
+        assert_eq!(find_masked(&haystack, &pattern).collect::<Vec<_>>(), vec![
+            1, 4, 7
+        ]);
+
+        let pattern = parse_masked_pattern("c1 48 8b").unwrap();
+        assert_eq!(find_masked(&haystack, &pattern).count(), 0);
+        assert_eq!(find_masked(&haystack, &[]).count(), 0);
+    }
+
+    #[test]
+    fn diff() {
+        let old = [0, 1, 2, 3, 4, 5, 6, 7];
+        let new = [0, 9, 9, 3, 4, 5, 6, 9];
+        let changes = diff_bytes(0x1000, &old, &new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].address, 0x1001);
+        assert_eq!(changes[0].old, vec![1, 2]);
+        assert_eq!(changes[0].new, vec![9, 9]);
+        assert_eq!(changes[1].address, 0x1007);
+        assert_eq!(changes[1].old, vec![7]);
+        assert_eq!(changes[1].new, vec![9]);
+        assert!(diff_bytes(0, &old, &old).is_empty());
+    }
+
+    #[test]
+    fn hexdump() {
+        let data = b"MZ\x90\x00\x03\x00\x00\x00\x04\x00\x00\x00\xff\xff\x00\x00hello";
+        let dump = HexDump::new(0x7ff6_1234_0000, data, HexDumpWidth::Byte);
+        assert_eq!(dump.rows().len(), 2);
+        assert_eq!(dump.rows()[1].address, 0x7ff6_1234_0010);
+        assert_eq!(dump.rows()[0].ascii(), "MZ..............");
+        assert_eq!(
+            dump.to_string(),
+            "00007ff6`12340000  4d 5a 90 00 03 00 00 00-04 00 00 00 ff ff 00 00  MZ..............\n\
+             00007ff6`12340010  68 65 6c 6c 6f                                   hello"
+        );
+
+        let dump = HexDump::new(0x7ff6_1234_0000, data, HexDumpWidth::Qword);
+        assert_eq!(dump.rows()[0].values(HexDumpWidth::Qword), vec![
+            0x00000003_00905a4d,
+            0x0000ffff_00000004
+        ]);
+        assert_eq!(
+            dump.to_string().lines().next().unwrap(),
+            "00007ff6`12340000  00000003`00905a4d 0000ffff`00000004  MZ.............."
+        );
+
+        let dump = HexDump::new(0, &data[..16], HexDumpWidth::Dword);
+        assert_eq!(
+            dump.to_string(),
+            "00000000`00000000  00905a4d 00000003 00000004 0000ffff  MZ.............."
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! This contains the plumbing to receive the text output by the engine, like
+//! the output of the commands run with [`DebugClient::exec`].
+//!
+//! [`DebugClient::exec`]: crate::client::DebugClient::exec
+use std::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+
+use bitflags::bitflags;
+use windows::core::{implement, PCWSTR};
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    IDebugOutputCallbacksWide, IDebugOutputCallbacksWide_Impl, DEBUG_LOG_APPEND, DEBUG_LOG_DML,
+    DEBUG_LOG_UNICODE, DEBUG_OUTPUT_DEBUGGEE, DEBUG_OUTPUT_DEBUGGEE_PROMPT, DEBUG_OUTPUT_ERROR,
+    DEBUG_OUTPUT_EXTENSION_WARNING, DEBUG_OUTPUT_NORMAL, DEBUG_OUTPUT_PROMPT,
+    DEBUG_OUTPUT_PROMPT_REGISTERS, DEBUG_OUTPUT_STATUS, DEBUG_OUTPUT_SYMBOLS, DEBUG_OUTPUT_VERBOSE,
+    DEBUG_OUTPUT_WARNING, DEBUG_OUTPUT_XML,
+};
+
+bitflags! {
+    /// The kinds of output produced by the engine.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OutputMask: u32 {
+        /// Normal output.
+        const NORMAL = DEBUG_OUTPUT_NORMAL;
+        /// Error output.
+        const ERROR = DEBUG_OUTPUT_ERROR;
+        /// Warnings.
+        const WARNING = DEBUG_OUTPUT_WARNING;
+        /// Additional output.
+        const VERBOSE = DEBUG_OUTPUT_VERBOSE;
+        /// The prompt.
+        const PROMPT = DEBUG_OUTPUT_PROMPT;
+        /// The registers dumped before the prompt.
+        const PROMPT_REGISTERS = DEBUG_OUTPUT_PROMPT_REGISTERS;
+        /// Warnings specific to extensions.
+        const EXTENSION_WARNING = DEBUG_OUTPUT_EXTENSION_WARNING;
+        /// The debug output of the target.
+        const DEBUGGEE = DEBUG_OUTPUT_DEBUGGEE;
+        /// The prompt of the target, when it reads input.
+        const DEBUGGEE_PROMPT = DEBUG_OUTPUT_DEBUGGEE_PROMPT;
+        /// Symbol messages.
+        const SYMBOLS = DEBUG_OUTPUT_SYMBOLS;
+        /// Status messages.
+        const STATUS = DEBUG_OUTPUT_STATUS;
+        /// XML output.
+        const XML = DEBUG_OUTPUT_XML;
+    }
+}
+
+bitflags! {
+    /// How the engine writes the log file.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct LogFileFlags: u32 {
+        /// Append to the file instead of overwriting it.
+        const APPEND = DEBUG_LOG_APPEND;
+        /// Write the log as UTF-16.
+        const UNICODE = DEBUG_LOG_UNICODE;
+        /// Keep the DML markup in the log.
+        const DML = DEBUG_LOG_DML;
+    }
+}
+
+pub trait OutputCallbacks {
+    /// Text has been output by the engine.
+    fn output(&self, mask: OutputMask, text: &str);
+}
+
+impl<T: OutputCallbacks + ?Sized> OutputCallbacks for Rc<T> {
+    fn output(&self, mask: OutputMask, text: &str) {
+        (**self).output(mask, text)
+    }
+}
+
+/// Output callbacks that accumulate the text they receive.
+#[derive(Default, Clone)]
+pub(crate) struct OutputCollector(Rc<RefCell<String>>);
+
+impl OutputCollector {
+    /// Take the text collected so far.
+    pub(crate) fn take(&self) -> String {
+        self.0.take()
+    }
+}
+
+impl OutputCallbacks for OutputCollector {
+    fn output(&self, _mask: OutputMask, text: &str) {
+        self.0.borrow_mut().push_str(text);
+    }
+}
+
+#[implement(IDebugOutputCallbacksWide)]
+pub(crate) struct DbgOutputCallbacks {
+    callbacks: Box<dyn OutputCallbacks>,
+}
+
+impl DbgOutputCallbacks {
+    pub(crate) fn new(callbacks: Box<dyn OutputCallbacks + 'static>) -> Self {
+        Self { callbacks }
+    }
+}
+
+impl IDebugOutputCallbacksWide_Impl for DbgOutputCallbacks {
+    fn Output(&self, mask: u32, text: &PCWSTR) -> windows::core::Result<()> {
+        if text.is_null() {
+            return Ok(());
+        }
+
+        let text = String::from_utf16_lossy(unsafe { text.as_wide() });
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            self.callbacks
+                .output(OutputMask::from_bits_retain(mask), &text)
+        }));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+//! This contains a parser for the headers of PE images mapped in the memory of
+//! the target, and helpers to dump them into loadable files.
+use std::path::Path;
+use std::{fs, mem};
+
+use anyhow::{bail, Context, Result};
+use windows::Win32::System::Diagnostics::Debug::{
+    IMAGE_DATA_DIRECTORY, IMAGE_DIRECTORY_ENTRY, IMAGE_FILE_HEADER, IMAGE_NT_HEADERS32,
+    IMAGE_NT_HEADERS64, IMAGE_NT_OPTIONAL_HDR64_MAGIC, IMAGE_SECTION_HEADER,
+};
+use windows::Win32::System::SystemServices::{
+    IMAGE_DOS_HEADER, IMAGE_DOS_SIGNATURE, IMAGE_NT_SIGNATURE,
+};
+
+use crate::client::DebugClient;
+
+/// The NT headers of a PE image, which come in two flavors.
+#[derive(Clone, Copy)]
+pub enum NtHeaders {
+    /// The headers of a 32-bit (PE32) image.
+    Nt32(IMAGE_NT_HEADERS32),
+    /// The headers of a 64-bit (PE32+) image.
+    Nt64(IMAGE_NT_HEADERS64),
+}
+
+impl NtHeaders {
+    /// Get the file header.
+    pub fn file_header(&self) -> &IMAGE_FILE_HEADER {
+        match self {
+            Self::Nt32(nt) => &nt.FileHeader,
+            Self::Nt64(nt) => &nt.FileHeader,
+        }
+    }
+
+    /// Get the relative virtual address of the entry point.
+    pub fn entry_point_rva(&self) -> u32 {
+        match self {
+            Self::Nt32(nt) => nt.OptionalHeader.AddressOfEntryPoint,
+            Self::Nt64(nt) => nt.OptionalHeader.AddressOfEntryPoint,
+        }
+    }
+
+    /// Get the size of the image once mapped.
+    pub fn size_of_image(&self) -> u32 {
+        match self {
+            Self::Nt32(nt) => nt.OptionalHeader.SizeOfImage,
+            Self::Nt64(nt) => nt.OptionalHeader.SizeOfImage,
+        }
+    }
+
+    /// Get the data directories.
+    pub fn data_directories(&self) -> &[IMAGE_DATA_DIRECTORY] {
+        match self {
+            Self::Nt32(nt) => &nt.OptionalHeader.DataDirectory,
+            Self::Nt64(nt) => &nt.OptionalHeader.DataDirectory,
+        }
+    }
+}
+
+/// The headers of a PE image mapped in the memory of the target.
+#[derive(Clone)]
+pub struct PeImage {
+    /// The base address of the image.
+    base: u64,
+    /// The DOS header.
+    dos_header: IMAGE_DOS_HEADER,
+    /// The NT headers.
+    nt_headers: NtHeaders,
+    /// The section table.
+    sections: Vec<IMAGE_SECTION_HEADER>,
+}
+
+impl PeImage {
+    /// Parse the headers of the PE image mapped at `base`.
+    pub fn parse(client: &DebugClient, base: u64) -> Result<Self> {
+        let dos_header = client.read_virtual_pod::<IMAGE_DOS_HEADER>(base)?;
+        if dos_header.e_magic != IMAGE_DOS_SIGNATURE {
+            bail!("wrong DOS signature for the image at {base:#x}");
+        }
+
+        // The 32-bit and 64-bit NT headers share their layout up to the
+        // optional header's magic, so peek at it first.
+        let nt_addr = base + u64::try_from(dos_header.e_lfanew)?;
+        let nt32 = client.read_virtual_pod::<IMAGE_NT_HEADERS32>(nt_addr)?;
+        if nt32.Signature != IMAGE_NT_SIGNATURE {
+            bail!("wrong NT signature for the image at {base:#x}");
+        }
+
+        let nt_headers = if nt32.OptionalHeader.Magic == IMAGE_NT_OPTIONAL_HDR64_MAGIC {
+            NtHeaders::Nt64(client.read_virtual_pod(nt_addr)?)
+        } else {
+            NtHeaders::Nt32(nt32)
+        };
+
+        // The section table follows the optional header, whose size is in the
+        // file header.
+        let file_header = nt_headers.file_header();
+        let sections_addr = nt_addr
+            + mem::size_of::<u32>() as u64
+            + mem::size_of::<IMAGE_FILE_HEADER>() as u64
+            + u64::from(file_header.SizeOfOptionalHeader);
+        let sections = (0..u64::from(file_header.NumberOfSections))
+            .map(|idx| {
+                let addr = sections_addr + idx * mem::size_of::<IMAGE_SECTION_HEADER>() as u64;
+                client
+                    .read_virtual_pod::<IMAGE_SECTION_HEADER>(addr)
+                    .with_context(|| format!("failed to read the section header at {addr:#x}"))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            base,
+            dos_header,
+            nt_headers,
+            sections,
+        })
+    }
+
+    /// Get the base address of the image.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Get the DOS header.
+    pub fn dos_header(&self) -> &IMAGE_DOS_HEADER {
+        &self.dos_header
+    }
+
+    /// Get the NT headers.
+    pub fn nt_headers(&self) -> &NtHeaders {
+        &self.nt_headers
+    }
+
+    /// Is the image a 64-bit (PE32+) image?
+    pub fn is_64bit(&self) -> bool {
+        matches!(self.nt_headers, NtHeaders::Nt64(_))
+    }
+
+    /// Get the address of the entry point.
+    pub fn entry_point(&self) -> u64 {
+        self.rva_to_va(self.nt_headers.entry_point_rva())
+    }
+
+    /// Get the size of the image once mapped.
+    pub fn size_of_image(&self) -> u32 {
+        self.nt_headers.size_of_image()
+    }
+
+    /// Get the data directory `entry` (`IMAGE_DIRECTORY_ENTRY_*`); `None` is
+    /// returned if the image doesn't have it.
+    pub fn data_directory(&self, entry: IMAGE_DIRECTORY_ENTRY) -> Option<IMAGE_DATA_DIRECTORY> {
+        self.nt_headers
+            .data_directories()
+            .get(usize::from(entry.0))
+            .copied()
+            .filter(|directory| directory.VirtualAddress != 0)
+    }
+
+    /// Get the section table.
+    pub fn sections(&self) -> &[IMAGE_SECTION_HEADER] {
+        &self.sections
+    }
+
+    /// Get the section named `name` (like `.text`).
+    pub fn section_by_name(&self, name: &str) -> Option<&IMAGE_SECTION_HEADER> {
+        self.sections
+            .iter()
+            .find(|section| section_name(section) == name)
+    }
+
+    /// Get the section containing the relative virtual address `rva`.
+    pub fn section_by_rva(&self, rva: u32) -> Option<&IMAGE_SECTION_HEADER> {
+        self.sections.iter().find(|section| {
+            let size = unsafe { section.Misc.VirtualSize };
+            (section.VirtualAddress..section.VirtualAddress.saturating_add(size)).contains(&rva)
+        })
+    }
+
+    /// Convert a relative virtual address into a virtual address.
+    pub fn rva_to_va(&self, rva: u32) -> u64 {
+        self.base + u64::from(rva)
+    }
+
+    /// Convert a virtual address into a relative virtual address; `None` is
+    /// returned if `va` isn't inside the image.
+    pub fn va_to_rva(&self, va: u64) -> Option<u32> {
+        let rva = u32::try_from(va.checked_sub(self.base)?).ok()?;
+
+        (rva < self.size_of_image()).then_some(rva)
+    }
+}
+
+/// Get the name of a section; it is stored in a nul-padded eight bytes
+/// buffer.
+pub fn section_name(section: &IMAGE_SECTION_HEADER) -> String {
+    let len = section
+        .Name
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(section.Name.len());
+
+    String::from_utf8_lossy(&section.Name[..len]).into_owned()
+}
+
+/// The offset of `e_lfanew` in the DOS header.
+const E_LFANEW_OFFSET: usize = 0x3c;
+
+fn read_u16(image: &[u8], offset: usize) -> Result<u16> {
+    let bytes = image
+        .get(offset..offset + 2)
+        .with_context(|| format!("the image is too small to read at {offset:#x}"))?;
+
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_u32(image: &[u8], offset: usize) -> Result<u32> {
+    let bytes = image
+        .get(offset..offset + 4)
+        .with_context(|| format!("the image is too small to read at {offset:#x}"))?;
+
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+fn write_bytes(image: &mut [u8], offset: usize, bytes: &[u8]) -> Result<()> {
+    image
+        .get_mut(offset..offset + bytes.len())
+        .with_context(|| format!("the image is too small to write at {offset:#x}"))?
+        .copy_from_slice(bytes);
+
+    Ok(())
+}
+
+/// Fix the headers of an image dumped from memory, as it is mapped, so that
+/// it can be loaded from disk: the raw offset and size of each section are
+/// set to its virtual ones. If `image_base` is specified, it replaces the
+/// image base of the optional header; as the memory was relocated for the
+/// address the image was mapped at, this should be that address.
+pub fn rebuild_dumped_image(image: &mut [u8], image_base: Option<u64>) -> Result<()> {
+    if read_u16(image, 0)? != IMAGE_DOS_SIGNATURE {
+        bail!("wrong DOS signature");
+    }
+
+    let nt = usize::try_from(read_u32(image, E_LFANEW_OFFSET)?)?;
+    if read_u32(image, nt)? != IMAGE_NT_SIGNATURE {
+        bail!("wrong NT signature");
+    }
+
+    let file_header = nt + mem::size_of::<u32>();
+    let number_of_sections = usize::from(read_u16(image, file_header + 2)?);
+    let size_of_optional_header = usize::from(read_u16(image, file_header + 16)?);
+    let optional_header = file_header + mem::size_of::<IMAGE_FILE_HEADER>();
+    let is_64bit = read_u16(image, optional_header)? == IMAGE_NT_OPTIONAL_HDR64_MAGIC.0;
+
+    if let Some(image_base) = image_base {
+        if is_64bit {
+            write_bytes(image, optional_header + 24, &image_base.to_le_bytes())?;
+        } else {
+            let image_base = u32::try_from(image_base)
+                .with_context(|| format!("{image_base:#x} doesn't fit a 32-bit image"))?;
+            write_bytes(image, optional_header + 28, &image_base.to_le_bytes())?;
+        }
+    }
+
+    let section_alignment = read_u32(image, optional_header + 32)?.max(1);
+    let size_of_image = u32::try_from(image.len())?;
+    let sections = optional_header + size_of_optional_header;
+    for idx in 0..number_of_sections {
+        let section = sections + idx * mem::size_of::<IMAGE_SECTION_HEADER>();
+        let virtual_size = read_u32(image, section + 8)?;
+        let virtual_address = read_u32(image, section + 12)?;
+
+        // The section spans up to the next section alignment in memory, but
+        // can't go past the end of the image.
+        let size = (virtual_size.saturating_add(section_alignment - 1) / section_alignment
+            * section_alignment)
+            .min(size_of_image.saturating_sub(virtual_address));
+
+        write_bytes(image, section + 16, &size.to_le_bytes())?;
+        write_bytes(image, section + 20, &virtual_address.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Dump the PE image mapped at `base` into a loadable file at `path`; see
+/// [`rebuild_dumped_image`]. If `rebase` is set, the image base of the file is
+/// set to `base`.
+pub fn dump_module(
+    client: &DebugClient,
+    base: u64,
+    path: impl AsRef<Path>,
+    rebase: bool,
+) -> Result<()> {
+    let path = path.as_ref();
+    let pe = PeImage::parse(client, base)?;
+    let mut image = vec![0; usize::try_from(pe.size_of_image())?];
+    client.read_virtual_lossy(base, &mut image);
+    rebuild_dumped_image(&mut image, rebase.then_some(base))?;
+
+    fs::write(path, image).with_context(|| format!("failed to write {path:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuild() {
+        // A PE32+ image with a single section, whose headers are laid out like
+        // the ones of a file.
+        let mut image = vec![0; 0x3000];
+        image[..2].copy_from_slice(b"MZ");
+        image[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        image[0x80..0x84].copy_from_slice(b"PE\0\0");
+        image[0x86..0x88].copy_from_slice(&1u16.to_le_bytes());
+        image[0x94..0x96].copy_from_slice(&0xf0u16.to_le_bytes());
+        image[0x98..0x9a].copy_from_slice(&0x20bu16.to_le_bytes());
+        image[0xb0..0xb8].copy_from_slice(&0x1_4000_0000u64.to_le_bytes());
+        image[0xb8..0xbc].copy_from_slice(&0x1000u32.to_le_bytes());
+        let section = 0x98 + 0xf0;
+        image[section + 8..section + 12].copy_from_slice(&0x1234u32.to_le_bytes());
+        image[section + 12..section + 16].copy_from_slice(&0x1000u32.to_le_bytes());
+        image[section + 16..section + 20].copy_from_slice(&0x1400u32.to_le_bytes());
+        image[section + 20..section + 24].copy_from_slice(&0x400u32.to_le_bytes());
+
+        rebuild_dumped_image(&mut image, Some(0x7ff6_0000_0000)).unwrap();
+        assert_eq!(read_u32(&image, section + 16).unwrap(), 0x2000);
+        assert_eq!(read_u32(&image, section + 20).unwrap(), 0x1000);
+        assert_eq!(image[0xb0..0xb8], 0x7ff6_0000_0000u64.to_le_bytes());
+
+        // A 32-bit image base can't be above 4GB.
+        image[0x98..0x9a].copy_from_slice(&0x10bu16.to_le_bytes());
+        assert!(rebuild_dumped_image(&mut image, Some(0x7ff6_0000_0000)).is_err());
+        image[0x3c..0x40].copy_from_slice(&0x4000u32.to_le_bytes());
+        assert!(rebuild_dumped_image(&mut image, None).is_err());
+    }
+}
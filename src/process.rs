@@ -0,0 +1,293 @@
+//! This contains helpers to read the parameters of the current process (its
+//! command line, current directory and environment) from its PEB, and the
+//! options used to create, attach to and release processes.
+use anyhow::{bail, Context, Result};
+use bitflags::bitflags;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    DEBUG_ATTACH_EXISTING, DEBUG_ATTACH_INVASIVE_NO_INITIAL_BREAK,
+    DEBUG_ATTACH_INVASIVE_RESUME_PROCESS, DEBUG_ATTACH_NONINVASIVE,
+    DEBUG_ATTACH_NONINVASIVE_ALLOW_PARTIAL, DEBUG_ATTACH_NONINVASIVE_NO_SUSPEND,
+    DEBUG_ECREATE_PROCESS_INHERIT_HANDLES, DEBUG_ECREATE_PROCESS_USE_IMPLICIT_COMMAND_LINE,
+    DEBUG_END_ACTIVE_DETACH, DEBUG_END_ACTIVE_TERMINATE, DEBUG_END_DISCONNECT, DEBUG_END_PASSIVE,
+    DEBUG_END_REENTRANT,
+};
+use windows::Win32::System::Threading::{
+    CREATE_NEW_CONSOLE, CREATE_NEW_PROCESS_GROUP, CREATE_UNICODE_ENVIRONMENT,
+    DEBUG_ONLY_THIS_PROCESS, DEBUG_PROCESS,
+};
+
+use crate::client::DebugClient;
+
+/// The largest environment block read by [`process_parameters`].
+const MAX_ENVIRONMENT_SIZE: u64 = 0x10_00_00;
+
+/// The parameters of a process, as stored in its
+/// `_RTL_USER_PROCESS_PARAMETERS` structure.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProcessParameters {
+    /// The path of the executable.
+    pub image_path: String,
+    /// The command line.
+    pub command_line: String,
+    /// The current directory.
+    pub current_directory: String,
+    /// The environment variables, in the order they are stored.
+    pub environment: Vec<(String, String)>,
+}
+
+bitflags! {
+    /// The flags passed to `CreateProcess` when the engine creates a process.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct CreateFlags: u32 {
+        /// Debug the process and the processes it creates.
+        const DEBUG_PROCESS = DEBUG_PROCESS.0;
+        /// Debug the process but not the processes it creates.
+        const DEBUG_ONLY_THIS_PROCESS = DEBUG_ONLY_THIS_PROCESS.0;
+        /// Give the process its own console.
+        const NEW_CONSOLE = CREATE_NEW_CONSOLE.0;
+        /// Create the process in a new process group.
+        const NEW_PROCESS_GROUP = CREATE_NEW_PROCESS_GROUP.0;
+        /// Don't use the debug heap (`DEBUG_CREATE_PROCESS_NO_DEBUG_HEAP`).
+        const NO_DEBUG_HEAP = CREATE_UNICODE_ENVIRONMENT.0;
+    }
+}
+
+bitflags! {
+    /// How the engine creates a process.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EngineCreateFlags: u32 {
+        /// The process inherits the handles of the debugger.
+        const INHERIT_HANDLES = DEBUG_ECREATE_PROCESS_INHERIT_HANDLES;
+        /// Use the implicit command line of the engine instead of the one
+        /// specified.
+        const USE_IMPLICIT_COMMAND_LINE = DEBUG_ECREATE_PROCESS_USE_IMPLICIT_COMMAND_LINE;
+    }
+}
+
+bitflags! {
+    /// How the engine attaches to a process; see
+    /// [`DebugClient::attach_process`]. The default is an invasive attach.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct AttachFlags: u32 {
+        /// Only suspend the process and read its state, without debugging it.
+        const NONINVASIVE = DEBUG_ATTACH_NONINVASIVE;
+        /// Reattach to a process that is already being debugged, like after
+        /// the previous debugger exited without detaching.
+        const EXISTING = DEBUG_ATTACH_EXISTING;
+        /// Don't suspend the threads of the process during a noninvasive
+        /// attach.
+        const NONINVASIVE_NO_SUSPEND = DEBUG_ATTACH_NONINVASIVE_NO_SUSPEND;
+        /// Don't break into the process once attached.
+        const INVASIVE_NO_INITIAL_BREAK = DEBUG_ATTACH_INVASIVE_NO_INITIAL_BREAK;
+        /// Resume the threads of the process once attached.
+        const INVASIVE_RESUME_PROCESS = DEBUG_ATTACH_INVASIVE_RESUME_PROCESS;
+        /// Attach noninvasively even if some of the state can't be read.
+        const NONINVASIVE_ALLOW_PARTIAL = DEBUG_ATTACH_NONINVASIVE_ALLOW_PARTIAL;
+    }
+}
+
+/// How [`DebugClient::end_session`] ends the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EndSession {
+    /// End the session without touching the targets; a live process is
+    /// terminated when the engine exits.
+    Passive,
+    /// Terminate the processes being debugged.
+    ActiveTerminate,
+    /// Detach from the processes being debugged, letting them run.
+    ActiveDetach,
+    /// End the session from another thread than the one using the engine,
+    /// like when it is stuck; only the minimum is cleaned up.
+    Reentrant,
+    /// Disconnect this client from a remote debugging server without ending
+    /// the session.
+    Disconnect,
+}
+
+impl EndSession {
+    pub(crate) fn as_flags(&self) -> u32 {
+        match self {
+            Self::Passive => DEBUG_END_PASSIVE,
+            Self::ActiveTerminate => DEBUG_END_ACTIVE_TERMINATE,
+            Self::ActiveDetach => DEBUG_END_ACTIVE_DETACH,
+            Self::Reentrant => DEBUG_END_REENTRANT,
+            Self::Disconnect => DEBUG_END_DISCONNECT,
+        }
+    }
+}
+
+/// The options used by [`DebugClient::create_process`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateProcessOptions {
+    /// The flags passed to `CreateProcess`.
+    pub create_flags: CreateFlags,
+    /// How the engine creates the process.
+    pub engine_flags: EngineCreateFlags,
+    /// The current directory of the process; the one of the debugger is used
+    /// if `None`.
+    pub initial_directory: Option<String>,
+    /// The environment variables of the process; the ones of the debugger are
+    /// used if `None`.
+    pub environment: Option<Vec<(String, String)>>,
+}
+
+impl Default for CreateProcessOptions {
+    fn default() -> Self {
+        Self {
+            create_flags: CreateFlags::DEBUG_ONLY_THIS_PROCESS,
+            engine_flags: EngineCreateFlags::empty(),
+            initial_directory: None,
+            environment: None,
+        }
+    }
+}
+
+/// Build an environment block made of `NAME=VALUE` strings, each terminated
+/// by a NULL character, and ending with an empty string.
+pub(crate) fn environment_block(environment: &[(String, String)]) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, value) in environment {
+        block.extend_from_slice(name.as_bytes());
+        block.push(b'=');
+        block.extend_from_slice(value.as_bytes());
+        block.push(0);
+    }
+
+    // N.B: An empty block still needs two terminators.
+    if block.is_empty() {
+        block.push(0);
+    }
+
+    block.push(0);
+
+    block
+}
+
+/// The offsets of the fields of the PEB and of `_RTL_USER_PROCESS_PARAMETERS`
+/// used by [`process_parameters`].
+struct Layout {
+    process_parameters: u64,
+    current_directory: u64,
+    image_path: u64,
+    command_line: u64,
+    environment: u64,
+    environment_size: u64,
+}
+
+const LAYOUT_64: Layout = Layout {
+    process_parameters: 0x20,
+    current_directory: 0x38,
+    image_path: 0x60,
+    command_line: 0x70,
+    environment: 0x80,
+    environment_size: 0x3f0,
+};
+
+const LAYOUT_32: Layout = Layout {
+    process_parameters: 0x10,
+    current_directory: 0x24,
+    image_path: 0x38,
+    command_line: 0x40,
+    environment: 0x48,
+    environment_size: 0x290,
+};
+
+/// Read the parameters of the current process. The layout follows the
+/// pointer size of the effective processor, so the 32-bit parameters of a
+/// WOW64 process are read when the debugger is in 32-bit mode (`.effmach
+/// x86`), and the native ones otherwise.
+pub fn process_parameters(client: &DebugClient) -> Result<ProcessParameters> {
+    let is_64bit = client.is_pointer_64bit()?;
+    let layout = if is_64bit { &LAYOUT_64 } else { &LAYOUT_32 };
+    let read_ptr = |addr: u64| -> Result<u64> {
+        if is_64bit {
+            client.read_virtual_pod::<u64>(addr)
+        } else {
+            client.read_virtual_pod::<u32>(addr).map(u64::from)
+        }
+    };
+
+    let peb = client.peb()?;
+    let params = read_ptr(peb + layout.process_parameters)
+        .context("failed to read PEB.ProcessParameters")?;
+    if params == 0 {
+        bail!("the process has no parameters yet");
+    }
+
+    let environment_addr = read_ptr(params + layout.environment)
+        .context("failed to read ProcessParameters.Environment")?;
+    let environment_size = read_ptr(params + layout.environment_size)
+        .context("failed to read ProcessParameters.EnvironmentSize")?;
+    let mut environment = vec![0; environment_size.min(MAX_ENVIRONMENT_SIZE) as usize];
+    client
+        .read_virtual_exact(environment_addr, &mut environment)
+        .context("failed to read the environment block")?;
+
+    let environment = environment
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect::<Vec<_>>();
+
+    Ok(ProcessParameters {
+        image_path: client.read_unicode_string(params + layout.image_path)?,
+        command_line: client.read_unicode_string(params + layout.command_line)?,
+        current_directory: client.read_unicode_string(params + layout.current_directory)?,
+        environment: parse_environment(&environment),
+    })
+}
+
+/// Parse an environment block made of `NAME=VALUE` strings, each terminated
+/// by a NULL character, and ending with an empty string.
+fn parse_environment(block: &[u16]) -> Vec<(String, String)> {
+    block
+        .split(|&c| c == 0)
+        .take_while(|entry| !entry.is_empty())
+        .map(|entry| {
+            let entry = String::from_utf16_lossy(entry);
+            // N.B: The name of the hidden variables tracking the current directory
+            // of each drive starts with `=`, like `=C:=C:\Windows`.
+            match entry.char_indices().skip(1).find(|&(_, c)| c == '=') {
+                Some((idx, _)) => (entry[..idx].to_string(), entry[idx + 1..].to_string()),
+                None => (entry, String::new()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment() {
+        let block = "=C:=C:\\Windows\0PATH=C:\\bin;D:\\bin\0EMPTY=\0BROKEN\0\0garbage"
+            .encode_utf16()
+            .collect::<Vec<_>>();
+
+        assert_eq!(parse_environment(&block), vec![
+            ("=C:".to_string(), "C:\\Windows".to_string()),
+            ("PATH".to_string(), "C:\\bin;D:\\bin".to_string()),
+            ("EMPTY".to_string(), String::new()),
+            ("BROKEN".to_string(), String::new()),
+        ]);
+        assert!(parse_environment(&[]).is_empty());
+    }
+
+    #[test]
+    fn block() {
+        let environment = vec![
+            ("=C:".to_string(), "C:\\Windows".to_string()),
+            ("PATH".to_string(), "C:\\bin".to_string()),
+        ];
+
+        assert_eq!(
+            environment_block(&environment),
+            b"=C:=C:\\Windows\0PATH=C:\\bin\0\0"
+        );
+        assert_eq!(environment_block(&[]), b"\0\0");
+    }
+}
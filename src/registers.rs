@@ -0,0 +1,450 @@
+//! This contains types describing the registers of the target.
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use bitflags::bitflags;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    DEBUG_REGISTER_DESCRIPTION, DEBUG_REGISTER_SUB_REGISTER, DEBUG_STACK_FRAME, DEBUG_VALUE,
+};
+
+use crate::client::{u128_from_debugvalue, u64_from_debugvalue};
+
+/// Describes how a sub-register (like `eax`) maps onto its master register
+/// (like `rax`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubRegister {
+    /// The index of the master register.
+    pub master: u32,
+    /// The size of the sub-register in bits.
+    pub length: u32,
+    /// The mask applied to the master register's value, after it has been
+    /// shifted.
+    pub mask: u64,
+    /// The number of bits the master register's value is shifted right by.
+    pub shift: u32,
+}
+
+/// A register as described by the debugger engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterDescription {
+    /// The index of the register.
+    pub index: u32,
+    /// The name of the register.
+    pub name: String,
+    /// The type of the value held by the register (`DEBUG_VALUE_*`).
+    pub ty: u32,
+    /// If the register is a sub-register, how it maps onto its master
+    /// register.
+    pub sub_register: Option<SubRegister>,
+}
+
+impl RegisterDescription {
+    pub(crate) fn new(index: u32, name: String, desc: &DEBUG_REGISTER_DESCRIPTION) -> Self {
+        let sub_register = (desc.Flags & DEBUG_REGISTER_SUB_REGISTER != 0).then_some(SubRegister {
+            master: desc.SubregMaster,
+            length: desc.SubregLength,
+            mask: desc.SubregMask,
+            shift: desc.SubregShift,
+        });
+
+        Self {
+            index,
+            name,
+            ty: desc.Type,
+            sub_register,
+        }
+    }
+
+    /// Is this register a sub-register of another one?
+    pub fn is_sub_register(&self) -> bool {
+        self.sub_register.is_some()
+    }
+}
+
+/// A cache of register indices keyed by register name. The indices depend on
+/// the effective processor, so the cache is flushed when it changes.
+#[derive(Default, Debug)]
+pub(crate) struct RegisterIndexCache {
+    /// The effective processor type the indices were resolved for.
+    processor_type: u32,
+    /// The register indices keyed by their names.
+    indices: HashMap<String, u32>,
+}
+
+impl RegisterIndexCache {
+    /// Flush the cache if the effective processor type has changed.
+    pub(crate) fn sync(&mut self, processor_type: u32) {
+        if self.processor_type != processor_type {
+            self.processor_type = processor_type;
+            self.indices.clear();
+        }
+    }
+
+    /// Get the cached index of the register `name`.
+    pub(crate) fn get(&self, name: &str) -> Option<u32> {
+        self.indices.get(name).copied()
+    }
+
+    /// Cache the `index` of the register `name`.
+    pub(crate) fn insert(&mut self, name: &str, index: u32) {
+        self.indices.insert(name.to_owned(), index);
+    }
+}
+
+/// The names of the registers making up a [`X64Context`], in the order they
+/// are read by [`X64Context::from_values`].
+pub const X64_CONTEXT_REGISTERS: [&str; 40] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip", "efl", "cs", "ds", "es", "fs", "gs", "ss", "xmm0", "xmm1", "xmm2", "xmm3",
+    "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13", "xmm14",
+    "xmm15",
+];
+
+/// The user-visible CPU context of an Intel x64 thread.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct X64Context {
+    pub rax: u64,
+    pub rbx: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rbp: u64,
+    pub rsp: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+    pub rip: u64,
+    pub rflags: u64,
+    pub cs: u16,
+    pub ds: u16,
+    pub es: u16,
+    pub fs: u16,
+    pub gs: u16,
+    pub ss: u16,
+    pub xmm: [u128; 16],
+}
+
+impl X64Context {
+    /// Build a [`X64Context`] off the values of the registers listed in
+    /// [`X64_CONTEXT_REGISTERS`].
+    pub fn from_values(values: &[DEBUG_VALUE]) -> Result<Self> {
+        if values.len() != X64_CONTEXT_REGISTERS.len() {
+            bail!(
+                "expected {} register values, but got {}",
+                X64_CONTEXT_REGISTERS.len(),
+                values.len()
+            );
+        }
+
+        let gprs = values[..24]
+            .iter()
+            .copied()
+            .map(u64_from_debugvalue)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut xmm = [0; 16];
+        for (xmm, value) in xmm.iter_mut().zip(&values[24..]) {
+            *xmm = u128_from_debugvalue(*value)?;
+        }
+
+        Ok(Self {
+            rax: gprs[0],
+            rbx: gprs[1],
+            rcx: gprs[2],
+            rdx: gprs[3],
+            rsi: gprs[4],
+            rdi: gprs[5],
+            rbp: gprs[6],
+            rsp: gprs[7],
+            r8: gprs[8],
+            r9: gprs[9],
+            r10: gprs[10],
+            r11: gprs[11],
+            r12: gprs[12],
+            r13: gprs[13],
+            r14: gprs[14],
+            r15: gprs[15],
+            rip: gprs[16],
+            rflags: gprs[17],
+            cs: gprs[18] as u16,
+            ds: gprs[19] as u16,
+            es: gprs[20] as u16,
+            fs: gprs[21] as u16,
+            gs: gprs[22] as u16,
+            ss: gprs[23] as u16,
+            xmm,
+        })
+    }
+}
+
+/// The size of the Intel x64 `CONTEXT` structure.
+pub const X64_CONTEXT_SIZE: usize = 0x4_d0;
+
+/// Read a little-endian [`u64`] at `offset` in `raw`.
+fn u64_at(raw: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(raw[offset..offset + 8].try_into().unwrap())
+}
+
+/// Read a little-endian [`u16`] at `offset` in `raw`.
+fn u16_at(raw: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(raw[offset..offset + 2].try_into().unwrap())
+}
+
+impl X64Context {
+    /// Build a [`X64Context`] off a raw Intel x64 `CONTEXT` structure, like the
+    /// ones filled by `GetContextStackTrace`.
+    pub fn from_context_bytes(raw: &[u8]) -> Result<Self> {
+        if raw.len() < X64_CONTEXT_SIZE {
+            bail!(
+                "expected at least {X64_CONTEXT_SIZE:#x} bytes of CONTEXT, but got {:#x}",
+                raw.len()
+            );
+        }
+
+        // The XMM registers live in the `XMM_SAVE_AREA32` at +0x100, itself storing
+        // them at +0xa0.
+        let mut xmm = [0; 16];
+        for (n, xmm) in xmm.iter_mut().enumerate() {
+            let offset = 0x1_a0 + (n * 16);
+            *xmm = u128::from_le_bytes(raw[offset..offset + 16].try_into().unwrap());
+        }
+
+        Ok(Self {
+            rax: u64_at(raw, 0x78),
+            rcx: u64_at(raw, 0x80),
+            rdx: u64_at(raw, 0x88),
+            rbx: u64_at(raw, 0x90),
+            rsp: u64_at(raw, 0x98),
+            rbp: u64_at(raw, 0xa0),
+            rsi: u64_at(raw, 0xa8),
+            rdi: u64_at(raw, 0xb0),
+            r8: u64_at(raw, 0xb8),
+            r9: u64_at(raw, 0xc0),
+            r10: u64_at(raw, 0xc8),
+            r11: u64_at(raw, 0xd0),
+            r12: u64_at(raw, 0xd8),
+            r13: u64_at(raw, 0xe0),
+            r14: u64_at(raw, 0xe8),
+            r15: u64_at(raw, 0xf0),
+            rip: u64_at(raw, 0xf8),
+            rflags: u32::from_le_bytes(raw[0x44..0x48].try_into().unwrap()).into(),
+            cs: u16_at(raw, 0x38),
+            ds: u16_at(raw, 0x3a),
+            es: u16_at(raw, 0x3c),
+            fs: u16_at(raw, 0x3e),
+            gs: u16_at(raw, 0x40),
+            ss: u16_at(raw, 0x42),
+            xmm,
+        })
+    }
+}
+
+/// A stack frame along with the CPU context as it was in that frame.
+#[derive(Clone)]
+pub struct FrameContext {
+    /// The stack frame.
+    pub frame: DEBUG_STACK_FRAME,
+    /// The registers as they were in that frame.
+    pub context: X64Context,
+}
+
+bitflags! {
+    /// The Intel x86 `rflags` / `eflags` register.
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Rflags: u64 {
+        /// Carry flag.
+        const CF = 1 << 0;
+        /// Parity flag.
+        const PF = 1 << 2;
+        /// Auxiliary carry flag.
+        const AF = 1 << 4;
+        /// Zero flag.
+        const ZF = 1 << 6;
+        /// Sign flag.
+        const SF = 1 << 7;
+        /// Trap flag.
+        const TF = 1 << 8;
+        /// Interrupt enable flag.
+        const IF = 1 << 9;
+        /// Direction flag.
+        const DF = 1 << 10;
+        /// Overflow flag.
+        const OF = 1 << 11;
+        /// Nested task flag.
+        const NT = 1 << 14;
+        /// Resume flag.
+        const RF = 1 << 16;
+        /// Virtual-8086 mode flag.
+        const VM = 1 << 17;
+        /// Alignment check / access control flag.
+        const AC = 1 << 18;
+        /// Virtual interrupt flag.
+        const VIF = 1 << 19;
+        /// Virtual interrupt pending flag.
+        const VIP = 1 << 20;
+        /// CPUID instruction availability flag.
+        const ID = 1 << 21;
+    }
+}
+
+impl Rflags {
+    /// The I/O privilege level.
+    pub fn iopl(&self) -> u8 {
+        ((self.bits() >> 12) & 0b11) as u8
+    }
+}
+
+/// A 128-bit vector register value (like `xmm0`) with typed views over its
+/// lanes. Lanes are numbered from the least significant one.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Vector128(pub u128);
+
+impl Vector128 {
+    /// Build a vector from its bytes.
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(u128::from_le_bytes(bytes))
+    }
+
+    /// Build a vector from four 32-bit lanes.
+    pub fn from_u32x4(lanes: [u32; 4]) -> Self {
+        let mut bytes = [0; 16];
+        for (chunk, lane) in bytes.chunks_exact_mut(4).zip(lanes) {
+            chunk.copy_from_slice(&lane.to_le_bytes());
+        }
+
+        Self::from_bytes(bytes)
+    }
+
+    /// Build a vector from two 64-bit lanes.
+    pub fn from_u64x2(lanes: [u64; 2]) -> Self {
+        Self(u128::from(lanes[0]) | (u128::from(lanes[1]) << 64))
+    }
+
+    /// Build a vector from four single-precision lanes.
+    pub fn from_f32x4(lanes: [f32; 4]) -> Self {
+        Self::from_u32x4(lanes.map(f32::to_bits))
+    }
+
+    /// Build a vector from two double-precision lanes.
+    pub fn from_f64x2(lanes: [f64; 2]) -> Self {
+        Self::from_u64x2(lanes.map(f64::to_bits))
+    }
+
+    /// The bytes of the vector.
+    pub fn bytes(&self) -> [u8; 16] {
+        self.0.to_le_bytes()
+    }
+
+    /// The vector viewed as four 32-bit lanes.
+    pub fn u32x4(&self) -> [u32; 4] {
+        let bytes = self.bytes();
+        [0, 1, 2, 3].map(|n| u32::from_le_bytes(bytes[n * 4..(n + 1) * 4].try_into().unwrap()))
+    }
+
+    /// The vector viewed as two 64-bit lanes.
+    pub fn u64x2(&self) -> [u64; 2] {
+        [self.0 as u64, (self.0 >> 64) as u64]
+    }
+
+    /// The vector viewed as four single-precision lanes.
+    pub fn f32x4(&self) -> [f32; 4] {
+        self.u32x4().map(f32::from_bits)
+    }
+
+    /// The vector viewed as two double-precision lanes.
+    pub fn f64x2(&self) -> [f64; 2] {
+        self.u64x2().map(f64::from_bits)
+    }
+}
+
+impl From<u128> for Vector128 {
+    fn from(value: u128) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Vector128> for u128 {
+    fn from(value: Vector128) -> Self {
+        value.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RegisterIndexCache, Rflags, Vector128, X64Context, X64_CONTEXT_SIZE};
+
+    #[test]
+    fn index_cache() {
+        let mut cache = RegisterIndexCache::default();
+        cache.sync(0x8664);
+        assert_eq!(cache.get("rax"), None);
+        cache.insert("rax", 0);
+        cache.insert("rip", 16);
+        assert_eq!(cache.get("rip"), Some(16));
+
+        // Syncing to the same processor keeps the indices around.
+        cache.sync(0x8664);
+        assert_eq!(cache.get("rax"), Some(0));
+
+        // Changing processor flushes them.
+        cache.sync(0x14c);
+        assert_eq!(cache.get("rax"), None);
+    }
+
+    #[test]
+    fn rflags() {
+        let flags = Rflags::from_bits_retain(0x3_246);
+        assert!(flags.contains(Rflags::ZF | Rflags::PF | Rflags::IF));
+        assert!(!flags.contains(Rflags::CF));
+        assert_eq!(flags.iopl(), 3);
+        assert_eq!(flags.bits(), 0x3_246);
+    }
+
+    #[test]
+    fn context_bytes() {
+        let mut raw = vec![0; X64_CONTEXT_SIZE];
+        raw[0x38..0x3a].copy_from_slice(&0x33u16.to_le_bytes());
+        raw[0x44..0x48].copy_from_slice(&0x246u32.to_le_bytes());
+        raw[0x78..0x80].copy_from_slice(&0x1337u64.to_le_bytes());
+        raw[0x98..0xa0].copy_from_slice(&0x7ff_f000u64.to_le_bytes());
+        raw[0xf8..0x100].copy_from_slice(&0x7ff6_1234_5678u64.to_le_bytes());
+        raw[0x1_b0..0x1_c0].copy_from_slice(&0xdeadbeefu128.to_le_bytes());
+
+        let context = X64Context::from_context_bytes(&raw).unwrap();
+        assert_eq!(context.cs, 0x33);
+        assert_eq!(context.rflags, 0x246);
+        assert_eq!(context.rax, 0x1337);
+        assert_eq!(context.rsp, 0x7ff_f000);
+        assert_eq!(context.rip, 0x7ff6_1234_5678);
+        assert_eq!(context.xmm[0], 0);
+        assert_eq!(context.xmm[1], 0xdeadbeef);
+        assert!(X64Context::from_context_bytes(&raw[..0x100]).is_err());
+    }
+
+    #[test]
+    fn vector128() {
+        let v = Vector128(0x00112233_44556677_8899aabb_ccddeeff);
+        assert_eq!(v.bytes()[0], 0xff);
+        assert_eq!(v.bytes()[15], 0x00);
+        assert_eq!(v.u32x4(), [0xccddeeff, 0x8899aabb, 0x44556677, 0x00112233]);
+        assert_eq!(v.u64x2(), [0x8899aabb_ccddeeff, 0x00112233_44556677]);
+        assert_eq!(Vector128::from_u32x4(v.u32x4()), v);
+        assert_eq!(Vector128::from_u64x2(v.u64x2()), v);
+        assert_eq!(Vector128::from_bytes(v.bytes()), v);
+
+        let f = Vector128::from_f32x4([1.0, -2.5, 3.25, 0.0]);
+        assert_eq!(f.f32x4(), [1.0, -2.5, 3.25, 0.0]);
+        let d = Vector128::from_f64x2([1.5, -1337.0]);
+        assert_eq!(d.f64x2(), [1.5, -1337.0]);
+    }
+}
@@ -0,0 +1,87 @@
+//! This contains snapshots of the execution state of a thread: its registers
+//! and the memory it is expected to dirty. Restoring a snapshot rewinds the
+//! target, which is what in-debugger fuzzing harnesses loop on.
+//!
+//! ```ignore
+//! // Stopped at the entry of `app!parse(buffer, size)`.
+//! let sp = client.sp()?;
+//! let ranges = [buffer..buffer + size, sp - 0x10_000..sp + 0x1_000];
+//! let snapshot = ExecutionSnapshot::capture(&client, &ranges)?;
+//! loop {
+//!     client.write_virtual_exact(buffer, &mutate(&input))?;
+//!     // Run until `parse` returns (or crashes), then rewind.
+//!     // ...
+//!     snapshot.restore(&client)?;
+//! }
+//! ```
+use std::ops::Range;
+
+use anyhow::{Context, Result};
+use windows::Win32::System::Diagnostics::Debug::Extensions::DEBUG_VALUE;
+
+use crate::client::DebugClient;
+use crate::memory::Snapshot;
+
+/// The registers of a thread and ranges of memory, captured with
+/// [`ExecutionSnapshot::capture`].
+#[derive(Clone)]
+pub struct ExecutionSnapshot {
+    /// The engine ID of the thread whose registers were captured.
+    thread_id: u32,
+    /// The index of every register but the sub-registers.
+    indices: Vec<u32>,
+    /// The value of the registers, in the order of `indices`.
+    values: Vec<DEBUG_VALUE>,
+    memory: Snapshot,
+}
+
+impl ExecutionSnapshot {
+    /// Capture the registers of the current thread and the memory of
+    /// `ranges`, which should cover what the execution dirties (like an input
+    /// buffer, the stack or the data of a module); unreadable pages are
+    /// skipped.
+    pub fn capture(client: &DebugClient, ranges: &[Range<u64>]) -> Result<Self> {
+        let indices = client
+            .registers()?
+            .into_iter()
+            .filter(|register| !register.is_sub_register())
+            .map(|register| register.index)
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            thread_id: client.current_thread()?,
+            values: client.reg_values(&indices)?,
+            indices,
+            memory: Snapshot::capture(client, ranges)?,
+        })
+    }
+
+    /// Get the engine ID of the thread whose registers were captured.
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    /// Get the captured memory.
+    pub fn memory(&self) -> &Snapshot {
+        &self.memory
+    }
+
+    /// Restore the captured memory and the registers of the captured thread;
+    /// the current thread isn't changed. The number of bytes of memory
+    /// written is returned.
+    pub fn restore(&self, client: &DebugClient) -> Result<usize> {
+        let restored = self.memory.restore(client)?;
+        client
+            .with_thread(self.thread_id, |client| {
+                client.set_reg_values(&self.indices, &self.values)
+            })
+            .with_context(|| {
+                format!(
+                    "failed to restore the registers of thread {}",
+                    self.thread_id
+                )
+            })?;
+
+        Ok(restored)
+    }
+}
@@ -0,0 +1,136 @@
+//! This contains types describing a symbolized call stack, like the one
+//! displayed by the `k` command.
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::System::Diagnostics::Debug::Extensions::DEBUG_STACK_FRAME;
+
+use crate::symbol::{SourceLine, SymbolName};
+
+/// A frame of a [`StackTrace`].
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StackFrame {
+    /// The index of the frame; the innermost frame is 0.
+    pub number: u32,
+    /// The address of the current instruction of the frame.
+    pub instruction_offset: u64,
+    /// The address the frame returns to.
+    pub return_offset: u64,
+    /// The frame pointer of the frame.
+    pub frame_offset: u64,
+    /// The stack pointer of the frame (`Child-SP`).
+    pub stack_offset: u64,
+    /// The symbol closest to the instruction, if any.
+    pub symbol: Option<SymbolName>,
+    /// The source line of the instruction, if private symbols are available.
+    pub line: Option<SourceLine>,
+}
+
+impl StackFrame {
+    /// Build a frame without symbols from a raw [`DEBUG_STACK_FRAME`].
+    pub fn from_raw(frame: &DEBUG_STACK_FRAME) -> Self {
+        Self {
+            number: frame.FrameNumber,
+            instruction_offset: frame.InstructionOffset,
+            return_offset: frame.ReturnOffset,
+            frame_offset: frame.FrameOffset,
+            stack_offset: frame.StackOffset,
+            symbol: None,
+            line: None,
+        }
+    }
+}
+
+/// Format an address like the engine does, like `00007ffb`1c2d3e4f`.
+fn engine_addr(addr: u64) -> String {
+    format!("{:08x}`{:08x}", addr >> 32, addr & 0xff_ff_ff_ff)
+}
+
+impl fmt::Display for StackFrame {
+    /// Format the frame like a line of the `k` command.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02x} {} {}     ",
+            self.number,
+            engine_addr(self.stack_offset),
+            engine_addr(self.return_offset)
+        )?;
+
+        match &self.symbol {
+            Some(symbol) => write!(f, "{symbol}")?,
+            None => write!(f, "{:#x}", self.instruction_offset)?,
+        }
+
+        if let Some(line) = &self.line {
+            write!(f, " [{} @ {}]", line.file, line.line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A symbolized call stack; see [`DebugClient::stack_trace`].
+///
+/// [`DebugClient::stack_trace`]: crate::client::DebugClient::stack_trace
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StackTrace {
+    /// The frames, from the innermost to the outermost.
+    pub frames: Vec<StackFrame>,
+}
+
+impl fmt::Display for StackTrace {
+    /// Format the stack like the `k` command.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, " # Child-SP          RetAddr               Call Site")?;
+        for frame in &self.frames {
+            writeln!(f, "{frame}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let trace = StackTrace {
+            frames: vec![
+                StackFrame {
+                    number: 0,
+                    instruction_offset: 0x7ffb_1c2d_3e4f,
+                    return_offset: 0x7ffb_1c2d_0010,
+                    stack_offset: 0x14_f5e8,
+                    symbol: Some(SymbolName {
+                        name: "ntdll!NtWaitForSingleObject".into(),
+                        displacement: 0x14,
+                    }),
+                    line: Some(SourceLine {
+                        file: "wait.c".into(),
+                        line: 12,
+                        displacement: 0,
+                    }),
+                    ..Default::default()
+                },
+                StackFrame {
+                    number: 1,
+                    instruction_offset: 0x1337,
+                    ..Default::default()
+                },
+            ],
+        };
+
+        assert_eq!(
+            trace.to_string(),
+            " # Child-SP          RetAddr               Call Site\n00 00000000`0014f5e8 \
+             00007ffb`1c2d0010     ntdll!NtWaitForSingleObject+0x14 [wait.c @ 12]\n01 \
+             00000000`00000000 00000000`00000000     0x1337\n"
+        );
+    }
+}
@@ -1,9 +1,247 @@
 use std::ffi::CString;
+use std::{fmt, mem};
 
-use anyhow::{Context, Result};
-use windows::Win32::System::Diagnostics::Debug::Extensions::IDebugSymbols3;
+use anyhow::{bail, Context, Result};
+use bitflags::bitflags;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::core::{IUnknown, Interface};
+use windows::Win32::Foundation::E_NOINTERFACE;
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    IDebugSymbols3, DEBUG_ANY_ID, DEBUG_MODNAME_IMAGE, DEBUG_MODNAME_MODULE, DEBUG_MODULE_AND_ID,
+    DEBUG_MODULE_PARAMETERS,
+};
+use windows::Win32::System::Diagnostics::Debug::{
+    UnDecorateSymbolName, IMAGE_DIRECTORY_ENTRY_EXPORT, IMAGE_DIRECTORY_ENTRY_IMPORT,
+    SYMOPT_ALLOW_ABSOLUTE_SYMBOLS, SYMOPT_ALLOW_ZERO_ADDRESS, SYMOPT_AUTO_PUBLICS,
+    SYMOPT_CASE_INSENSITIVE, SYMOPT_DEBUG, SYMOPT_DEFERRED_LOADS, SYMOPT_DISABLE_FAST_SYMBOLS,
+    SYMOPT_DISABLE_SRVSTAR_ON_STARTUP, SYMOPT_DISABLE_SYMSRV_AUTODETECT,
+    SYMOPT_DISABLE_SYMSRV_TIMEOUT, SYMOPT_EXACT_SYMBOLS, SYMOPT_FAIL_CRITICAL_ERRORS,
+    SYMOPT_FAVOR_COMPRESSED, SYMOPT_FLAT_DIRECTORY, SYMOPT_IGNORE_CVREC, SYMOPT_IGNORE_IMAGEDIR,
+    SYMOPT_IGNORE_NT_SYMPATH, SYMOPT_INCLUDE_32BIT_MODULES, SYMOPT_LOAD_ANYTHING,
+    SYMOPT_LOAD_LINES, SYMOPT_NO_CPP, SYMOPT_NO_IMAGE_SEARCH, SYMOPT_NO_PROMPTS, SYMOPT_NO_PUBLICS,
+    SYMOPT_NO_UNQUALIFIED_LOADS, SYMOPT_OMAP_FIND_NEAREST, SYMOPT_OVERWRITE, SYMOPT_PUBLICS_ONLY,
+    SYMOPT_READONLY_CACHE, SYMOPT_SECURE, SYMOPT_SYMPATH_LAST, SYMOPT_UNDNAME, UNDNAME_COMPLETE,
+};
+use windows::Win32::System::SystemServices::{
+    IMAGE_EXPORT_DIRECTORY, IMAGE_IMPORT_DESCRIPTOR, IMAGE_ORDINAL_FLAG32, IMAGE_ORDINAL_FLAG64,
+};
 
 use crate::as_pcstr::AsPCSTR;
+use crate::client::{engine_string, DebugClient};
+use crate::pe::PeImage;
+use crate::typed::TypedValue;
+
+/// Undecorate a symbol name mangled by MSVC, like `?Foo@@YAXXZ` into
+/// `void __cdecl Foo(void)`. A module prefix (`module!`) is preserved, and
+/// names that aren't mangled are returned untouched.
+pub fn undecorate(name: &str) -> Result<String> {
+    let (module, symbol) = match name.split_once('!') {
+        Some((module, symbol)) => (Some(module), symbol),
+        None => (None, name),
+    };
+
+    if !symbol.starts_with('?') {
+        return Ok(name.to_string());
+    }
+
+    let symbol_cstr = CString::new(symbol).context("failed to convert symbol to CString")?;
+    let mut buffer = vec![0; 0x4_00];
+    let len =
+        unsafe { UnDecorateSymbolName(symbol_cstr.as_pcstr(), &mut buffer, UNDNAME_COMPLETE) };
+    if len == 0 {
+        bail!("UnDecorateSymbolName failed for {symbol}");
+    }
+
+    let undecorated = String::from_utf8_lossy(&buffer[..len as usize]);
+
+    Ok(match module {
+        Some(module) => format!("{module}!{undecorated}"),
+        None => undecorated.into_owned(),
+    })
+}
+
+bitflags! {
+    /// The options (`SYMOPT_*`) controlling how the engine loads and
+    /// presents symbols.
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct SymbolOptions: u32 {
+        /// Symbol searches are case insensitive.
+        const CASE_INSENSITIVE = SYMOPT_CASE_INSENSITIVE;
+        /// Undecorate the names of the symbols.
+        const UNDNAME = SYMOPT_UNDNAME;
+        /// Only load the symbols of a module when they are needed.
+        const DEFERRED_LOADS = SYMOPT_DEFERRED_LOADS;
+        const NO_CPP = SYMOPT_NO_CPP;
+        /// Load the line number information.
+        const LOAD_LINES = SYMOPT_LOAD_LINES;
+        const OMAP_FIND_NEAREST = SYMOPT_OMAP_FIND_NEAREST;
+        const LOAD_ANYTHING = SYMOPT_LOAD_ANYTHING;
+        const IGNORE_CVREC = SYMOPT_IGNORE_CVREC;
+        const NO_UNQUALIFIED_LOADS = SYMOPT_NO_UNQUALIFIED_LOADS;
+        const FAIL_CRITICAL_ERRORS = SYMOPT_FAIL_CRITICAL_ERRORS;
+        const EXACT_SYMBOLS = SYMOPT_EXACT_SYMBOLS;
+        const ALLOW_ABSOLUTE_SYMBOLS = SYMOPT_ALLOW_ABSOLUTE_SYMBOLS;
+        const IGNORE_NT_SYMPATH = SYMOPT_IGNORE_NT_SYMPATH;
+        const INCLUDE_32BIT_MODULES = SYMOPT_INCLUDE_32BIT_MODULES;
+        const PUBLICS_ONLY = SYMOPT_PUBLICS_ONLY;
+        const NO_PUBLICS = SYMOPT_NO_PUBLICS;
+        const AUTO_PUBLICS = SYMOPT_AUTO_PUBLICS;
+        const NO_IMAGE_SEARCH = SYMOPT_NO_IMAGE_SEARCH;
+        const SECURE = SYMOPT_SECURE;
+        /// Don't prompt the user (for example for proxy credentials).
+        const NO_PROMPTS = SYMOPT_NO_PROMPTS;
+        const OVERWRITE = SYMOPT_OVERWRITE;
+        const IGNORE_IMAGEDIR = SYMOPT_IGNORE_IMAGEDIR;
+        const FLAT_DIRECTORY = SYMOPT_FLAT_DIRECTORY;
+        const FAVOR_COMPRESSED = SYMOPT_FAVOR_COMPRESSED;
+        const ALLOW_ZERO_ADDRESS = SYMOPT_ALLOW_ZERO_ADDRESS;
+        const DISABLE_SYMSRV_AUTODETECT = SYMOPT_DISABLE_SYMSRV_AUTODETECT;
+        const READONLY_CACHE = SYMOPT_READONLY_CACHE;
+        const SYMPATH_LAST = SYMOPT_SYMPATH_LAST;
+        const DISABLE_FAST_SYMBOLS = SYMOPT_DISABLE_FAST_SYMBOLS;
+        const DISABLE_SYMSRV_TIMEOUT = SYMOPT_DISABLE_SYMSRV_TIMEOUT;
+        const DISABLE_SRVSTAR_ON_STARTUP = SYMOPT_DISABLE_SRVSTAR_ON_STARTUP;
+        /// Print diagnostic information about the symbol loading.
+        const DEBUG = SYMOPT_DEBUG;
+    }
+}
+
+/// Information about a loaded module.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ModuleInfo {
+    /// The name of the module (like `ntdll`).
+    pub name: String,
+    /// The path of the image file of the module.
+    pub image_path: String,
+    /// The base address of the module.
+    pub base: u64,
+    /// The size of the module in memory.
+    pub size: u32,
+    /// The timestamp of the image.
+    pub timestamp: u32,
+    /// The checksum of the image.
+    pub checksum: u32,
+}
+
+/// A symbol name resolved from an address.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SymbolName {
+    /// The qualified name of the symbol (like `nt!KiSystemCall64`).
+    pub name: String,
+    /// The displacement of the address from the symbol.
+    pub displacement: u64,
+}
+
+impl SymbolName {
+    /// Get a copy of the symbol with its name undecorated; see [`undecorate`].
+    pub fn undecorated(&self) -> Result<Self> {
+        Ok(Self {
+            name: undecorate(&self.name)?,
+            displacement: self.displacement,
+        })
+    }
+}
+
+impl fmt::Display for SymbolName {
+    /// Format the symbol as `module!symbol+0x{disp}`; the displacement is
+    /// omitted when it is zero.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if self.displacement != 0 {
+            write!(f, "+{:#x}", self.displacement)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A source location resolved from an address.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SourceLine {
+    /// The path of the source file.
+    pub file: String,
+    /// The line number in the source file.
+    pub line: u32,
+    /// The displacement of the address from the first instruction of the
+    /// line.
+    pub displacement: u64,
+}
+
+impl fmt::Display for SourceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.file, self.line)
+    }
+}
+
+/// A symbol of a module.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ModuleSymbol {
+    /// The qualified name of the symbol (like `nt!KiSystemCall64`).
+    pub name: String,
+    /// The address of the symbol.
+    pub addr: u64,
+}
+
+/// A symbol added with [`crate::client::DebugClient::add_synthetic_symbol`],
+/// which is used to remove it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntheticSymbol(pub(crate) DEBUG_MODULE_AND_ID);
+
+impl SyntheticSymbol {
+    /// Get the base address of the module the symbol was added to.
+    pub fn module_base(&self) -> u64 {
+        self.0.ModuleBase
+    }
+
+    /// Get the ID of the symbol in its module.
+    pub fn id(&self) -> u64 {
+        self.0.Id
+    }
+}
+
+impl ModuleSymbol {
+    /// Get a copy of the symbol with its name undecorated; see [`undecorate`].
+    pub fn undecorated(&self) -> Result<Self> {
+        Ok(Self {
+            name: undecorate(&self.name)?,
+            addr: self.addr,
+        })
+    }
+}
+
+/// An entry of the export directory of a module.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Export {
+    /// The name of the export, if it isn't exported by ordinal only.
+    pub name: Option<String>,
+    /// The ordinal of the export (biased by the ordinal base).
+    pub ordinal: u32,
+    /// The relative virtual address of the export; for forwarded exports, it
+    /// points to the name of the forwardee.
+    pub rva: u32,
+}
+
+/// An IAT slot of a module, described by the import directory.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Import {
+    /// The name of the imported module (like `KERNEL32.dll`).
+    pub module: String,
+    /// The name of the imported function, if it isn't imported by ordinal.
+    pub name: Option<String>,
+    /// The ordinal of the imported function, if it is imported by ordinal.
+    pub ordinal: Option<u16>,
+    /// The address of the IAT slot.
+    pub slot: u64,
+    /// The pointer currently stored in the IAT slot.
+    pub value: u64,
+}
 
 #[derive(Clone)]
 pub struct SymbolModule {
@@ -18,16 +256,281 @@ impl SymbolModule {
         Self { symbols, base }
     }
 
+    /// Get the base address of the module.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Get the parameters of the module.
+    fn parameters(&self) -> Result<DEBUG_MODULE_PARAMETERS> {
+        let mut params = DEBUG_MODULE_PARAMETERS::default();
+        unsafe {
+            self.symbols
+                .GetModuleParameters(1, Some(&self.base), 0, &mut params)
+        }
+        .with_context(|| format!("GetModuleParameters failed for {:#x}", self.base))?;
+
+        Ok(params)
+    }
+
+    /// Get one of the names (`DEBUG_MODNAME_*`) of the module.
+    fn name_string(&self, which: u32) -> Result<String> {
+        engine_string(|buffer, size| unsafe {
+            self.symbols
+                .GetModuleNameString(which, DEBUG_ANY_ID, self.base, buffer, size)
+        })
+        .with_context(|| format!("GetModuleNameString failed for {:#x}", self.base))
+    }
+
+    /// Get the size of the module in memory.
+    pub fn size(&self) -> Result<u32> {
+        Ok(self.parameters()?.Size)
+    }
+
+    /// Get the name of the module (like `ntdll`).
+    pub fn name(&self) -> Result<String> {
+        self.name_string(DEBUG_MODNAME_MODULE)
+    }
+
+    /// Get the path of the image file of the module.
+    pub fn image_name(&self) -> Result<String> {
+        self.name_string(DEBUG_MODNAME_IMAGE)
+    }
+
+    /// Get information about the module.
+    pub fn info(&self) -> Result<ModuleInfo> {
+        let params = self.parameters()?;
+
+        Ok(ModuleInfo {
+            name: self.name()?,
+            image_path: self.image_name()?,
+            base: params.Base,
+            size: params.Size,
+            timestamp: params.TimeDateStamp,
+            checksum: params.Checksum,
+        })
+    }
+
+    /// Get the symbols of the module whose name matches `pattern` (like
+    /// `Nt*`; `*` matches every symbol).
+    pub fn symbols(&self, pattern: &str) -> Result<Vec<ModuleSymbol>> {
+        let pattern = format!("{}!{pattern}", self.name()?);
+        let pattern_cstr = CString::new(pattern).context("failed to convert pattern to CString")?;
+        let handle = unsafe { self.symbols.StartSymbolMatch(pattern_cstr.as_pcstr()) }
+            .context("StartSymbolMatch failed")?;
+
+        let mut symbols = Vec::new();
+        let res = loop {
+            // N.B: The match is only advanced when the name fits in the buffer,
+            // so querying the size of the name first is fine.
+            let mut addr = 0;
+            let name = engine_string(|buffer, size| unsafe {
+                self.symbols
+                    .GetNextSymbolMatch(handle, buffer, size, Some(&mut addr))
+            });
+
+            match name {
+                Ok(name) => symbols.push(ModuleSymbol { name, addr }),
+                // `E_NOINTERFACE` signals that there are no more matches.
+                Err(e)
+                    if e.downcast_ref::<windows::core::Error>()
+                        .is_some_and(|e| e.code() == E_NOINTERFACE) =>
+                {
+                    break Ok(symbols)
+                }
+                Err(e) => break Err(e.context("GetNextSymbolMatch failed")),
+            }
+        };
+
+        unsafe { self.symbols.EndSymbolMatch(handle) }.context("EndSymbolMatch failed")?;
+
+        res
+    }
+
+    /// Get the symbol closest to the relative virtual address `rva` of the
+    /// module.
+    pub fn symbol_by_rva(&self, rva: u32) -> Result<SymbolName> {
+        let addr = self.base + u64::from(rva);
+        let mut displacement = 0;
+        let name = engine_string(|buffer, size| unsafe {
+            self.symbols
+                .GetNameByOffset(addr, buffer, size, Some(&mut displacement))
+        })
+        .with_context(|| format!("GetNameByOffset failed for {addr:#x}"))?;
+
+        Ok(SymbolName { name, displacement })
+    }
+
+    /// Get a [`DebugClient`] to access the memory the module is mapped in.
+    fn client(&self) -> Result<DebugClient> {
+        DebugClient::new(&self.symbols.cast::<IUnknown>()?)
+    }
+
+    /// Get the exports of the module by parsing its export directory in the
+    /// memory of the target.
+    pub fn exports(&self) -> Result<Vec<Export>> {
+        let client = self.client()?;
+        let pe = PeImage::parse(&client, self.base)?;
+        let Some(directory) = pe.data_directory(IMAGE_DIRECTORY_ENTRY_EXPORT) else {
+            return Ok(Vec::new());
+        };
+
+        let export_dir = client.read_virtual_pod::<IMAGE_EXPORT_DIRECTORY>(
+            self.base + u64::from(directory.VirtualAddress),
+        )?;
+
+        let read_array = |rva: u32, n: u32, size: usize| -> Result<Vec<u8>> {
+            let mut buffer = vec![0; n as usize * size];
+            client
+                .read_virtual_exact(self.base + u64::from(rva), &mut buffer)
+                .with_context(|| format!("failed to read the export array at {rva:#x}"))?;
+
+            Ok(buffer)
+        };
+
+        let functions = read_array(
+            export_dir.AddressOfFunctions,
+            export_dir.NumberOfFunctions,
+            4,
+        )?;
+        let names = read_array(export_dir.AddressOfNames, export_dir.NumberOfNames, 4)?;
+        let name_ordinals = read_array(
+            export_dir.AddressOfNameOrdinals,
+            export_dir.NumberOfNames,
+            2,
+        )?;
+
+        // Map the index of every function to its name, if it has one.
+        let mut function_names = vec![None; export_dir.NumberOfFunctions as usize];
+        for (name_rva, index) in names.chunks_exact(4).zip(name_ordinals.chunks_exact(2)) {
+            let name_rva = u32::from_le_bytes(name_rva.try_into()?);
+            let index = usize::from(u16::from_le_bytes(index.try_into()?));
+            let name = client.read_cstring_virtual(self.base + u64::from(name_rva))?;
+            if let Some(function_name) = function_names.get_mut(index) {
+                *function_name = Some(name);
+            }
+        }
+
+        let exports = functions
+            .chunks_exact(4)
+            .zip(function_names)
+            .zip(export_dir.Base..)
+            .filter_map(|((rva, name), ordinal)| {
+                let rva = u32::from_le_bytes(rva.try_into().unwrap());
+                // Unused slots of the function table are zeroed.
+                (rva != 0).then_some(Export { name, ordinal, rva })
+            })
+            .collect();
+
+        Ok(exports)
+    }
+
+    /// Get the IAT slots of the module by walking its import descriptors in
+    /// the memory of the target. The value of every slot can be compared
+    /// against the exports of the imported modules to find hooks.
+    pub fn imports(&self) -> Result<Vec<Import>> {
+        let client = self.client()?;
+        let pe = PeImage::parse(&client, self.base)?;
+        let Some(directory) = pe.data_directory(IMAGE_DIRECTORY_ENTRY_IMPORT) else {
+            return Ok(Vec::new());
+        };
+
+        let (thunk_size, ordinal_flag) = if pe.is_64bit() {
+            (8, IMAGE_ORDINAL_FLAG64)
+        } else {
+            (4, u64::from(IMAGE_ORDINAL_FLAG32))
+        };
+
+        let read_thunk = |addr: u64| -> Result<u64> {
+            let mut buffer = [0; 8];
+            client.read_virtual_exact(addr, &mut buffer[..thunk_size])?;
+
+            Ok(u64::from_le_bytes(buffer))
+        };
+
+        let mut imports = Vec::new();
+        let mut descriptor_addr = self.base + u64::from(directory.VirtualAddress);
+        loop {
+            let descriptor = client.read_virtual_pod::<IMAGE_IMPORT_DESCRIPTOR>(descriptor_addr)?;
+            // The descriptors are terminated by a zeroed one.
+            if descriptor.Name == 0 && descriptor.FirstThunk == 0 {
+                break;
+            }
+
+            let module = client.read_cstring_virtual(self.base + u64::from(descriptor.Name))?;
+
+            // The names live in the import name table; if it is missing, the IAT is
+            // the only source of names and is only usable when it isn't bound.
+            let original_first_thunk = unsafe { descriptor.Anonymous.OriginalFirstThunk };
+            let names_rva = if original_first_thunk != 0 {
+                original_first_thunk
+            } else {
+                descriptor.FirstThunk
+            };
+
+            let mut name_addr = self.base + u64::from(names_rva);
+            let mut slot = self.base + u64::from(descriptor.FirstThunk);
+            loop {
+                let thunk = read_thunk(name_addr)?;
+                if thunk == 0 {
+                    break;
+                }
+
+                let (name, ordinal) = if (thunk & ordinal_flag) != 0 {
+                    (None, Some(thunk as u16))
+                } else {
+                    // Skip the hint of the `IMAGE_IMPORT_BY_NAME`.
+                    let name_addr = self.base + (thunk & 0x7f_ff_ff_ff) + 2;
+                    (Some(client.read_cstring_virtual(name_addr)?), None)
+                };
+
+                imports.push(Import {
+                    module: module.clone(),
+                    name,
+                    ordinal,
+                    slot,
+                    value: read_thunk(slot)?,
+                });
+
+                name_addr += thunk_size as u64;
+                slot += thunk_size as u64;
+            }
+
+            descriptor_addr += mem::size_of::<IMAGE_IMPORT_DESCRIPTOR>() as u64;
+        }
+
+        Ok(imports)
+    }
+
     pub fn get_type(&self, name: &str) -> Result<SymbolType> {
-        let name = CString::new(name).context("failed to convert name to CString")?;
-        let id = unsafe { self.symbols.GetTypeId(self.base, name.as_pcstr()) }
-            .context("failed to get type ID by name")?;
+        let id = self.type_id(name)?;
 
         Ok(SymbolType {
             module: self.clone(),
             id,
         })
     }
+
+    /// Get the type ID of the type `name` (like `_EPROCESS`).
+    pub fn type_id(&self, name: &str) -> Result<u32> {
+        let name_cstr = CString::new(name).context("failed to convert name to CString")?;
+
+        unsafe { self.symbols.GetTypeId(self.base, name_cstr.as_pcstr()) }
+            .with_context(|| format!("failed to get type ID of {name}"))
+    }
+
+    /// Get the size of the type `name` (like `_KTHREAD`).
+    pub fn type_size(&self, name: &str) -> Result<u32> {
+        self.get_type(name)?.size()
+    }
+
+    /// Get the offset of the field `field` in the type `name`, for example
+    /// `field_offset("_EPROCESS", "UniqueProcessId")`.
+    pub fn field_offset(&self, name: &str, field: &str) -> Result<u32> {
+        self.get_type(name)?
+            .get_field_offset(field)
+            .with_context(|| format!("failed to get the offset of {name}.{field}"))
+    }
 }
 
 #[derive(Clone)]
@@ -50,4 +553,15 @@ impl SymbolType {
 
         Ok(offset)
     }
+
+    /// Get the size of this type.
+    pub fn size(&self) -> Result<u32> {
+        unsafe { self.module.symbols.GetTypeSize(self.module.base, self.id) }
+            .context("failed to get type size")
+    }
+
+    /// Get a [`TypedValue`] of this type living at `addr`.
+    pub fn value_at(&self, addr: u64) -> TypedValue {
+        TypedValue::new(self.module.symbols.clone(), self.module.base, self.id, addr)
+    }
 }
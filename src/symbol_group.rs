@@ -0,0 +1,102 @@
+//! This contains a wrapper around symbol groups, which is how the engine
+//! exposes the local variables and the arguments of a function.
+use anyhow::{Context, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use windows::Win32::Foundation::BOOL;
+use windows::Win32::System::Diagnostics::Debug::Extensions::{
+    IDebugSymbolGroup2, DEBUG_SCOPE_GROUP_ALL, DEBUG_SCOPE_GROUP_ARGUMENTS,
+    DEBUG_SCOPE_GROUP_LOCALS, DEBUG_SYMBOL_IS_ARGUMENT, DEBUG_SYMBOL_PARAMETERS,
+};
+
+use crate::client::engine_string;
+
+/// The symbols of the current scope to put in a [`SymbolGroup`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeGroup {
+    /// The arguments of the function.
+    Arguments,
+    /// The local variables of the function.
+    Locals,
+    /// Both the arguments and the local variables of the function.
+    #[default]
+    All,
+}
+
+impl ScopeGroup {
+    pub(crate) fn as_flags(&self) -> u32 {
+        match self {
+            Self::Arguments => DEBUG_SCOPE_GROUP_ARGUMENTS,
+            Self::Locals => DEBUG_SCOPE_GROUP_LOCALS,
+            Self::All => DEBUG_SCOPE_GROUP_ALL,
+        }
+    }
+}
+
+/// A symbol of a [`SymbolGroup`].
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SymbolGroupEntry {
+    /// The name of the symbol.
+    pub name: String,
+    /// The name of the type of the symbol.
+    pub type_name: String,
+    /// The value of the symbol, formatted by the engine.
+    pub value: String,
+    /// Is the symbol an argument of the function?
+    pub is_argument: bool,
+}
+
+/// A group of symbols, like the arguments and local variables of the
+/// function of the current scope.
+pub struct SymbolGroup(pub IDebugSymbolGroup2);
+
+impl SymbolGroup {
+    /// Get the number of symbols in the group.
+    pub fn len(&self) -> Result<u32> {
+        unsafe { self.0.GetNumberSymbols() }.context("GetNumberSymbols failed")
+    }
+
+    /// Is the group empty?
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Get the symbol at `index`.
+    pub fn entry(&self, index: u32) -> Result<SymbolGroupEntry> {
+        let mut params = [DEBUG_SYMBOL_PARAMETERS::default()];
+        unsafe { self.0.GetSymbolParameters(index, &mut params) }
+            .with_context(|| format!("GetSymbolParameters failed for {index}"))?;
+
+        let name =
+            engine_string(|buffer, size| unsafe { self.0.GetSymbolName(index, buffer, size) })
+                .with_context(|| format!("GetSymbolName failed for {index}"))?;
+
+        let type_name =
+            engine_string(|buffer, size| unsafe { self.0.GetSymbolTypeName(index, buffer, size) })
+                .with_context(|| format!("GetSymbolTypeName failed for {index}"))?;
+
+        let value =
+            engine_string(|buffer, size| unsafe { self.0.GetSymbolValueText(index, buffer, size) })
+                .with_context(|| format!("GetSymbolValueText failed for {index}"))?;
+
+        Ok(SymbolGroupEntry {
+            name,
+            type_name,
+            value,
+            is_argument: (params[0].Flags & DEBUG_SYMBOL_IS_ARGUMENT) != 0,
+        })
+    }
+
+    /// Get every symbol of the group.
+    pub fn entries(&self) -> Result<Vec<SymbolGroupEntry>> {
+        (0..self.len()?).map(|index| self.entry(index)).collect()
+    }
+
+    /// Expand (or collapse) the symbol at `index`; its children (like the
+    /// fields of a structure) are inserted in the group right after it.
+    pub fn expand(&self, index: u32, expand: bool) -> Result<()> {
+        unsafe { self.0.ExpandSymbol(index, BOOL::from(expand)) }
+            .with_context(|| format!("ExpandSymbol failed for {index}"))
+    }
+}
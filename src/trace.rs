@@ -0,0 +1,259 @@
+//! This contains a call tracer: it records the invocations of hooked
+//! functions (see [`crate::hooks`]) in an in-memory [`Trace`] that can be
+//! exported as CSV, or as JSON with the `serde` feature.
+//!
+//! ```ignore
+//! let tracer = Tracer::new(CallConv::Win64).with_stack_depth(8);
+//! let hooks = Rc::new(HookManager::new(client.clone()));
+//! hooks.install(tracer.hook("KERNELBASE!VirtualAlloc", 4))?;
+//! client.set_event_callbacks(Rc::clone(&hooks))?;
+//! // ...
+//! tracer.trace().write_csv(File::create("trace.csv")?)?;
+//! ```
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::mem;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::callconv::CallConv;
+use crate::hooks::Hook;
+
+/// A call recorded by a [`Tracer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TraceEntry {
+    /// When the function was entered.
+    pub timestamp: SystemTime,
+    /// The system ID of the thread making the call.
+    pub thread_id: u32,
+    /// The hooked function, like `KERNELBASE!VirtualAlloc`.
+    pub function: String,
+    /// The arguments of the call.
+    pub args: Vec<u64>,
+    /// The value returned by the function; `None` if it hasn't returned
+    /// (yet).
+    pub return_value: Option<u64>,
+    /// The instruction addresses of the call stack when the function was
+    /// entered, from the innermost frame.
+    pub stack: Vec<u64>,
+}
+
+/// The calls recorded by a [`Tracer`], in the order they were made.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Trace {
+    /// The calls.
+    pub entries: Vec<TraceEntry>,
+}
+
+/// Quote a CSV field if it needs to be.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Format a list of values like `0x1;0x2`.
+fn csv_list(values: &[u64]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{v:#x}"))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+impl Trace {
+    /// Get the number of calls.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Is the trace empty?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the trace as CSV, one call per line. The timestamps are in
+    /// microseconds since the UNIX epoch, and the arguments and the stack are
+    /// lists of hexadecimal values separated by `;`.
+    pub fn write_csv<W: Write>(&self, mut w: W) -> Result<()> {
+        writeln!(w, "timestamp,thread_id,function,args,return_value,stack")?;
+        for entry in &self.entries {
+            let timestamp = entry
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .context("the timestamp is before the UNIX epoch")?
+                .as_micros();
+
+            writeln!(
+                w,
+                "{timestamp},{},{},{},{},{}",
+                entry.thread_id,
+                csv_field(&entry.function),
+                csv_list(&entry.args),
+                entry
+                    .return_value
+                    .map_or_else(String::new, |v| format!("{v:#x}")),
+                csv_list(&entry.stack)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the trace as JSON.
+    #[cfg(feature = "serde")]
+    pub fn write_json<W: Write>(&self, w: W) -> Result<()> {
+        serde_json::to_writer_pretty(w, self).context("failed to serialize the trace")
+    }
+}
+
+/// The state shared by a [`Tracer`] and the hooks it creates.
+#[derive(Default)]
+struct TracerState {
+    trace: Trace,
+    /// The index of the entry of the pending calls, by engine thread ID and
+    /// stack pointer at function entry.
+    pending: HashMap<(u32, u64), usize>,
+}
+
+/// Records the calls of the functions hooked with [`Tracer::hook`].
+pub struct Tracer {
+    state: Rc<RefCell<TracerState>>,
+    conv: CallConv,
+    stack_depth: usize,
+}
+
+impl Tracer {
+    /// Create a tracer reading the arguments according to the calling
+    /// convention `conv`.
+    pub fn new(conv: CallConv) -> Self {
+        Self {
+            state: Rc::default(),
+            conv,
+            stack_depth: 0,
+        }
+    }
+
+    /// Record the `depth` innermost frames of the call stack of every call;
+    /// no frame is recorded by default.
+    pub fn with_stack_depth(mut self, depth: usize) -> Self {
+        self.stack_depth = depth;
+
+        self
+    }
+
+    /// Create a [`Hook`] on `target` recording its calls and their first `n`
+    /// arguments; install it with [`crate::hooks::HookManager::install`].
+    pub fn hook(&self, target: &str, n: usize) -> Hook {
+        let conv = self.conv;
+        let stack_depth = self.stack_depth;
+        let enter_state = Rc::clone(&self.state);
+        let exit_state = Rc::clone(&self.state);
+
+        Hook::on(target)
+            .enter(move |ctx| {
+                let client = ctx.client();
+                let stack = if stack_depth > 0 {
+                    client
+                        .context_stack_frames(stack_depth)?
+                        .iter()
+                        .map(|frame| frame.InstructionOffset)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                let entry = TraceEntry {
+                    timestamp: SystemTime::now(),
+                    thread_id: client.get_current_thread_id()?,
+                    function: ctx.name().to_string(),
+                    args: ctx.args(n, conv)?,
+                    return_value: None,
+                    stack,
+                };
+
+                let mut state = enter_state.borrow_mut();
+                let index = state.trace.entries.len();
+                state.trace.entries.push(entry);
+                state
+                    .pending
+                    .insert((ctx.thread_id(), ctx.stack_pointer()), index);
+
+                Ok(())
+            })
+            .exit(move |ctx| {
+                let return_value = ctx.return_value()?;
+                let mut state = exit_state.borrow_mut();
+                let key = (ctx.thread_id(), ctx.stack_pointer());
+                if let Some(index) = state.pending.remove(&key) {
+                    state.trace.entries[index].return_value = Some(return_value);
+                }
+
+                Ok(())
+            })
+    }
+
+    /// Get a copy of the calls recorded so far.
+    pub fn trace(&self) -> Trace {
+        self.state.borrow().trace.clone()
+    }
+
+    /// Take the calls recorded so far, leaving the trace empty.
+    pub fn take(&self) -> Trace {
+        let mut state = self.state.borrow_mut();
+        state.pending.clear();
+
+        mem::take(&mut state.trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn csv() {
+        let trace = Trace {
+            entries: vec![
+                TraceEntry {
+                    timestamp: UNIX_EPOCH + Duration::from_micros(1_337),
+                    thread_id: 0x10,
+                    function: "KERNELBASE!VirtualAlloc".into(),
+                    args: vec![0, 0x1000],
+                    return_value: Some(0x2000),
+                    stack: vec![0x7ffb_0000_1000, 0x7ffb_0000_2000],
+                },
+                TraceEntry {
+                    timestamp: UNIX_EPOCH + Duration::from_secs(1),
+                    thread_id: 0x14,
+                    function: "app!map<int,\"a\">".into(),
+                    args: Vec::new(),
+                    return_value: None,
+                    stack: Vec::new(),
+                },
+            ],
+        };
+
+        let mut csv = Vec::new();
+        trace.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            concat!(
+                "timestamp,thread_id,function,args,return_value,stack\n",
+                "1337,16,KERNELBASE!VirtualAlloc,0x0;0x1000,0x2000,0x7ffb00001000;0x7ffb00002000\n",
+                "1000000,20,\"app!map<int,\"\"a\"\">\",,,\n",
+            )
+        );
+    }
+}
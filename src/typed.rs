@@ -0,0 +1,145 @@
+//! This contains a typed view over the memory of the target, driven by the
+//! type information of the symbols.
+use std::ffi::CString;
+
+use anyhow::{bail, Context, Result};
+use windows::Win32::System::Diagnostics::Debug::Extensions::IDebugSymbols3;
+
+use crate::as_pcstr::AsPCSTR;
+
+/// A value of a known type living at an address in the target, for example
+/// a `nt!_PEB` structure.
+///
+/// Fields can be navigated by name:
+///
+/// ```ignore
+/// let peb = client.typed_value("nt!_PEB", peb_addr)?;
+/// let image_base = peb.field("ImageBaseAddress")?.read_u64()?;
+/// ```
+#[derive(Clone)]
+pub struct TypedValue {
+    /// The debugger symbols interface.
+    symbols: IDebugSymbols3,
+    /// The base address of the module that owns the type.
+    module: u64,
+    /// The type ID of the value.
+    type_id: u32,
+    /// The address of the value.
+    addr: u64,
+}
+
+impl TypedValue {
+    pub(crate) fn new(symbols: IDebugSymbols3, module: u64, type_id: u32, addr: u64) -> Self {
+        Self {
+            symbols,
+            module,
+            type_id,
+            addr,
+        }
+    }
+
+    /// Get the address of the value.
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    /// Get the base address of the module that owns the type of the value.
+    pub fn module(&self) -> u64 {
+        self.module
+    }
+
+    /// Get the type ID of the value.
+    pub fn type_id(&self) -> u32 {
+        self.type_id
+    }
+
+    /// Get the size of the type of the value.
+    pub fn size(&self) -> Result<u32> {
+        unsafe { self.symbols.GetTypeSize(self.module, self.type_id) }.context("GetTypeSize failed")
+    }
+
+    /// Get the field `name` of the value.
+    pub fn field(&self, name: &str) -> Result<TypedValue> {
+        let name_cstr = CString::new(name).context("failed to convert name to CString")?;
+        let mut type_id = 0;
+        let mut offset = 0;
+        unsafe {
+            self.symbols.GetFieldTypeAndOffset(
+                self.module,
+                self.type_id,
+                name_cstr.as_pcstr(),
+                Some(&mut type_id),
+                Some(&mut offset),
+            )
+        }
+        .with_context(|| format!("GetFieldTypeAndOffset failed for {name}"))?;
+
+        Ok(Self::new(
+            self.symbols.clone(),
+            self.module,
+            type_id,
+            self.addr + u64::from(offset),
+        ))
+    }
+
+    /// Read the raw bytes of the value.
+    pub fn read_bytes(&self) -> Result<Vec<u8>> {
+        let size = self.size()?;
+        let mut buffer = vec![0u8; size as usize];
+        let mut amount_read = 0;
+        unsafe {
+            self.symbols.ReadTypedDataVirtual(
+                self.addr,
+                self.module,
+                self.type_id,
+                buffer.as_mut_ptr().cast(),
+                size,
+                Some(&mut amount_read),
+            )
+        }
+        .with_context(|| format!("ReadTypedDataVirtual failed for {:#x}", self.addr))?;
+
+        if amount_read != size {
+            bail!("expected to read_typed {size:#x} bytes, but read {amount_read:#x}");
+        }
+
+        Ok(buffer)
+    }
+
+    /// Read the value as an integer that is at most `N` bytes long; smaller
+    /// values are zero extended.
+    fn read_uint<const N: usize>(&self) -> Result<[u8; N]> {
+        let bytes = self.read_bytes()?;
+        if bytes.len() > N {
+            bail!(
+                "a {:#x} bytes long value doesn't fit in {N:#x} bytes",
+                bytes.len()
+            );
+        }
+
+        let mut value = [0; N];
+        value[..bytes.len()].copy_from_slice(&bytes);
+
+        Ok(value)
+    }
+
+    /// Read the value as a [`u8`].
+    pub fn read_u8(&self) -> Result<u8> {
+        self.read_uint().map(u8::from_le_bytes)
+    }
+
+    /// Read the value as a [`u16`].
+    pub fn read_u16(&self) -> Result<u16> {
+        self.read_uint().map(u16::from_le_bytes)
+    }
+
+    /// Read the value as a [`u32`].
+    pub fn read_u32(&self) -> Result<u32> {
+        self.read_uint().map(u32::from_le_bytes)
+    }
+
+    /// Read the value as a [`u64`]; this is how pointers are read.
+    pub fn read_u64(&self) -> Result<u64> {
+        self.read_uint().map(u64::from_le_bytes)
+    }
+}
@@ -0,0 +1,200 @@
+//! This contains types describing the x64 exception handling data
+//! (`RUNTIME_FUNCTION` & `UNWIND_INFO`), which gives the boundaries and the
+//! prologues of functions.
+use anyhow::{bail, Context, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The function has an exception handler.
+pub const UNW_FLAG_EHANDLER: u8 = 1;
+/// The function has a termination handler.
+pub const UNW_FLAG_UHANDLER: u8 = 2;
+/// The unwind info is chained to the one of another function entry.
+pub const UNW_FLAG_CHAININFO: u8 = 4;
+
+/// A x64 function table entry (`RUNTIME_FUNCTION`), with its relative
+/// virtual addresses converted into virtual addresses.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RuntimeFunction {
+    /// The address of the first instruction of the function.
+    pub begin: u64,
+    /// The address right after the last instruction of the function.
+    pub end: u64,
+    /// The address of the unwind info of the function.
+    pub unwind_info: u64,
+}
+
+impl RuntimeFunction {
+    /// The size of a `RUNTIME_FUNCTION`.
+    pub const SIZE: usize = 12;
+
+    /// Parse a raw `RUNTIME_FUNCTION` of the module mapped at `base`.
+    pub fn parse(base: u64, bytes: &[u8]) -> Result<Self> {
+        let bytes = bytes
+            .get(..Self::SIZE)
+            .context("RUNTIME_FUNCTION is too small")?;
+        let rva = |offset: usize| {
+            let rva = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+            base + u64::from(rva)
+        };
+
+        Ok(Self {
+            begin: rva(0),
+            end: rva(4),
+            unwind_info: rva(8),
+        })
+    }
+
+    /// Get the size of the function; malformed entries, whose end is before
+    /// their start, are empty.
+    pub fn size(&self) -> u64 {
+        self.end.saturating_sub(self.begin)
+    }
+
+    /// Does the function contain `addr`?
+    pub fn contains(&self, addr: u64) -> bool {
+        (self.begin..self.end).contains(&addr)
+    }
+}
+
+/// An unwind code, which describes one operation of the prologue.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnwindCode {
+    /// The offset from the start of the prologue of the end of the
+    /// instruction performing the operation.
+    pub code_offset: u8,
+    /// The operation (`UWOP_*`).
+    pub op: u8,
+    /// The operation information, whose meaning depends on the operation.
+    pub info: u8,
+}
+
+/// The unwind info (`UNWIND_INFO`) of a x64 function.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnwindInfo {
+    /// The version of the structure.
+    pub version: u8,
+    /// The flags (`UNW_FLAG_*`).
+    pub flags: u8,
+    /// The size of the prologue of the function.
+    pub prolog_size: u8,
+    /// The register used as frame pointer (0 if none).
+    pub frame_register: u8,
+    /// The scaled offset of the frame pointer from the stack pointer.
+    pub frame_offset: u8,
+    /// The unwind codes; an operation can use several slots, so this is the
+    /// raw array.
+    pub codes: Vec<UnwindCode>,
+    /// The function entry this unwind info is chained to, if any.
+    pub chained: Option<RuntimeFunction>,
+}
+
+impl UnwindInfo {
+    /// The size of the fixed part of an `UNWIND_INFO`.
+    pub const HEADER_SIZE: usize = 4;
+
+    /// Get the total size of an `UNWIND_INFO` from its header, which is
+    /// enough to read the codes and the chained function entry.
+    pub fn size_from_header(header: &[u8; Self::HEADER_SIZE]) -> usize {
+        let count = usize::from(header[2]);
+        // The array of codes always has an even number of slots.
+        let codes_size = (count + (count & 1)) * 2;
+        let chained_size = if ((header[0] >> 3) & UNW_FLAG_CHAININFO) != 0 {
+            RuntimeFunction::SIZE
+        } else {
+            0
+        };
+
+        Self::HEADER_SIZE + codes_size + chained_size
+    }
+
+    /// Parse a raw `UNWIND_INFO` of the module mapped at `base`.
+    pub fn parse(base: u64, bytes: &[u8]) -> Result<Self> {
+        let Some(header) = bytes.get(..Self::HEADER_SIZE) else {
+            bail!("UNWIND_INFO is too small");
+        };
+
+        let header: &[u8; Self::HEADER_SIZE] = header.try_into().unwrap();
+        if bytes.len() < Self::size_from_header(header) {
+            bail!("UNWIND_INFO is too small");
+        }
+
+        let flags = header[0] >> 3;
+        let count = usize::from(header[2]);
+        let codes_end = Self::HEADER_SIZE + count * 2;
+        let codes = bytes[Self::HEADER_SIZE..codes_end]
+            .chunks_exact(2)
+            .map(|code| UnwindCode {
+                code_offset: code[0],
+                op: code[1] & 0xf,
+                info: code[1] >> 4,
+            })
+            .collect();
+
+        let chained = if (flags & UNW_FLAG_CHAININFO) != 0 {
+            let offset = Self::HEADER_SIZE + (count + (count & 1)) * 2;
+            Some(RuntimeFunction::parse(base, &bytes[offset..])?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            version: header[0] & 0b111,
+            flags,
+            prolog_size: header[1],
+            frame_register: header[3] & 0xf,
+            frame_offset: header[3] >> 4,
+            codes,
+            chained,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let function = RuntimeFunction::parse(0x1000, &[
+            0x00, 0x10, 0, 0, 0x40, 0x10, 0, 0, 0x00, 0x20, 0, 0,
+        ])
+        .unwrap();
+        assert_eq!(function.begin, 0x2000);
+        assert_eq!(function.end, 0x2040);
+        assert_eq!(function.size(), 0x40);
+        assert!(function.contains(0x203f));
+        assert!(!function.contains(0x2040));
+
+        // A malformed entry ending before it starts is empty.
+        let malformed = RuntimeFunction::parse(0x1000, &[
+            0x40, 0x10, 0, 0, 0x00, 0x10, 0, 0, 0x00, 0x20, 0, 0,
+        ])
+        .unwrap();
+        assert_eq!(malformed.size(), 0);
+
+        // sub rsp, 28h
+        let bytes = [0x01, 0x04, 0x01, 0x00, 0x04, 0x42, 0x00, 0x00];
+        assert_eq!(UnwindInfo::size_from_header(&[1, 4, 1, 0]), bytes.len());
+        let info = UnwindInfo::parse(0x1000, &bytes).unwrap();
+        assert_eq!(info.version, 1);
+        assert_eq!(info.flags, 0);
+        assert_eq!(info.prolog_size, 4);
+        assert_eq!(info.codes, vec![UnwindCode {
+            code_offset: 4,
+            op: 2,
+            info: 4
+        }]);
+        assert_eq!(info.chained, None);
+
+        let mut bytes = vec![0x01 | (UNW_FLAG_CHAININFO << 3), 0x00, 0x00, 0x00];
+        bytes.extend_from_slice(&[0x00, 0x10, 0, 0, 0x40, 0x10, 0, 0, 0x00, 0x20, 0, 0]);
+        let info = UnwindInfo::parse(0x1000, &bytes).unwrap();
+        assert_eq!(info.chained, Some(function));
+        assert!(UnwindInfo::parse(0x1000, &bytes[..8]).is_err());
+    }
+}